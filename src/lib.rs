@@ -17,8 +17,9 @@
 
 pub mod nvm3;
 pub mod protocol;
-use std::ffi::{c_char, c_void, CStr};
+use std::ffi::{c_char, c_int, c_void, CStr};
 use std::fmt;
+use std::os::unix::io::FromRawFd;
 
 #[repr(C)]
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -34,7 +35,7 @@ pub enum CpcNvm3ErrorCodes {
     CPC_NVM3_NOT_CLOSED = -4,
     /// An unknown error occured, refer to logs for more details
     CPC_NVM3_UNKNOWN_ERROR = -5,
-    /// An invalid argument was provided
+    /// An invalid argument was provided, or the secondary rejected the arguments of the operation
     CPC_NVM3_INVALID_ARG = -6,
     /// There is a version mismatch between the lib CPC NVM3 and the CPC NVM3 component on the remote device
     CPC_NVM3_INVALID_VERSION = -7,
@@ -46,6 +47,101 @@ pub enum CpcNvm3ErrorCodes {
     CPC_NVM3_CPC_ENDPOINT_ERROR = -10,
     /// The read provided buffer is too small
     CPC_NVM3_BUFFER_TOO_SMALL = -11,
+    /// The operation did not complete before its deadline
+    CPC_NVM3_TIMEOUT = -12,
+    /// The operation was cancelled by the user
+    CPC_NVM3_CANCELLED = -13,
+    /// The operation is not supported by the secondary
+    CPC_NVM3_NOT_SUPPORTED = -14,
+    /// The backup file is corrupted or was not written by the CPC NVM3 library
+    CPC_NVM3_BACKUP_CORRUPT = -15,
+    /// The NVM3 storage of the secondary is full
+    CPC_NVM3_STORAGE_FULL = -16,
+    /// The secondary failed to access its NVM3 storage
+    CPC_NVM3_IO_ERROR = -17,
+    /// The NVM3 module of the secondary is not opened yet, try again later
+    CPC_NVM3_SECONDARY_NOT_READY = -18,
+    /// An object already exists with the provided NVM3 object key
+    CPC_NVM3_ALREADY_EXISTS = -19,
+    /// The secondary rejected the size of the object for its NVM3 configuration, the payload
+    /// must be shrunk. Sizes beyond the limits negotiated when opening the instance are
+    /// rejected before reaching the secondary, with CPC_NVM3_INVALID_ARG, except for an
+    /// object that `cpc_nvm3_append_data` would grow past them.
+    CPC_NVM3_OBJECT_TOO_LARGE = -20,
+    /// A lock of the library was poisoned by a panic in another thread, the state it guards
+    /// can't be trusted anymore. This is reported with and without the `fair-lock` feature.
+    /// A poisoned instance can only be released with `cpc_nvm3_deinit_force`.
+    CPC_NVM3_INTERNAL_POISONED = -21,
+    /// The object exists but is not of the type the operation expects, like a counter
+    /// accessed as a data object
+    CPC_NVM3_WRONG_OBJECT_TYPE = -22,
+    /// The connection to the CPC endpoint was lost and the automatic reconnection is disabled
+    CPC_NVM3_CONNECTION_LOST = -23,
+}
+
+/// Feature set supported by every secondary
+pub const CPC_NVM3_FEATURES_BASE: u32 = 0;
+/// The secondary reports its maximum object size. Without it, the maximum write size
+/// stands in for the maximum object size
+pub const CPC_NVM3_FEATURE_MAX_OBJECT_SIZE: u32 = 1 << 0;
+
+/// Identifiers of the commands sent to the secondary, as carried on the wire
+pub const CPC_NVM3_COMMAND_GET_VERSION: u8 = 0x00;
+pub const CPC_NVM3_COMMAND_NOOP: u8 = 0x03;
+pub const CPC_NVM3_COMMAND_PROP_VALUE_GET: u8 = 0x04;
+pub const CPC_NVM3_COMMAND_WRITE_DATA: u8 = 0x06;
+pub const CPC_NVM3_COMMAND_READ_DATA: u8 = 0x08;
+pub const CPC_NVM3_COMMAND_GET_OBJECT_INFO: u8 = 0x0A;
+pub const CPC_NVM3_COMMAND_READ_COUNTER: u8 = 0x0C;
+pub const CPC_NVM3_COMMAND_WRITE_COUNTER: u8 = 0x0E;
+pub const CPC_NVM3_COMMAND_INCREMENT_COUNTER: u8 = 0x0F;
+pub const CPC_NVM3_COMMAND_DELETE_OBJECT: u8 = 0x10;
+pub const CPC_NVM3_COMMAND_ENUMERATE_OBJECTS: u8 = 0x11;
+pub const CPC_NVM3_COMMAND_GET_OBJECT_COUNT: u8 = 0x13;
+
+/// Size limits negotiated with the secondary when the CPC NVM3 instance was opened
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct CpcNvm3Limits {
+    /// Maximum size of the data passed to a single write
+    pub max_write_size: u16,
+    /// Maximum size of an NVM3 object. This is the maximum write size when the
+    /// secondary does not report it, see `cpc_nvm3_get_maximum_object_size`.
+    pub max_object_size: u16,
+    /// Maximum size of the data carried by a single write fragment
+    pub max_fragment_size: u16,
+    /// Maximum size of a CPC frame written to the endpoint
+    pub cpc_max_write: u16,
+}
+
+/// Size of `CpcNvm3SessionInfo::cpcd_instance_name`, NUL terminator included
+pub const CPC_NVM3_SESSION_INSTANCE_NAME_SIZE: usize = 64;
+
+/// Description of an opened session, see `cpc_nvm3_get_session_info`
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CpcNvm3SessionInfo {
+    /// Major, minor and patch version of the NVM3 protocol of the secondary
+    pub secondary_version: [u8; 3],
+    /// Size limits negotiated while opening the instance
+    pub limits: CpcNvm3Limits,
+    /// Features supported by the secondary, a combination of the CPC_NVM3_FEATURE_* flags
+    pub features: u32,
+    /// NUL-terminated name of the cpcd instance, truncated if it does not fit
+    pub cpcd_instance_name: [c_char; CPC_NVM3_SESSION_INSTANCE_NAME_SIZE],
+}
+
+/// Size of `CpcNvm3Result::message`, NUL terminator included
+pub const CPC_NVM3_RESULT_MESSAGE_SIZE: usize = 128;
+
+/// Outcome of an operation, filled by the `_r` variants like `cpc_nvm3_write_data_r`
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CpcNvm3Result {
+    /// 0 on success, otherwise the negative CpcNvm3ErrorCodes also returned by the function
+    pub code: i32,
+    /// NUL-terminated description of the error, truncated if it does not fit. Empty on success.
+    pub message: [c_char; CPC_NVM3_RESULT_MESSAGE_SIZE],
 }
 
 #[repr(C)]
@@ -72,17 +168,35 @@ pub enum CpcNvm3LogLevel {
     CPC_NVM3_LOG_TRACE,
 }
 
+impl CpcNvm3ObjectType {
+    // The strings are NUL terminated so they can be handed to C as-is
+    fn as_nul_terminated_str(&self) -> &'static str {
+        match self {
+            CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER => "COUNTER\0",
+            CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA => "DATA\0",
+            CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN => "UNKNOWN\0",
+        }
+    }
+}
+
 impl fmt::Display for CpcNvm3ObjectType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let variant_str = match self {
-            CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER => "COUNTER",
-            CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA => "DATA",
-            CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN => "UNKNOWN",
-        };
+        let variant_str = self.as_nul_terminated_str().trim_end_matches('\0');
         write!(f, "{}", variant_str)
     }
 }
 
+/// @brief Get the text form of an NVM3 object type.
+///
+/// @param[in]  object_type  The object type to convert.
+///
+/// @return A static NUL terminated string: "COUNTER", "DATA" or "UNKNOWN".
+///         The string must not be freed or modified.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_object_type_str(object_type: CpcNvm3ObjectType) -> *const c_char {
+    object_type.as_nul_terminated_str().as_ptr() as *const c_char
+}
+
 /// @brief Initialize the logger for the CPC NVM3 library. The user must provide
 ///        the desired log level and the output destination file.
 ///
@@ -98,8 +212,8 @@ impl fmt::Display for CpcNvm3ObjectType {
 /// @param[in]  append     A boolean that indicates whether to append to the log file if provided
 ///                        or to overwrite the existing content (if false).
 ///
-/// @note The logger can only be initialized once. Attempting to initialize the logger
-///       when it has already been initialized will be ignored.
+/// @note Attempting to initialize the logger when it has already been initialized will be
+///       ignored. Call cpc_nvm3_shutdown_logger first to change the output.
 #[no_mangle]
 pub extern "C" fn cpc_nvm3_init_logger(
     prefix: *const c_char,
@@ -134,12 +248,26 @@ pub extern "C" fn cpc_nvm3_init_logger(
 
     match nvm3::init_logger(prefix_string, level, file_path_string_option, append) {
         Ok(_) => 0,
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("{}", err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Shut down the logger of the CPC NVM3 library. The log file is flushed and closed,
+///        and the logs are discarded until cpc_nvm3_init_logger is called again.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Shutting down a logger that was never initialized does nothing.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_shutdown_logger() -> i32 {
+    match nvm3::shutdown_logger() {
+        Ok(_) => 0,
+        Err(err) => err.code() as i32,
     }
 }
 
@@ -163,12 +291,10 @@ pub extern "C" fn cpc_nvm3_init(handle: *mut nvm3::cpc_nvm3_handle_t) -> i32 {
             unsafe { *handle = nvm3_handle };
             0
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("{}", err);
+            err.code() as i32
+        }
     }
 }
 
@@ -187,22 +313,80 @@ pub extern "C" fn cpc_nvm3_init(handle: *mut nvm3::cpc_nvm3_handle_t) -> i32 {
 pub extern "C" fn cpc_nvm3_deinit(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i32 {
     match nvm3::deinit(cpc_nvm3_handle) {
         Ok(_) => 0,
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Deinitialize the specified CPC NVM3 instance, closing it first if it is still opened.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance to be deinitialized.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Unlike `cpc_nvm3_deinit`, this function does not require `cpc_nvm3_close` to be
+///       called first. A warning is logged when an opened instance gets closed. It also
+///       releases an instance that returns CPC_NVM3_INTERNAL_POISONED.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_deinit_force(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i32 {
+    match nvm3::deinit_force(cpc_nvm3_handle) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Interrupt the operation in flight on a CPC NVM3 instance, from another thread.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+///
+/// @note The interrupted operation returns CPC_NVM3_CANCELLED once one of its reads times
+///       out, which takes at most the read timeout set with `cpc_nvm3_set_cpc_timeout`. This
+///       function returns right away and does nothing when no operation is in flight.
+/// @note A read that receives a frame is not interrupted, so an operation whose responses
+///       keep arriving completes normally. A read that fails for another reason returns its
+///       own error, a lost connection is reconnected as usual.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_interrupt(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i32 {
+    match nvm3::interrupt(cpc_nvm3_handle) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("{}", err);
+            err.code() as i32
+        }
     }
 }
 
+/// @brief Check whether a handle refers to an initialized CPC NVM3 instance.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to check.
+///
+/// @return true if the handle was returned by `cpc_nvm3_init` and has not been released by
+///         `cpc_nvm3_deinit` since, false otherwise.
+///
+/// @note Handles are never reused by `cpc_nvm3_init`, so a handle that was deinitialized
+///       stays invalid.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_handle_is_valid(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> bool {
+    nvm3::handle_is_valid(cpc_nvm3_handle)
+}
+
 /// @brief Initialize the CPC NVM3 library.
 ///        Upon success the user will get a handle that must be passed to subsequent calls.
 ///
 /// @param[in]  instance_name    The name of the daemon instance. It will be the value of the instance_name in the config file of the daemon.
 ///                              This value can be NULL, and so the default "cpcd_0" value will be used. If running a single instance, this can
 ///                              be left to NULL, but when running simultaneous instances, it will need to be supplied.
-/// @param[in]  instance_id      The id of the NVM3 instance.
 /// @param[in]  enable_tracing   Enable tracing
 ///
 /// @return On success, the function returns 0. On error, it returns a negative value.
@@ -230,12 +414,230 @@ pub extern "C" fn cpc_nvm3_open(
 
     match nvm3::open(cpc_nvm3_handle, instance_name, enable_cpc_traces) {
         Ok(_) => return 0,
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Same as `cpc_nvm3_open`, but bounds the whole call, from connecting to CPCd to the end
+///        of the handshake with the secondary, by a deadline.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpcd_instance_name   The name of the daemon instance.
+/// @param[in]  enable_cpc_traces    Enable tracing
+/// @param[in]  open_timeout_ms      The time the call is allowed to take, in milliseconds.
+///                                  `cpc_nvm3_open` uses 15 seconds.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the call does not
+///         complete in time, the function returns CPC_NVM3_TIMEOUT.
+///
+/// @note While opening, no read, including those of the responses dropped along the way,
+///       waits longer than the time left before the deadline, nor longer than the default
+///       read timeout set with `cpc_nvm3_set_default_cpc_timeout`. Once opened, the read
+///       timeout is back to that default and can be changed with `cpc_nvm3_set_cpc_timeout`.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_open_ex(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpcd_instance_name: *const c_char,
+    enable_cpc_traces: bool,
+    open_timeout_ms: u32,
+) -> i32 {
+    if cpcd_instance_name.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(cpcd_instance_name) };
+    let instance_name = match c_str.to_str() {
+        Ok(name) => name,
+        Err(err) => {
+            log::error!(
+                "Failed to convert cpcd_instance_name to string. {}",
+                err.to_string()
+            );
+            return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+        }
+    };
+
+    match nvm3::open_ex(
+        cpc_nvm3_handle,
+        instance_name,
+        enable_cpc_traces,
+        std::time::Duration::from_millis(open_timeout_ms as u64),
+    ) {
+        Ok(_) => return 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Same as `cpc_nvm3_open`, but retries while CPCd or the NVM3 endpoint is not reachable yet.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpcd_instance_name   The name of the daemon instance.
+/// @param[in]  enable_cpc_traces    Enable tracing
+/// @param[in]  max_attempts         The maximum number of times the open is attempted, at least 1.
+/// @param[in]  delay_ms             The time to wait between two attempts, in milliseconds.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. When every attempt fails,
+///         the error of the last one is returned.
+///
+/// @note Only the failures reported as CPC_NVM3_CPC_ENDPOINT_ERROR or CPC_NVM3_TRY_AGAIN are
+///       retried, which is how a CPCd that is not running yet is reported. This is meant for
+///       hosts that may start before CPCd at boot. The call blocks for the whole retry period.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_open_with_retry(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpcd_instance_name: *const c_char,
+    enable_cpc_traces: bool,
+    max_attempts: u32,
+    delay_ms: u32,
+) -> i32 {
+    if cpcd_instance_name.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(cpcd_instance_name) };
+    let instance_name = match c_str.to_str() {
+        Ok(name) => name,
+        Err(err) => {
+            log::error!(
+                "Failed to convert cpcd_instance_name to string. {}",
+                err.to_string()
+            );
+            return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+        }
+    };
+
+    match nvm3::open_with_retry(
+        cpc_nvm3_handle,
+        instance_name,
+        enable_cpc_traces,
+        max_attempts,
+        std::time::Duration::from_millis(delay_ms as u64),
+    ) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Set a label for the CPC NVM3 instance. The label prefixes the logs emitted by
+///        the operations on this instance, which tells them apart when several instances are used.
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  label            The label, at most 32 bytes long. An empty string or NULL restores
+///                              the default label, which is the handle number.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_instance_label(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    label: *const c_char,
+) -> i32 {
+    let label = if label.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(label) }.to_str() {
+            Ok(label) => label,
+            Err(err) => {
+                log::error!("Failed to convert label to string. {}", err.to_string());
+                return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+            }
+        }
+    };
+
+    match nvm3::set_instance_label(cpc_nvm3_handle, label) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Get the name of the cpcd instance the CPC NVM3 instance was opened with.
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[out] buffer           Pointer to the buffer where the NUL-terminated name will be stored.
+/// @param[in]  buffer_size      The size of the buffer, including the NUL terminator.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. If the instance was
+///         never opened, the function returns CPC_NVM3_NOT_OPEN. If the name and its NUL
+///         terminator don't fit in the buffer, the function returns CPC_NVM3_BUFFER_TOO_SMALL.
+///
+/// @note The name is kept after `cpc_nvm3_close`, until the instance is opened again or
+///       de-initialized.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_instance_name(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    buffer: *mut c_char,
+    buffer_size: usize,
+) -> i32 {
+    if buffer.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_instance_name(cpc_nvm3_handle) {
+        Ok(name) => {
+            if name.len() + 1 > buffer_size {
+                log::error!(
+                    "[{}] The cpcd instance name needs {} bytes, the buffer holds {}",
+                    nvm3::instance_label(cpc_nvm3_handle),
+                    name.len() + 1,
+                    buffer_size
+                );
+                return CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL as i32;
             }
-        },
+            unsafe {
+                std::ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, buffer, name.len());
+                *buffer.add(name.len()) = 0;
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Set the unique ID identifying this host to the secondary. By default, the process ID
+///        is used, which can collide when several hosts share a secondary through different
+///        cpcd instances.
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  unique_id        The unique ID of the host, it must not be 0.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note The unique ID is applied by the next call to `cpc_nvm3_open`, or by the next reconnection
+///       to the CPC endpoint. Changing it after the instance was opened has no effect until then.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_unique_id(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    unique_id: u32,
+) -> i32 {
+    match nvm3::set_unique_id(cpc_nvm3_handle, unique_id) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
@@ -248,6 +650,10 @@ pub extern "C" fn cpc_nvm3_open(
 /// @return On success, the function returns 0.
 ///         On error, the function returns a negative value, corresponding to a specific
 ///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Closing an instance that is already closed, or that was never opened,
+///       returns CPC_NVM3_NOT_OPEN and leaves the instance untouched. The instance
+///       can still be de-initialized with cpc_nvm3_deinit afterwards.
 #[no_mangle]
 pub extern "C" fn cpc_nvm3_close(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i32 {
     match nvm3::close(cpc_nvm3_handle) {
@@ -255,12 +661,10 @@ pub extern "C" fn cpc_nvm3_close(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i3
             log::debug!("Closed instance #{}", cpc_nvm3_handle);
             0
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
@@ -279,6 +683,11 @@ pub extern "C" fn cpc_nvm3_close(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i3
 ///
 /// @note The buffer is not copied. The user must ensure the data buffer is not modified during the write operation.
 /// @note This API will return CPC_NVM3_TRY_AGAIN if another process is writing to the same object.
+/// @note `data_length` is checked against the maximum object size before the buffer is accessed,
+///       but the library cannot verify the size of the buffer itself. The user must ensure
+///       `data_ptr` points to at least `data_length` bytes.
+/// @note A size within the negotiated limits can still be rejected by the NVM3 configuration of
+///       the secondary, in which case the function returns CPC_NVM3_OBJECT_TOO_LARGE.
 #[no_mangle]
 pub extern "C" fn cpc_nvm3_write_data(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
@@ -294,6 +703,9 @@ pub extern "C" fn cpc_nvm3_write_data(
         log::error!("data_ptr must not be NULL");
         return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
     }
+    if let Err(error_code) = validate_write_length(cpc_nvm3_handle, data_length) {
+        return error_code;
+    }
     let data: &[u8] = unsafe { std::slice::from_raw_parts(data_ptr, data_length as usize) };
 
     match nvm3::write_data(cpc_nvm3_handle, cpc_nvm3_object_key, data) {
@@ -304,41 +716,663 @@ pub extern "C" fn cpc_nvm3_write_data(
             );
             return 0;
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
-/// @brief Read data from the specified object in the CPC NVM3 library.
-///        The user must provide a valid handle obtained from the initialization process.
+// A length larger than any object can't be legitimate, rejecting it before building the
+// slice bounds how far past the caller's buffer could be read. The check is skipped when
+// the limits are not known yet, the write then fails on its own.
+fn validate_write_length(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    data_length: u16,
+) -> Result<(), i32> {
+    check_write_length(cpc_nvm3_handle, data_length).map_err(|err| {
+        log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+        err.code() as i32
+    })
+}
+
+fn check_write_length(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    data_length: u16,
+) -> Result<(), nvm3::CpcNvm3Error> {
+    if let Ok(limits) = nvm3::get_limits(cpc_nvm3_handle) {
+        if data_length > limits.max_object_size {
+            return Err(nvm3::CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                format!(
+                    "data_length ({}) exceeds the maximum object size ({})",
+                    data_length, limits.max_object_size
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Fill the result of an `_r` variant, which is optional, and return its code
+fn report_result(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    result: *mut CpcNvm3Result,
+    outcome: Result<(), nvm3::CpcNvm3Error>,
+) -> i32 {
+    let (code, message) = match outcome {
+        Ok(()) => (0, String::new()),
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            (err.code() as i32, err.to_string())
+        }
+    };
+    if result.is_null() {
+        return code;
+    }
+
+    let mut report = CpcNvm3Result {
+        code,
+        message: [0; CPC_NVM3_RESULT_MESSAGE_SIZE],
+    };
+    // The message is NUL-terminated and truncated on a character boundary to fit
+    let mut message_len = message.len().min(CPC_NVM3_RESULT_MESSAGE_SIZE - 1);
+    while !message.is_char_boundary(message_len) {
+        message_len -= 1;
+    }
+    for (dst, src) in report
+        .message
+        .iter_mut()
+        .zip(&message.as_bytes()[..message_len])
+    {
+        *dst = *src as c_char;
+    }
+    unsafe { *result = report };
+    code
+}
+
+fn invalid_arg(context: &str) -> nvm3::CpcNvm3Error {
+    nvm3::CpcNvm3Error::ErrorCodeWithContext(
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+        context.to_string(),
+    )
+}
+
+/// @brief Write data to the specified object, like `cpc_nvm3_write_data`, and describe the
+///        outcome in a `CpcNvm3Result`.
 ///
 /// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
-/// @param[in]  cpc_nvm3_object_key  The key of the object to read data from.
-/// @param[out] buffer_ptr              A pointer to the buffer where the read data will be stored.
-/// @param[in]  buffer_size             The size of the provided buffer.
-/// @param[out] object_size             A pointer to a variable where the actual size of the NVM3 object will be stored.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to write data to.
+/// @param[in]  data_ptr             A pointer to the data buffer to be written.
+/// @param[in]  data_length          The length of the data to be written.
+/// @param[out] result               A pointer to the result filled with the returned code and,
+///                                  on error, its context. This value is optional, when a NULL
+///                                  pointer is provided, it will be ignored.
 ///
 /// @return On success, the function returns 0. On error, it returns a negative value.
 ///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
-///         indicating the type of error that occurred. If the connection to the CPC
-///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///         indicating the type of error that occurred.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_write_data_r(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    data_ptr: *const u8,
+    data_length: u16,
+    result: *mut CpcNvm3Result,
+) -> i32 {
+    let outcome = if data_length == 0 {
+        Err(invalid_arg("data_length must not be 0"))
+    } else if data_ptr.is_null() {
+        Err(invalid_arg("data_ptr must not be NULL"))
+    } else {
+        check_write_length(cpc_nvm3_handle, data_length).and_then(|_| {
+            let data: &[u8] = unsafe { std::slice::from_raw_parts(data_ptr, data_length as usize) };
+            nvm3::write_data(cpc_nvm3_handle, cpc_nvm3_object_key, data).map(|_| ())
+        })
+    };
+    report_result(cpc_nvm3_handle, result, outcome)
+}
+
+/// @brief Read data from the specified object, like `cpc_nvm3_read_data`, and describe the
+///        outcome in a `CpcNvm3Result`.
 ///
-/// @note The user must ensure the provided buffer is large enough to hold the read data.
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to read data from.
+/// @param[out] buffer_ptr           A pointer to the buffer where the read data will be stored.
+/// @param[in]  buffer_size          The size of the provided buffer.
+/// @param[out] object_size          A pointer to a variable where the actual size of the NVM3
+///                                  object will be stored.
+/// @param[out] result               A pointer to the result filled with the returned code and,
+///                                  on error, its context. This value is optional, when a NULL
+///                                  pointer is provided, it will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
 #[no_mangle]
-pub extern "C" fn cpc_nvm3_read_data(
+pub extern "C" fn cpc_nvm3_read_data_r(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
     cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
     buffer_ptr: *mut c_void,
     buffer_size: u16,
     object_size: *mut u16,
+    result: *mut CpcNvm3Result,
 ) -> i32 {
-    if buffer_ptr.is_null() || object_size.is_null() {
-        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
-    }
+    let outcome = if buffer_ptr.is_null() || object_size.is_null() {
+        Err(invalid_arg("buffer_ptr and object_size must not be NULL"))
+    } else {
+        let buffer =
+            unsafe { std::slice::from_raw_parts_mut(buffer_ptr as *mut u8, buffer_size as usize) };
+        let data_size_ref: &mut u16 = unsafe { &mut *object_size };
+        nvm3::read_data(cpc_nvm3_handle, cpc_nvm3_object_key, buffer, data_size_ref)
+    };
+    report_result(cpc_nvm3_handle, result, outcome)
+}
+
+/// @brief Write the specified counter, like `cpc_nvm3_write_counter`, and describe the
+///        outcome in a `CpcNvm3Result`.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter.
+/// @param[in]  value                The value to write.
+/// @param[out] result               A pointer to the result filled with the returned code and,
+///                                  on error, its context. This value is optional, when a NULL
+///                                  pointer is provided, it will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_write_counter_r(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: u32,
+    result: *mut CpcNvm3Result,
+) -> i32 {
+    let outcome = nvm3::write_counter(cpc_nvm3_handle, cpc_nvm3_object_key, value);
+    report_result(cpc_nvm3_handle, result, outcome)
+}
+
+/// @brief Read the specified counter, like `cpc_nvm3_read_counter`, and describe the
+///        outcome in a `CpcNvm3Result`.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter.
+/// @param[out] value                A pointer to the variable where the counter data will be stored.
+/// @param[out] result               A pointer to the result filled with the returned code and,
+///                                  on error, its context. This value is optional, when a NULL
+///                                  pointer is provided, it will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_counter_r(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: *mut u32,
+    result: *mut CpcNvm3Result,
+) -> i32 {
+    let outcome = if value.is_null() {
+        Err(invalid_arg("value must not be NULL"))
+    } else {
+        nvm3::read_counter(cpc_nvm3_handle, cpc_nvm3_object_key)
+            .map(|read_value| unsafe { *value = read_value })
+    };
+    report_result(cpc_nvm3_handle, result, outcome)
+}
+
+/// @brief Delete the specified object, like `cpc_nvm3_delete_object`, and describe the
+///        outcome in a `CpcNvm3Result`.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to delete.
+/// @param[out] result               A pointer to the result filled with the returned code and,
+///                                  on error, its context. This value is optional, when a NULL
+///                                  pointer is provided, it will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_delete_object_r(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    result: *mut CpcNvm3Result,
+) -> i32 {
+    let outcome = nvm3::delete_object(cpc_nvm3_handle, cpc_nvm3_object_key);
+    report_result(cpc_nvm3_handle, result, outcome)
+}
+
+/// @brief Write data to the specified object in the CPC NVM3 library and report
+///        how many fragments were needed to transfer it and how many bytes were written.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to write data to.
+/// @param[in]  data_ptr                A pointer to the data buffer to be written.
+/// @param[in]  data_length             The length of the data to be written.
+/// @param[out] fragment_count          A pointer to the variable where the number of fragments
+///                                     will be stored. This value is optional, when a NULL pointer
+///                                     is provided, it will be ignored.
+/// @param[out] bytes_written           A pointer to the variable where the number of bytes written
+///                                     will be stored. This value is optional, when a NULL pointer
+///                                     is provided, it will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note The number of fragments depends on the maximum fragment size negotiated when
+///       the instance was opened. A write that fits in a single fragment reports 1.
+/// @note `bytes_written` is the length of the fragments the secondary acknowledged, also on
+///       error. The secondary only commits the object once its last fragment is received, so
+///       the object is written when it is `data_length`. A smaller value tells how far the
+///       transfer went before it failed, the object is left as it was.
+/// @note As with `cpc_nvm3_write_data`, the user must ensure `data_ptr` points to at least
+///       `data_length` bytes.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_write_data_ex(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    data_ptr: *const u8,
+    data_length: u16,
+    fragment_count: *mut u16,
+    bytes_written: *mut u16,
+) -> i32 {
+    if !bytes_written.is_null() {
+        unsafe { *bytes_written = 0 };
+    }
+    if data_length == 0 {
+        log::error!("data_length must not be 0");
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    if data_ptr.is_null() {
+        log::error!("data_ptr must not be NULL");
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    if let Err(error_code) = validate_write_length(cpc_nvm3_handle, data_length) {
+        return error_code;
+    }
+    let data: &[u8] = unsafe { std::slice::from_raw_parts(data_ptr, data_length as usize) };
+
+    let mut acknowledged = 0;
+    let result = nvm3::write_data_acknowledged(
+        cpc_nvm3_handle,
+        cpc_nvm3_object_key,
+        data,
+        &mut acknowledged,
+    );
+    if !bytes_written.is_null() {
+        unsafe { *bytes_written = acknowledged };
+    }
+    match result {
+        Ok(count) => {
+            log::debug!(
+                "Successfully wrote to NVM3 data object {:?} in {} fragment(s)",
+                cpc_nvm3_object_key,
+                count
+            );
+            if !fragment_count.is_null() {
+                unsafe { *fragment_count = count };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Append data to the end of a data object, the object is created if it doesn't exist.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to append data to.
+/// @param[in]  data_ptr             A pointer to the data buffer to be appended.
+/// @param[in]  data_length          The length of the data to be appended.
+/// @param[out] object_size          A pointer to the variable where the size of the object
+///                                  after the append will be stored. This value is optional,
+///                                  when a NULL pointer is provided, it will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the object is a counter, the
+///         function returns CPC_NVM3_WRONG_OBJECT_TYPE. If the appended object would exceed
+///         the maximum object size, the function returns CPC_NVM3_OBJECT_TOO_LARGE. In both
+///         cases the object is left untouched.
+///
+/// @note The append is not atomic. NVM3 has no partial writes, so the current content of the
+///       object is read back and the whole object is written again. The instance is locked
+///       during the operation, but a write from another host sharing the secondary in
+///       between is overwritten by the append.
+/// @note As with `cpc_nvm3_write_data`, the user must ensure `data_ptr` points to at least
+///       `data_length` bytes.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_append_data(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    data_ptr: *const u8,
+    data_length: u16,
+    object_size: *mut u16,
+) -> i32 {
+    if data_length == 0 {
+        log::error!("data_length must not be 0");
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    if data_ptr.is_null() {
+        log::error!("data_ptr must not be NULL");
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    if let Err(error_code) = validate_write_length(cpc_nvm3_handle, data_length) {
+        return error_code;
+    }
+    let data: &[u8] = unsafe { std::slice::from_raw_parts(data_ptr, data_length as usize) };
+
+    match nvm3::append_data(cpc_nvm3_handle, cpc_nvm3_object_key, data) {
+        Ok(size) => {
+            log::debug!(
+                "Successfully appended to NVM3 data object {:?}, it is now {} bytes",
+                cpc_nvm3_object_key,
+                size
+            );
+            if !object_size.is_null() {
+                unsafe { *object_size = size };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Write several data objects in one call.
+///
+/// @param[in]  cpc_nvm3_handle    The handle to the CPC NVM3 instance.
+/// @param[in]  keys_ptr           Pointer to an array of `count` object keys.
+/// @param[in]  data_ptrs          Pointer to an array of `count` pointers to the data to write.
+/// @param[in]  data_lengths       Pointer to an array of `count` data lengths.
+/// @param[in]  count              The number of objects to write.
+/// @param[out] results_ptr        Pointer to an array of `count` results. Each entry is 0 if the
+///                                object was written, or a negative CpcNvm3ErrorCodes otherwise.
+/// @param[out] bytes_written_ptr  Pointer to an array of `count` lengths, where the length of the
+///                                fragments the secondary acknowledged for each entry will be
+///                                stored. This value is optional, when a NULL pointer is
+///                                provided, it will be ignored.
+///
+/// @return On success, the function returns 0 and every entry of `results_ptr` is 0.
+///         On error, it returns the negative CpcNvm3ErrorCodes that stopped the batch.
+///
+/// @note The batch stops at the first failing entry. The entries before it are marked 0, while
+///       the failing entry and the ones that were not attempted hold the error code. The
+///       caller can resume the batch from the first non-zero entry of `results_ptr`.
+/// @note As with `cpc_nvm3_write_data_ex`, an entry of `bytes_written_ptr` below the data
+///       length tells how far the transfer of that object went, its object is not written.
+///       The entries that were not attempted get 0.
+/// @note Every data pointer and length is validated before anything is written. If one of them
+///       is invalid, CPC_NVM3_INVALID_ARG is returned and `results_ptr` is left untouched.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_write_data_batch(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    keys_ptr: *const nvm3::cpc_nvm3_object_key_t,
+    data_ptrs: *const *const u8,
+    data_lengths: *const u16,
+    count: u16,
+    results_ptr: *mut i32,
+    bytes_written_ptr: *mut u16,
+) -> i32 {
+    if keys_ptr.is_null() || data_ptrs.is_null() || data_lengths.is_null() || results_ptr.is_null()
+    {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let keys = unsafe { std::slice::from_raw_parts(keys_ptr, count as usize) };
+    let data_ptrs = unsafe { std::slice::from_raw_parts(data_ptrs, count as usize) };
+    let data_lengths = unsafe { std::slice::from_raw_parts(data_lengths, count as usize) };
+    let results = unsafe { std::slice::from_raw_parts_mut(results_ptr, count as usize) };
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for index in 0..count as usize {
+        if data_ptrs[index].is_null() || data_lengths[index] == 0 {
+            log::error!("Entry {} of the batch has no data to write", index);
+            return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+        }
+        if let Err(error_code) = validate_write_length(cpc_nvm3_handle, data_lengths[index]) {
+            return error_code;
+        }
+        let data: &[u8] =
+            unsafe { std::slice::from_raw_parts(data_ptrs[index], data_lengths[index] as usize) };
+        entries.push((keys[index], data));
+    }
+
+    let mut acknowledged = vec![0; count as usize];
+    let outcome = nvm3::write_data_batch(cpc_nvm3_handle, &entries, &mut acknowledged);
+    if !bytes_written_ptr.is_null() {
+        let bytes_written =
+            unsafe { std::slice::from_raw_parts_mut(bytes_written_ptr, count as usize) };
+        bytes_written.copy_from_slice(&acknowledged);
+    }
+    match outcome {
+        Ok(writes) => fill_batch_results(cpc_nvm3_handle, &writes, results),
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            results.fill(err.code() as i32);
+            err.code() as i32
+        }
+    }
+}
+
+/// Report the results of a batch into the caller's array. The entries that were not
+/// attempted get the error code that stopped the batch, which is also returned.
+fn fill_batch_results<T>(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    batch_results: &[Result<T, nvm3::CpcNvm3Error>],
+    results: &mut [i32],
+) -> i32 {
+    let mut status = 0;
+    for (index, result) in batch_results.iter().enumerate() {
+        results[index] = match result {
+            Ok(_) => 0,
+            Err(err) => {
+                log::error!(
+                    "[{}] Batch entry {} failed. {}",
+                    nvm3::instance_label(cpc_nvm3_handle),
+                    index,
+                    err
+                );
+                status = err.code() as i32;
+                status
+            }
+        };
+    }
+    results[batch_results.len()..].fill(status);
+    status
+}
+
+/// @brief Write data read from a file descriptor to the specified object.
+///        The data is read one fragment at a time, so the object is never buffered entirely.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to write data to.
+/// @param[in]  fd                   The file descriptor to read the data from. It is read from its
+///                                  current position and is not closed.
+/// @param[in]  length               The length of the data to be written.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the file descriptor provides
+///         less than `length` bytes, the function returns CPC_NVM3_INVALID_ARG.
+///
+/// @note `length` is validated against the maximum object size and the maximum write size
+///       before anything is read from the file descriptor.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_write_data_from_fd(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    fd: c_int,
+    length: u16,
+) -> i32 {
+    if fd < 0 {
+        log::error!("fd must be a valid file descriptor");
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    // The file descriptor belongs to the caller, it must not be closed when the file is dropped
+    let mut file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+
+    match nvm3::write_data_from_reader(cpc_nvm3_handle, cpc_nvm3_object_key, &mut *file, length) {
+        Ok(count) => {
+            log::debug!(
+                "Successfully wrote to NVM3 data object {:?} in {} fragment(s)",
+                cpc_nvm3_object_key,
+                count
+            );
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Callback invoked by `cpc_nvm3_update_data` with the current content of the object.
+///
+/// @param[in]  current_data        A pointer to the current data of the object. The pointer is only
+///                                 valid for the duration of the callback.
+/// @param[in]  current_size        The size of the current data. This is 0 when the object does not exist.
+/// @param[out] new_data            A pointer to the buffer where the updated data must be written.
+/// @param[in]  new_data_capacity   The size of the `new_data` buffer, which is the maximum write size.
+/// @param[out] new_size            A pointer to the variable where the size of the updated data must be stored.
+/// @param[in]  user_ctx            The user context provided to `cpc_nvm3_update_data`.
+///
+/// @return The callback must return 0 for the updated data to be written. Any other value
+///         aborts the update and nothing is written.
+#[allow(non_camel_case_types)]
+pub type cpc_nvm3_update_data_callback_t = Option<
+    unsafe extern "C" fn(
+        current_data: *const u8,
+        current_size: u16,
+        new_data: *mut u8,
+        new_data_capacity: u16,
+        new_size: *mut u16,
+        user_ctx: *mut c_void,
+    ) -> i32,
+>;
+
+/// @brief Read a data object, let the user transform it and write the result back.
+///        The object is created if it does not exist yet.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to update.
+/// @param[in]  callback             The callback that computes the updated data from the current data.
+/// @param[in]  user_ctx             A user context passed as-is to the callback. This value can be NULL.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the callback aborts the update,
+///         the function returns CPC_NVM3_FAILURE.
+///
+/// @note The read, the callback and the write are performed while holding the lock of the
+///       CPC NVM3 instance, so no other operation on this handle can slip in between. This does
+///       not protect against other handles or other processes accessing the same object.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_update_data(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    callback: cpc_nvm3_update_data_callback_t,
+    user_ctx: *mut c_void,
+) -> i32 {
+    let callback = match callback {
+        Some(callback) => callback,
+        None => {
+            log::error!("callback must not be NULL");
+            return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+        }
+    };
+
+    let new_data_capacity = match nvm3::get_maximum_write_size(cpc_nvm3_handle) {
+        Ok(maximum_write_size) => maximum_write_size,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            return err.code() as i32;
+        }
+    };
+
+    let result = nvm3::try_update_data(cpc_nvm3_handle, cpc_nvm3_object_key, |current_data| {
+        let mut new_data = vec![0u8; new_data_capacity as usize];
+        let mut new_size: u16 = 0;
+        let status = unsafe {
+            callback(
+                current_data.as_ptr(),
+                current_data.len() as u16,
+                new_data.as_mut_ptr(),
+                new_data_capacity,
+                &mut new_size,
+                user_ctx,
+            )
+        };
+        if status != 0 {
+            return Err(nvm3::CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                format!("The update was aborted by the callback ({})", status),
+            ));
+        }
+        if new_size > new_data_capacity {
+            return Err(nvm3::CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                format!(
+                    "The callback reported {} bytes but the buffer only holds {} bytes",
+                    new_size, new_data_capacity
+                ),
+            ));
+        }
+        new_data.truncate(new_size as usize);
+        Ok(new_data)
+    });
+
+    match result {
+        Ok(_) => {
+            log::debug!(
+                "Successfully updated NVM3 data object {:?}",
+                cpc_nvm3_object_key
+            );
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Read data from the specified object in the CPC NVM3 library.
+///        The user must provide a valid handle obtained from the initialization process.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to read data from.
+/// @param[out] buffer_ptr              A pointer to the buffer where the read data will be stored.
+/// @param[in]  buffer_size             The size of the provided buffer.
+/// @param[out] object_size             A pointer to a variable where the actual size of the NVM3 object will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note The user must ensure the provided buffer is large enough to hold the read data.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_data(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    buffer_ptr: *mut c_void,
+    buffer_size: u16,
+    object_size: *mut u16,
+) -> i32 {
+    if buffer_ptr.is_null() || object_size.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
 
     let buffer =
         unsafe { std::slice::from_raw_parts_mut(buffer_ptr as *mut u8, buffer_size as usize) };
@@ -349,31 +1383,492 @@ pub extern "C" fn cpc_nvm3_read_data(
             log::debug!("Successfully read NVM3 object");
             return 0;
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
-/// @brief Retrieve the count of objects stored in the specified CPC NVM3 instance.
+/// @brief Read data from the specified object, truncated to the size of the provided buffer.
 ///
-/// @param[in]  cpc_nvm3_handle     The handle to the CPC NVM3 instance.
-/// @param[out] object_count        Pointer to a variable where the total count
-///                                 of stored objects will be written.
-///                                 The value at this pointer will be updated
-///                                 only if the function is successful.
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to read data from.
+/// @param[out] buffer_ptr           A pointer to the buffer where the read data will be stored.
+///                                  It can be NULL when `buffer_size` is 0.
+/// @param[in]  buffer_size          The size of the provided buffer.
+/// @param[out] full_size            A pointer to a variable where the full size of the NVM3
+///                                  object will be stored.
 ///
-/// @return On success, the function returns 0 and the object count is written
-///         to the variable pointed to by the `object_count` parameter.
-///         On error, it returns a negative value. This negative number corresponds
-///         to a specific CpcNvm3ErrorCodes, indicating the type of error that occurred.
+/// @return On success, the function returns 0, even when the object was truncated.
+///         On error, it returns a negative value. This negative number corresponds to a
+///         specific CpcNvm3ErrorCodes, indicating the type of error that occurred. If the
+///         object is a counter, the function returns CPC_NVM3_WRONG_OBJECT_TYPE.
+///
+/// @note The first min(`full_size`, `buffer_size`) bytes of the object are copied to the buffer.
+///       The read was truncated when `full_size` is larger than `buffer_size`.
+/// @note Unlike `cpc_nvm3_read_data`, the whole object is transferred from the secondary
+///       whatever the size of the buffer.
 #[no_mangle]
-pub extern "C" fn cpc_nvm3_get_object_count(
+pub extern "C" fn cpc_nvm3_read_data_truncating(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
-    object_count: *mut u16,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    buffer_ptr: *mut c_void,
+    buffer_size: u16,
+    full_size: *mut u16,
+) -> i32 {
+    if (buffer_ptr.is_null() && buffer_size != 0) || full_size.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let buffer: &mut [u8] = if buffer_size == 0 {
+        &mut []
+    } else {
+        unsafe { std::slice::from_raw_parts_mut(buffer_ptr as *mut u8, buffer_size as usize) }
+    };
+
+    match nvm3::read_data_truncating(cpc_nvm3_handle, cpc_nvm3_object_key, buffer) {
+        Ok(object_size) => {
+            log::debug!("Successfully read NVM3 object");
+            unsafe { *full_size = object_size };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Read several data objects in order, their data concatenated in one buffer.
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  keys_ptr         Pointer to an array of `count` object keys, in the order of
+///                              their data in the buffer.
+/// @param[in]  count            The number of objects to read.
+/// @param[out] buffer_ptr       A pointer to the buffer where the concatenated data will be stored.
+/// @param[in]  buffer_size      The size of the provided buffer.
+/// @param[out] data_size        A pointer to a variable where the total size read will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If an object does not exist, the
+///         function returns CPC_NVM3_INVALID_OBJECT_KEY and its key is logged. If an object
+///         does not fit, the function returns CPC_NVM3_BUFFER_TOO_SMALL and the size needed
+///         up to that object is logged.
+///
+/// @note The objects are read while holding the lock of the CPC NVM3 instance. The read stops
+///       at the first object that fails, the content of the buffer is then unspecified.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_concat(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    keys_ptr: *const nvm3::cpc_nvm3_object_key_t,
+    count: u16,
+    buffer_ptr: *mut c_void,
+    buffer_size: usize,
+    data_size: *mut usize,
+) -> i32 {
+    if keys_ptr.is_null() || buffer_ptr.is_null() || data_size.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let keys = unsafe { std::slice::from_raw_parts(keys_ptr, count as usize) };
+    let buffer = unsafe { std::slice::from_raw_parts_mut(buffer_ptr as *mut u8, buffer_size) };
+    let data_size_ref: &mut usize = unsafe { &mut *data_size };
+
+    match nvm3::read_concat(cpc_nvm3_handle, keys, buffer, data_size_ref) {
+        Ok(_) => {
+            log::debug!("Successfully read {} NVM3 objects", count);
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Read data from the specified object without managing a CPC NVM3 instance. An instance
+///        is initialized, opened, closed and de-initialized around the read.
+///
+/// @param[in]  cpcd_instance_name   The name of the daemon instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the object to read data from.
+/// @param[out] buffer_ptr           A pointer to the buffer where the read data will be stored.
+/// @param[in]  buffer_size          The size of the provided buffer.
+/// @param[out] object_size          A pointer to a variable where the actual size of the NVM3 object will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes, the same ones
+///         `cpc_nvm3_init`, `cpc_nvm3_open` and `cpc_nvm3_read_data` return.
+///
+/// @note Every call connects to the daemon and goes through the whole handshake with the
+///       secondary. This is meant for scripts doing a single read, use `cpc_nvm3_read_data`
+///       on an opened instance for repeated reads.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_data_oneshot(
+    cpcd_instance_name: *const c_char,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    buffer_ptr: *mut c_void,
+    buffer_size: u16,
+    object_size: *mut u16,
+) -> i32 {
+    if cpcd_instance_name.is_null() || buffer_ptr.is_null() || object_size.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(cpcd_instance_name) };
+    let instance_name = match c_str.to_str() {
+        Ok(name) => name,
+        Err(err) => {
+            log::error!(
+                "Failed to convert cpcd_instance_name to string. {}",
+                err.to_string()
+            );
+            return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+        }
+    };
+    let buffer =
+        unsafe { std::slice::from_raw_parts_mut(buffer_ptr as *mut u8, buffer_size as usize) };
+    let data_size_ref: &mut u16 = unsafe { &mut *object_size };
+
+    match nvm3::read_data_oneshot(instance_name, cpc_nvm3_object_key, buffer, data_size_ref) {
+        Ok(_) => {
+            log::debug!("Successfully read NVM3 object");
+            0
+        }
+        // The instance is already released, there is no label left to log
+        Err(err) => {
+            log::error!("{}", err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Read several data objects in one call.
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  keys_ptr         Pointer to an array of `count` object keys.
+/// @param[out] buffer_ptrs      Pointer to an array of `count` pointers to the buffers where the read
+///                              data will be stored.
+/// @param[in]  buffer_sizes     Pointer to an array of `count` buffer sizes.
+/// @param[out] object_sizes     Pointer to an array of `count` variables where the size of each read
+///                              object will be stored.
+/// @param[in]  count            The number of objects to read.
+/// @param[out] results_ptr      Pointer to an array of `count` results. Each entry is 0 if the object
+///                              was read, or a negative CpcNvm3ErrorCodes otherwise.
+///
+/// @return On success, the function returns 0 and every entry of `results_ptr` is 0.
+///         On error, it returns the negative CpcNvm3ErrorCodes that stopped the batch.
+///
+/// @note The batch stops at the first failing entry, as with `cpc_nvm3_write_data_batch`. The
+///       caller can resume the batch from the first non-zero entry of `results_ptr`.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_data_batch(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    keys_ptr: *const nvm3::cpc_nvm3_object_key_t,
+    buffer_ptrs: *const *mut u8,
+    buffer_sizes: *const u16,
+    object_sizes: *mut u16,
+    count: u16,
+    results_ptr: *mut i32,
+) -> i32 {
+    if keys_ptr.is_null()
+        || buffer_ptrs.is_null()
+        || buffer_sizes.is_null()
+        || object_sizes.is_null()
+        || results_ptr.is_null()
+    {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let keys = unsafe { std::slice::from_raw_parts(keys_ptr, count as usize) };
+    let buffer_ptrs = unsafe { std::slice::from_raw_parts(buffer_ptrs, count as usize) };
+    let buffer_sizes = unsafe { std::slice::from_raw_parts(buffer_sizes, count as usize) };
+    let object_sizes = unsafe { std::slice::from_raw_parts_mut(object_sizes, count as usize) };
+    let results = unsafe { std::slice::from_raw_parts_mut(results_ptr, count as usize) };
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for index in 0..count as usize {
+        if buffer_ptrs[index].is_null() {
+            log::error!("Entry {} of the batch has no buffer", index);
+            return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+        }
+        let buffer = unsafe {
+            std::slice::from_raw_parts_mut(buffer_ptrs[index], buffer_sizes[index] as usize)
+        };
+        entries.push((keys[index], buffer));
+    }
+
+    match nvm3::read_data_batch(cpc_nvm3_handle, &mut entries) {
+        Ok(reads) => {
+            for (index, read) in reads.iter().enumerate() {
+                object_sizes[index] = *read.as_ref().unwrap_or(&0);
+            }
+            fill_batch_results(cpc_nvm3_handle, &reads, results)
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            results.fill(err.code() as i32);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Set how many mismatched responses an operation drops before failing.
+///
+/// @param[in]  cpc_nvm3_handle            The handle to the CPC NVM3 instance.
+/// @param[in]  max_mismatched_responses   The number of responses an operation may drop, 32 by default.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note A response is dropped when it doesn't match the command in flight, such as the late
+///       response of a timed out command or a frame meant for another host sharing the daemon.
+///       Once the limit is exceeded, the operation returns CPC_NVM3_FAILURE.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_max_mismatched_responses(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    max_mismatched_responses: u16,
+) -> i32 {
+    match nvm3::set_max_mismatched_responses(cpc_nvm3_handle, max_mismatched_responses) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Configure the detection of a transaction ID desynchronization with the secondary.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  threshold         The number of consecutive responses with an unexpected
+///                               transaction ID that triggers the detection, 8 by default.
+///                               0 disables the detection.
+/// @param[in]  reconnect         true to reopen the endpoint once the threshold is reached,
+///                               false to only log a warning. false by default.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Such a run of responses usually means the secondary restarted and lost track of the
+///       transaction IDs. When reconnecting, the operation in flight returns CPC_NVM3_TRY_AGAIN
+///       and must be issued again.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_desync_detection(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    threshold: u16,
+    reconnect: bool,
+) -> i32 {
+    match nvm3::set_desync_detection(cpc_nvm3_handle, threshold, reconnect) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Reject the responses with a fixed layout that carry bytes past their last field.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  strict_parsing    true to reject such responses, false to ignore the extra
+///                               bytes. false by default.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note The extra bytes are ignored by default so that a newer firmware can append fields to
+///       a response. With strict parsing, an operation receiving such a response returns
+///       CPC_NVM3_FAILURE.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_strict_parsing(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    strict_parsing: bool,
+) -> i32 {
+    match nvm3::set_strict_parsing(cpc_nvm3_handle, strict_parsing) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Bound the size of the responses reassembled from several fragments.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  max_reassembly    The most bytes the fragments of a single read or enumeration
+///                               response can add up to. 0 restores the default, the maximum
+///                               object size for reads and 65535 keys for enumerations.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note This protects the host from a secondary that keeps sending fragments without ever
+///       sending the last one. An operation receiving more fails with CPC_NVM3_FAILURE.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_max_reassembly(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    max_reassembly: u32,
+) -> i32 {
+    match nvm3::set_max_reassembly(cpc_nvm3_handle, max_reassembly) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Enable or disable the automatic reconnection to the CPC endpoint.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  auto_reconnect    true to reconnect as soon as the connection is lost, false to
+///                               leave the reconnection to the caller. true by default.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note By default, an operation that loses the connection reconnects and returns
+///       CPC_NVM3_TRY_AGAIN. Without the automatic reconnection it returns
+///       CPC_NVM3_CONNECTION_LOST, and the instance must be closed and opened again.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_auto_reconnect(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    auto_reconnect: bool,
+) -> i32 {
+    match nvm3::set_auto_reconnect(cpc_nvm3_handle, auto_reconnect) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Make the operations fail right away with CPC_NVM3_TRY_AGAIN instead of blocking
+///        when the response of the secondary is not available yet.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  nonblocking       true to enable non-blocking reads, false to restore blocking reads.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Writes are not affected: the command of an operation that returns CPC_NVM3_TRY_AGAIN
+///       was already sent. Calling the same operation again with the same arguments collects
+///       its response without sending the command again. Calling any other operation first
+///       drops the pending response.
+/// @note An operation that already received part of a fragmented response returns
+///       CPC_NVM3_TRY_AGAIN too, but it is sent again when retried.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_nonblocking(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    nonblocking: bool,
+) -> i32 {
+    match nvm3::set_nonblocking(cpc_nvm3_handle, nonblocking) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Wait for every acknowledged write to be committed to the flash of the secondary.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. The CPC NVM3 protocol
+///         has no sync command, so an opened instance returns CPC_NVM3_NOT_SUPPORTED.
+///
+/// @note The secondary doesn't buffer writes: the acknowledgement of a write, and the 0 returned
+///       by `cpc_nvm3_write_data` or `cpc_nvm3_write_counter`, is only sent once NVM3 stored the
+///       object. A burst of successful writes is therefore already committed.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_sync(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i32 {
+    match nvm3::sync(cpc_nvm3_handle) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Periodically ping the secondary so the CPC endpoint does not stay idle.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  interval_ms       The longest time in milliseconds the endpoint can stay idle.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note The pings are sent from a background thread and only when no other operation was
+///       performed during the interval. A ping is skipped when another operation is in progress.
+/// @note Calling this function again replaces the previous interval. The keepalive stops when
+///       `cpc_nvm3_disable_keepalive` or `cpc_nvm3_deinit` is called.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_enable_keepalive(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    interval_ms: u32,
+) -> i32 {
+    match nvm3::enable_keepalive(
+        cpc_nvm3_handle,
+        std::time::Duration::from_millis(interval_ms as u64),
+    ) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Stop the keepalive started with `cpc_nvm3_enable_keepalive`.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+///
+/// @return On success, the function returns 0. This is also the case when the keepalive
+///         was not enabled. On error, the function returns a negative value, corresponding
+///         to a specific CpcNvm3ErrorCodes, indicating the type of error that occurred.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_disable_keepalive(cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t) -> i32 {
+    match nvm3::disable_keepalive(cpc_nvm3_handle) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve the count of objects stored in the specified CPC NVM3 instance.
+///
+/// @param[in]  cpc_nvm3_handle     The handle to the CPC NVM3 instance.
+/// @param[out] object_count        Pointer to a variable where the total count
+///                                 of stored objects will be written.
+///                                 The value at this pointer will be updated
+///                                 only if the function is successful.
+///
+/// @return On success, the function returns 0 and the object count is written
+///         to the variable pointed to by the `object_count` parameter.
+///         On error, it returns a negative value. This negative number corresponds
+///         to a specific CpcNvm3ErrorCodes, indicating the type of error that occurred.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_object_count(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    object_count: *mut u16,
 ) -> i32 {
     if object_count.is_null() {
         return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
@@ -386,197 +1881,1386 @@ pub extern "C" fn cpc_nvm3_get_object_count(
             *object_count_ref = count;
             return 0;
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve the count of objects of a given type stored in the CPC NVM3 instance.
+///
+/// @param[in]  cpc_nvm3_handle     The handle to the CPC NVM3 instance.
+/// @param[in]  object_type         The type of the objects to count.
+/// @param[out] object_count        Pointer to a variable where the count of objects of this type
+///                                 will be written, only if the function is successful.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+///
+/// @note The enumeration of the objects does not report their type, so this function queries
+///       the information of every object. Its cost grows with the total number of objects.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_object_count_by_type(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    object_type: CpcNvm3ObjectType,
+    object_count: *mut u16,
+) -> i32 {
+    if object_count.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    match nvm3::get_object_count_by_type(cpc_nvm3_handle, object_type) {
+        Ok(count) => {
+            log::debug!(
+                "Successfully counted {} NVM3 objects of type {}",
+                count,
+                object_type
+            );
+            unsafe { *object_count = count };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Get a list of objects available on the CPC NVM3 instance
+///
+/// This function retrieves a list of keys for the objects stored in the NVM3 instance.
+///
+/// @param[in]  cpc_nvm3_handle             The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_keys_ptr    Pointer to an array where the object keys will be stored.
+/// @param[in]  max_key_count               Maximum number of keys that can be stored in the array.
+/// @param[out] object_count                Pointer to a variable where the actual count of keys will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN. If the secondary
+///         reports that its storage is full or can't be accessed, the function returns
+///         CPC_NVM3_STORAGE_FULL or CPC_NVM3_IO_ERROR respectively.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_list_objects(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_keys_ptr: *const nvm3::cpc_nvm3_object_key_t,
+    max_key_count: u16,
+    object_count: *mut u16,
+) -> i32 {
+    if cpc_nvm3_object_keys_ptr.is_null() || object_count.is_null() || max_key_count == 0 {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let buffer = unsafe {
+        std::slice::from_raw_parts_mut(
+            cpc_nvm3_object_keys_ptr as *mut nvm3::cpc_nvm3_object_key_t,
+            max_key_count as usize,
+        )
+    };
+
+    let object_count_ref: &mut u16 = unsafe { &mut *object_count };
+
+    match nvm3::list_objects(cpc_nvm3_handle, buffer, object_count_ref) {
+        Ok(count) => {
+            log::debug!("Successfully listed {:?} NVM3 objects", count);
+            return 0;
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Callback invoked by `cpc_nvm3_list_objects_progress` after each received fragment.
+///
+/// @param[in]  key_count   The number of keys received so far.
+/// @param[in]  user_ctx    The user context provided to `cpc_nvm3_list_objects_progress`.
+///
+/// @return The callback must return true for the enumeration to continue. Returning false
+///         cancels it.
+#[allow(non_camel_case_types)]
+pub type cpc_nvm3_list_objects_progress_callback_t =
+    Option<unsafe extern "C" fn(key_count: u16, user_ctx: *mut c_void) -> bool>;
+
+/// @brief Get a list of objects available on the CPC NVM3 instance, reporting the progress
+///        of the enumeration.
+///
+/// @param[in]  cpc_nvm3_handle             The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_keys_ptr    Pointer to an array where the object keys will be stored.
+/// @param[in]  max_key_count               Maximum number of keys that can be stored in the array.
+/// @param[in]  callback                    The callback invoked after each received fragment. This value
+///                                         can be NULL, in which case the function behaves like
+///                                         `cpc_nvm3_list_objects`.
+/// @param[in]  user_ctx                    A user context passed as-is to the callback. This value can be NULL.
+/// @param[out] object_count                Pointer to a variable where the actual count of keys will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the callback cancels the
+///         enumeration, the function returns CPC_NVM3_CANCELLED.
+///
+/// @note The callback is invoked while holding the lock of the CPC NVM3 instance. It must not
+///       call back into the library with the same handle.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_list_objects_progress(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_keys_ptr: *mut nvm3::cpc_nvm3_object_key_t,
+    max_key_count: u16,
+    callback: cpc_nvm3_list_objects_progress_callback_t,
+    user_ctx: *mut c_void,
+    object_count: *mut u16,
+) -> i32 {
+    if cpc_nvm3_object_keys_ptr.is_null() || object_count.is_null() || max_key_count == 0 {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let buffer =
+        unsafe { std::slice::from_raw_parts_mut(cpc_nvm3_object_keys_ptr, max_key_count as usize) };
+    let object_count_ref: &mut u16 = unsafe { &mut *object_count };
+
+    let progress = |key_count| match callback {
+        Some(callback) => unsafe { callback(key_count, user_ctx) },
+        None => true,
+    };
+
+    match nvm3::list_objects_with_progress(cpc_nvm3_handle, buffer, object_count_ref, progress) {
+        Ok(_) => {
+            log::debug!("Successfully listed {:?} NVM3 objects", *object_count_ref);
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Callback invoked by `cpc_nvm3_export_all` with the content of a data object.
+///
+/// @param[in]  cpc_nvm3_object_key  The key of the data object.
+/// @param[in]  data                 A pointer to the data of the object. The pointer is only
+///                                  valid for the duration of the callback.
+/// @param[in]  size                 The size of the data.
+/// @param[in]  user_ctx             The user context provided to `cpc_nvm3_export_all`.
+#[allow(non_camel_case_types)]
+pub type cpc_nvm3_export_data_callback_t = Option<
+    unsafe extern "C" fn(
+        cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+        data: *const u8,
+        size: u16,
+        user_ctx: *mut c_void,
+    ),
+>;
+
+/// @brief Callback invoked by `cpc_nvm3_export_all` with the value of a counter.
+///
+/// @param[in]  cpc_nvm3_object_key  The key of the counter.
+/// @param[in]  value                The value of the counter.
+/// @param[in]  user_ctx             The user context provided to `cpc_nvm3_export_all`.
+#[allow(non_camel_case_types)]
+pub type cpc_nvm3_export_counter_callback_t = Option<
+    unsafe extern "C" fn(
+        cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+        value: u32,
+        user_ctx: *mut c_void,
+    ),
+>;
+
+/// @brief Read every object of the CPC NVM3 instance and pass them to the callbacks.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  data_callback     The callback invoked for each data object. This value can be NULL,
+///                               in which case the data objects are not reported.
+/// @param[in]  counter_callback  The callback invoked for each counter. This value can be NULL,
+///                               in which case the counters are not reported.
+/// @param[in]  user_ctx          A user context passed as-is to the callbacks. This value can be NULL.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+///
+/// @note An object that vanished, or that can't be read as its type, is logged and skipped, the
+///       export carries on with the next object. Any other error, e.g. a lost connection,
+///       aborts the export.
+/// @note The callbacks are invoked while holding the lock of the CPC NVM3 instance. They must
+///       not call back into the library with the same handle.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_export_all(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    data_callback: cpc_nvm3_export_data_callback_t,
+    counter_callback: cpc_nvm3_export_counter_callback_t,
+    user_ctx: *mut c_void,
+) -> i32 {
+    let on_data = |key, data: &[u8]| {
+        if let Some(data_callback) = data_callback {
+            unsafe { data_callback(key, data.as_ptr(), data.len() as u16, user_ctx) }
+        }
+    };
+    let on_counter = |key, value| {
+        if let Some(counter_callback) = counter_callback {
+            unsafe { counter_callback(key, value, user_ctx) }
+        }
+    };
+
+    match nvm3::export_all(cpc_nvm3_handle, on_data, on_counter) {
+        Ok(skipped) => {
+            log::debug!("Successfully exported NVM3 objects, {} skipped", skipped);
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+fn c_path<'a>(path: *const c_char) -> Result<&'a std::path::Path, i32> {
+    if path.is_null() {
+        log::error!("The path is NULL");
+        return Err(CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32);
+    }
+    match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(path) => Ok(std::path::Path::new(path)),
+        Err(err) => {
+            log::error!("Failed to convert the path to string. {}", err);
+            Err(CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32)
+        }
+    }
+}
+
+/// @brief Export every object of the CPC NVM3 instance to a directory, one file per object.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  dir               The path of an existing directory. The files it already holds
+///                               for the exported keys are overwritten.
+/// @param[out] exported_count    Pointer to a variable where the number of exported objects will
+///                               be stored. This value can be NULL.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+///
+/// @note A data object is written as is to `<key>.bin` and a counter as its 4 bytes little
+///       endian value to `<key>.cnt`, the key being in decimal. Each object is written as soon
+///       as it's read, so only one object is held in memory at a time.
+/// @note An object that can't be read, as with `cpc_nvm3_export_all`, or written is logged and
+///       skipped.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_export_all_to_dir(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    dir: *const c_char,
+    exported_count: *mut u32,
+) -> i32 {
+    let dir = match c_path(dir) {
+        Ok(dir) => dir,
+        Err(error_code) => return error_code,
+    };
+
+    match nvm3::export_all_to_dir(cpc_nvm3_handle, dir) {
+        Ok(exported) => {
+            if !exported_count.is_null() {
+                unsafe { *exported_count = exported as u32 };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Back up every object of the CPC NVM3 instance to a file.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  path              The path of the backup file, it is overwritten if it exists.
+/// @param[out] skipped_count     Pointer to a variable where the number of objects that could not be
+///                               read will be stored. This value can be NULL.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+///
+/// @note An object that can't be read is logged and left out of the backup, as with
+///       `cpc_nvm3_export_all`. A link error aborts the backup and no file is written.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_backup_to_file(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    path: *const c_char,
+    skipped_count: *mut u32,
+) -> i32 {
+    let path = match c_path(path) {
+        Ok(path) => path,
+        Err(error_code) => return error_code,
+    };
+
+    match nvm3::backup_to_file(cpc_nvm3_handle, path) {
+        Ok(skipped) => {
+            if !skipped_count.is_null() {
+                unsafe { *skipped_count = skipped as u32 };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Back up every object of the CPC NVM3 instance to a buffer, in the format of
+///        `cpc_nvm3_backup_to_file`.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] buffer            Pointer to the buffer where the backup will be stored. This value
+///                               can be NULL if `buffer_size` is 0.
+/// @param[in]  buffer_size       The size of the buffer.
+/// @param[out] used_len          Pointer to a variable where the length of the backup will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the backup doesn't fit in the
+///         buffer, the function returns CPC_NVM3_BUFFER_TOO_SMALL and `used_len` holds the
+///         size the buffer needs.
+///
+/// @note The objects are read from the secondary on every call. Sizing the buffer with a first
+///       call and an empty buffer reads them twice, and objects written in between can make
+///       the second call need more room.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_backup_to_buffer(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    buffer: *mut u8,
+    buffer_size: usize,
+    used_len: *mut usize,
+) -> i32 {
+    if (buffer.is_null() && buffer_size > 0) || used_len.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    let buffer: &mut [u8] = if buffer_size == 0 {
+        &mut []
+    } else {
+        unsafe { std::slice::from_raw_parts_mut(buffer, buffer_size) }
+    };
+    let used_len_ref: &mut usize = unsafe { &mut *used_len };
+
+    match nvm3::backup_to_buffer(cpc_nvm3_handle, buffer, used_len_ref) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Restore the objects of a backup file written by `cpc_nvm3_backup_to_file`.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[in]  path              The path of the backup file.
+/// @param[out] restored_count    Pointer to a variable where the number of restored objects will be
+///                               stored. This value can be NULL.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. A file with a wrong magic, format
+///         version or CRC returns CPC_NVM3_BACKUP_CORRUPT.
+///
+/// @note The whole file is validated before the first object is written, a corrupted backup
+///       leaves the secondary untouched. The restore stops at the first object that fails to
+///       be written.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_restore_from_file(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    path: *const c_char,
+    restored_count: *mut u32,
+) -> i32 {
+    let path = match c_path(path) {
+        Ok(path) => path,
+        Err(error_code) => return error_code,
+    };
+
+    match nvm3::restore_from_file(cpc_nvm3_handle, path) {
+        Ok(restored) => {
+            if !restored_count.is_null() {
+                unsafe { *restored_count = restored as u32 };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Get one page of the objects available on the CPC NVM3 instance
+///
+/// Page `page_index` holds the keys at positions `page_index * page_size` up to
+/// `(page_index + 1) * page_size` in the order the secondary enumerates them.
+///
+/// @param[in]  cpc_nvm3_handle             The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_keys_ptr    Pointer to an array of at least `page_size` keys where the object keys will be stored.
+/// @param[in]  page_size                   Maximum number of keys in a page.
+/// @param[in]  page_index                  Index of the page to list, starting at 0.
+/// @param[out] object_count                Pointer to a variable where the count of keys in this page will be stored.
+/// @param[out] more                        Pointer to a variable set to true if more objects follow this page.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note Objects created or deleted between two calls can shift the pages.
+///
+/// @note The secondary cannot resume an enumeration, so every call enumerates the objects
+///       from the first one up to the end of the requested page. Listing a large storage
+///       page by page costs O(n²) transfers, `cpc_nvm3_list_objects` or a cursor opened with
+///       `cpc_nvm3_object_cursor_open` lists it in one pass.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_list_objects_paged(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_keys_ptr: *mut nvm3::cpc_nvm3_object_key_t,
+    page_size: u16,
+    page_index: u16,
+    object_count: *mut u16,
+    more: *mut bool,
+) -> i32 {
+    if cpc_nvm3_object_keys_ptr.is_null()
+        || object_count.is_null()
+        || more.is_null()
+        || page_size == 0
+    {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let buffer =
+        unsafe { std::slice::from_raw_parts_mut(cpc_nvm3_object_keys_ptr, page_size as usize) };
+    let object_count_ref: &mut u16 = unsafe { &mut *object_count };
+    let more_ref: &mut bool = unsafe { &mut *more };
+
+    match nvm3::list_objects_paged(
+        cpc_nvm3_handle,
+        page_size,
+        page_index,
+        buffer,
+        object_count_ref,
+        more_ref,
+    ) {
+        Ok(_) => {
+            log::debug!(
+                "Successfully listed {} NVM3 objects in page {}",
+                *object_count_ref,
+                page_index
+            );
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// A cursor over the objects of a CPC NVM3 instance. It is opened with
+/// `cpc_nvm3_object_cursor_open` and must be released with `cpc_nvm3_object_cursor_close`.
+#[allow(non_camel_case_types)]
+pub struct cpc_nvm3_object_cursor_t {
+    cursor: nvm3::ObjectCursor,
+}
+
+/// @brief Open a cursor to list the objects of the CPC NVM3 instance page by page
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  page_size        Maximum number of keys returned by each call to `cpc_nvm3_object_cursor_next`.
+/// @param[out] cursor           Pointer to a variable where the cursor will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred.
+///
+/// @note The first call to `cpc_nvm3_object_cursor_next` enumerates every object at once, the
+///       following calls carry on from the last key returned without querying the
+///       secondary. Objects created or deleted after the first page are not reflected.
+/// @note The cursor keeps every key until it is closed, its memory is O(object count)
+///       whatever the page size, up to 4 bytes per object. Only the caller's buffer is
+///       bounded by `page_size`.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_object_cursor_open(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    page_size: u16,
+    cursor: *mut *mut cpc_nvm3_object_cursor_t,
+) -> i32 {
+    if cursor.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    match nvm3::open_object_cursor(cpc_nvm3_handle, page_size) {
+        Ok(object_cursor) => {
+            let object_cursor = Box::new(cpc_nvm3_object_cursor_t {
+                cursor: object_cursor,
+            });
+            unsafe { *cursor = Box::into_raw(object_cursor) };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief List the next page of objects from a cursor
+///
+/// @param[in]  cursor                      The cursor obtained from `cpc_nvm3_object_cursor_open`.
+/// @param[in]  cpc_nvm3_object_keys_ptr    Pointer to an array where the object keys will be stored.
+/// @param[in]  max_key_count               Maximum number of keys that can be stored in the array. It must be at least the page size of the cursor.
+/// @param[out] object_count                Pointer to a variable where the count of keys in this page will be stored.
+/// @param[out] more                        Pointer to a variable set to true if another call can return more objects.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost while the first page enumerates the objects, the function will
+///         return CPC_NVM3_TRY_AGAIN and the first page can be requested again.
+///
+/// @note Only the first page queries the secondary. The following pages are served from the
+///       keys kept by the cursor, they never return CPC_NVM3_TRY_AGAIN.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_object_cursor_next(
+    cursor: *mut cpc_nvm3_object_cursor_t,
+    cpc_nvm3_object_keys_ptr: *mut nvm3::cpc_nvm3_object_key_t,
+    max_key_count: u16,
+    object_count: *mut u16,
+    more: *mut bool,
+) -> i32 {
+    if cursor.is_null()
+        || cpc_nvm3_object_keys_ptr.is_null()
+        || object_count.is_null()
+        || more.is_null()
+        || max_key_count == 0
+    {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let object_cursor = unsafe { &mut (*cursor).cursor };
+    let buffer =
+        unsafe { std::slice::from_raw_parts_mut(cpc_nvm3_object_keys_ptr, max_key_count as usize) };
+
+    match object_cursor.next_page(buffer) {
+        Ok(count) => {
+            log::debug!("Successfully listed {} NVM3 objects", count);
+            unsafe { *object_count = count };
+            unsafe { *more = object_cursor.has_more() };
+            0
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Release a cursor opened with `cpc_nvm3_object_cursor_open`
+///
+/// @param[in]  cursor  The cursor to release. This value can be NULL.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_object_cursor_close(cursor: *mut cpc_nvm3_object_cursor_t) {
+    if !cursor.is_null() {
+        drop(unsafe { Box::from_raw(cursor) });
+    }
+}
+
+/// @brief Write a value to the specified counter.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter.
+/// @param[in]  value                   The value to write.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_write_counter(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: u32,
+) -> i32 {
+    match nvm3::write_counter(cpc_nvm3_handle, cpc_nvm3_object_key, value) {
+        Ok(_) => {
+            log::debug!(
+                "Successfully wrote to NVM3 counter {:?}",
+                cpc_nvm3_object_key
+            );
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Create a counter, failing if an object already exists with the same key.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter.
+/// @param[in]  initial_value        The value of the new counter.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If an object already exists with
+///         the key, the function returns CPC_NVM3_ALREADY_EXISTS.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_create_counter(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    initial_value: u32,
+) -> i32 {
+    match nvm3::create_counter(cpc_nvm3_handle, cpc_nvm3_object_key, initial_value) {
+        Ok(_) => {
+            log::debug!(
+                "Successfully created NVM3 counter {:?}",
+                cpc_nvm3_object_key
+            );
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Write a value to an existing counter.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter.
+/// @param[in]  value                The value to write.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If no object exists with the key,
+///         the function returns CPC_NVM3_INVALID_OBJECT_KEY. If the object is a data
+///         object, the function returns CPC_NVM3_WRONG_OBJECT_TYPE.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_update_counter(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: u32,
+) -> i32 {
+    match nvm3::update_counter(cpc_nvm3_handle, cpc_nvm3_object_key, value) {
+        Ok(_) => {
+            log::debug!(
+                "Successfully updated NVM3 counter {:?}",
+                cpc_nvm3_object_key
+            );
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Write a value to the specified counter and read back the stored value.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter.
+/// @param[in]  value                The value to write.
+/// @param[out] stored_value         A pointer to the variable where the value read back from the counter will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note The write and the read are performed while holding the lock of the CPC NVM3 instance.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_write_counter_readback(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: u32,
+    stored_value: *mut u32,
+) -> i32 {
+    if stored_value.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    match nvm3::write_counter_readback(cpc_nvm3_handle, cpc_nvm3_object_key, value) {
+        Ok(counter_value) => {
+            log::debug!(
+                "Successfully wrote NVM3 counter {:?}, it now holds {}",
+                cpc_nvm3_object_key,
+                counter_value
+            );
+            unsafe { *stored_value = counter_value };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Read data from the specified counter.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter object to read data from.
+/// @param[out] value                   A pointer to the variable where the counter data will be stored.
+///                                     This value is optional, when a NULL pointer is provided, it
+///                                     will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_counter(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: *mut u32,
+) -> i32 {
+    if value.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::read_counter(cpc_nvm3_handle, cpc_nvm3_object_key) {
+        Ok(read_value) => {
+            unsafe { *value = read_value };
+            log::debug!("Successfully read NVM3 counter object");
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            return err.code() as i32;
+        }
+    }
+    0
+}
+
+/// @brief Read data from the specified counter, interpreted as a two's complement signed value.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter object to read data from.
+/// @param[out] value                A pointer to the variable where the counter data will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note The counter holds 32 bits, a value of 0xFFFFFFFF is read as -1.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_counter_signed(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: *mut i32,
+) -> i32 {
+    if value.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::read_counter_signed(cpc_nvm3_handle, cpc_nvm3_object_key) {
+        Ok(read_value) => {
+            unsafe { *value = read_value };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Read a 4 bytes data object as a little endian 32 bits value.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the data object to read.
+/// @param[out] value                A pointer to the variable where the value will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the object is not 4 bytes, the
+///         function returns CPC_NVM3_INVALID_ARG. If it is a counter, the function returns
+///         CPC_NVM3_WRONG_OBJECT_TYPE.
+///
+/// @note This is meant for values stored as data objects rather than counters, use
+///       `cpc_nvm3_read_counter` for the latter.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_u32_data(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    value: *mut u32,
+) -> i32 {
+    if value.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::read_u32_data(cpc_nvm3_handle, cpc_nvm3_object_key) {
+        Ok(read_value) => {
+            unsafe { *value = read_value };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Read several counters in one call.
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  keys_ptr         Pointer to an array of `count` counter keys.
+/// @param[in]  count            The number of counters to read.
+/// @param[out] values_ptr       Pointer to an array of `count` values where the counter values will be stored.
+/// @param[out] results_ptr      Pointer to an array of `count` results. Each entry is 0 if the counter
+///                              was read, or the negative CpcNvm3ErrorCodes of its failure.
+///
+/// @return The function returns 0 once every counter has been attempted, even if some of them
+///         are missing or are not counters. On any other error, it returns a negative value
+///         corresponding to a specific CpcNvm3ErrorCodes, and no result is reported.
+///
+/// @note The counters are read while holding the lock of the CPC NVM3 instance.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_read_counters(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    keys_ptr: *const nvm3::cpc_nvm3_object_key_t,
+    count: u16,
+    values_ptr: *mut u32,
+    results_ptr: *mut i32,
+) -> i32 {
+    if keys_ptr.is_null() || values_ptr.is_null() || results_ptr.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let keys = unsafe { std::slice::from_raw_parts(keys_ptr, count as usize) };
+    let values = unsafe { std::slice::from_raw_parts_mut(values_ptr, count as usize) };
+    let results = unsafe { std::slice::from_raw_parts_mut(results_ptr, count as usize) };
+
+    match nvm3::read_counters(cpc_nvm3_handle, keys) {
+        Ok(counters) => {
+            for (index, (key, counter)) in counters.into_iter().enumerate() {
+                match counter {
+                    Ok(value) => {
+                        values[index] = value;
+                        results[index] = 0;
+                    }
+                    Err(err) => {
+                        log::error!(
+                            "[{}] Failed to read NVM3 counter {:?}. {}",
+                            nvm3::instance_label(cpc_nvm3_handle),
+                            key,
+                            err
+                        );
+                        values[index] = 0;
+                        results[index] = err.code() as i32;
+                    }
+                }
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Increment the specified counter.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter object to increment data from.
+/// @param[out] new_value            A pointer to the variable where the counter new value will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_increment_counter(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    new_value: *mut u32,
+) -> i32 {
+    match nvm3::increment_counter(cpc_nvm3_handle, cpc_nvm3_object_key) {
+        Ok(read_value) => {
+            log::debug!("Successfully incremented NVM3 counter");
+            if !new_value.is_null() {
+                unsafe { *new_value = read_value };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Increment the specified counter and report its value before and after the increment.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the counter object to increment.
+/// @param[out] old_value            A pointer to the variable where the counter value before the
+///                                  increment will be stored. This value is optional, when a NULL
+///                                  pointer is provided, it will be ignored.
+/// @param[out] new_value            A pointer to the variable where the counter new value will be stored.
+///                                  This value is optional, when a NULL pointer is provided, it will be ignored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note Counters wrap around. When the counter overflows, `new_value` is 0 and `old_value`
+///       is 0xFFFFFFFF.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_increment_counter_ex(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    old_value: *mut u32,
+    new_value: *mut u32,
+) -> i32 {
+    match nvm3::increment_counter_ex(cpc_nvm3_handle, cpc_nvm3_object_key) {
+        Ok((previous_value, incremented_value)) => {
+            log::debug!("Successfully incremented NVM3 counter");
+            if !old_value.is_null() {
+                unsafe { *old_value = previous_value };
+            }
+            if !new_value.is_null() {
+                unsafe { *new_value = incremented_value };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve the maximum allowable size for an object that can be written
+///        to the NVM3 instance on the remote device. The user must provide a
+///        valid handle obtained from the initialization process.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Make sure to verify that the CPC NVM3 instance is opened and functional
+///       before calling this function, as it will fail otherwise.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_maximum_write_size(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    max_write: *mut u16,
+) -> i32 {
+    if max_write.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_maximum_write_size(cpc_nvm3_handle) {
+        Ok(maximum_write_size) => {
+            log::info!("Maximum write size is {} bytes", maximum_write_size);
+            unsafe { *max_write = maximum_write_size };
+            0
+        }
+
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve the maximum size of an object of the NVM3 instance on the remote device.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] max_object_size   Pointer to where the maximum object size will be stored.
+/// @param[out] approximate       Pointer to where it will be stored whether the size is an
+///                               approximation. This pointer can be NULL.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Secondaries that predate the maximum object size property don't report it, the
+///       maximum write size is used instead and `approximate` is set to true.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_maximum_object_size(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    max_object_size: *mut u16,
+    approximate: *mut bool,
+) -> i32 {
+    if max_object_size.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_maximum_object_size(cpc_nvm3_handle) {
+        Ok((maximum_object_size, is_approximate)) => {
+            log::debug!("Maximum object size is {} bytes", maximum_object_size);
+            unsafe {
+                *max_object_size = maximum_object_size;
+                if !approximate.is_null() {
+                    *approximate = is_approximate;
+                }
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve the maximum size of the data carried by a single write command. Writes
+///        larger than this are split in fragments of this size.
+///
+/// @param[in]  cpc_nvm3_handle     The handle to the CPC NVM3 instance.
+/// @param[out] max_fragment_size   A pointer to the variable where the maximum write fragment
+///                                 size will be stored.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note The fragment size is the CPC maximum write size minus the CPC NVM3 protocol overhead.
+///       It is known once the instance is opened, CPC_NVM3_NOT_OPEN is returned otherwise.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_maximum_write_fragment_size(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    max_fragment_size: *mut u16,
+) -> i32 {
+    if max_fragment_size.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_maximum_write_fragment_size(cpc_nvm3_handle) {
+        Ok(maximum_write_fragment_size) => {
+            log::debug!(
+                "Maximum write fragment size is {} bytes",
+                maximum_write_fragment_size
+            );
+            unsafe { *max_fragment_size = maximum_write_fragment_size };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve the maximum size of a frame that the CPC endpoint can transmit, as
+///        reported by libcpc when the instance was opened. The difference between this
+///        value and the maximum write fragment size is the CPC NVM3 protocol overhead.
+///
+/// @param[in]  cpc_nvm3_handle     The handle to the CPC NVM3 instance.
+/// @param[out] cpc_max_write       A pointer to the variable where the CPC maximum write size
+///                                 will be stored.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note The CPC NVM3 instance must be opened before calling this function.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_cpc_max_write_size(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_max_write: *mut u16,
+) -> i32 {
+    if cpc_max_write.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_cpc_max_write_size(cpc_nvm3_handle) {
+        Ok(cpc_max_write_size) => {
+            log::debug!("CPC maximum write size is {} bytes", cpc_max_write_size);
+            unsafe { *cpc_max_write = cpc_max_write_size };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve the bitmap of features supported by the secondary, as learned when
+///        the instance was opened. Each bit corresponds to a CPC_NVM3_FEATURE_* value.
+///
+/// @param[in]  cpc_nvm3_handle     The handle to the CPC NVM3 instance.
+/// @param[out] features            A pointer to the variable where the feature bitmap will be stored.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note The CPC NVM3 instance must be opened before calling this function. The features
+///       are derived from the properties the secondary answers while the instance is opened,
+///       no request is dedicated to them.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_features(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    features: *mut u32,
+) -> i32 {
+    if features.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_features(cpc_nvm3_handle) {
+        Ok(supported_features) => {
+            log::debug!("Supported features are {:#010x}", supported_features);
+            unsafe { *features = supported_features };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve every size limit of the CPC NVM3 instance in one call.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] limits            A pointer to the structure where the limits will be stored.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note The limits are cached when the instance is opened, so the CPC NVM3 instance
+///       must be opened before calling this function.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_limits(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    limits: *mut CpcNvm3Limits,
+) -> i32 {
+    if limits.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_limits(cpc_nvm3_handle) {
+        Ok(instance_limits) => {
+            log::debug!("Limits are {:?}", instance_limits);
+            unsafe { *limits = instance_limits };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Retrieve everything learned about the secondary while opening the CPC NVM3 instance:
+///        the secondary version, the limits, the features and the cpcd instance name.
+///
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] session_info      A pointer to the structure where the session info will be stored.
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. If the instance is
+///         not opened, the function returns CPC_NVM3_NOT_OPEN.
+///
+/// @note Every field is cached when the instance is opened, no request is sent to the secondary.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_session_info(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    session_info: *mut CpcNvm3SessionInfo,
+) -> i32 {
+    if session_info.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_session_info(cpc_nvm3_handle) {
+        Ok(instance_session_info) => {
+            log::debug!("Session info is {:?}", instance_session_info);
+            unsafe { *session_info = instance_session_info };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
-/// @brief Get a list of objects available on the CPC NVM3 instance
+/// Kind of the last status reported by `cpc_nvm3_get_last_status`: an sl_status code
+pub const CPC_NVM3_STATUS_KIND_SL_STATUS: u8 = 0;
+/// Kind of the last status reported by `cpc_nvm3_get_last_status`: an NVM3 ecode
+pub const CPC_NVM3_STATUS_KIND_ECODE: u8 = 1;
+
+/// @brief Retrieve the last status received from the secondary, exactly as it was sent.
 ///
-/// This function retrieves a list of keys for the objects stored in the NVM3 instance.
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] raw               A pointer to the variable where the status value will be stored.
+/// @param[out] kind              A pointer to the variable where the kind of status will be stored.
+///                               It is CPC_NVM3_STATUS_KIND_SL_STATUS or CPC_NVM3_STATUS_KIND_ECODE.
 ///
-/// @param[in]  cpc_nvm3_handle             The handle to the CPC NVM3 instance.
-/// @param[in]  cpc_nvm3_object_keys_ptr    Pointer to an array where the object keys will be stored.
-/// @param[in]  max_key_count               Maximum number of keys that can be stored in the array.
-/// @param[out] object_count                Pointer to a variable where the actual count of keys will be stored.
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. If no status was
+///         received yet, the function returns CPC_NVM3_FAILURE.
 ///
-/// @return On success, the function returns 0. On error, it returns a negative value.
-///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
-///         indicating the type of error that occurred. If the connection to the CPC
-///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+/// @note Successful operations also report a status, so this is the status of the last
+///       operation that received one, whether it failed or not.
 #[no_mangle]
-pub extern "C" fn cpc_nvm3_list_objects(
+pub extern "C" fn cpc_nvm3_get_last_status(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
-    cpc_nvm3_object_keys_ptr: *const nvm3::cpc_nvm3_object_key_t,
-    max_key_count: u16,
-    object_count: *mut u16,
+    raw: *mut u32,
+    kind: *mut u8,
 ) -> i32 {
-    if cpc_nvm3_object_keys_ptr.is_null() || object_count.is_null() || max_key_count == 0 {
+    if raw.is_null() || kind.is_null() {
         return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
     }
-
-    let buffer = unsafe {
-        std::slice::from_raw_parts_mut(
-            cpc_nvm3_object_keys_ptr as *mut nvm3::cpc_nvm3_object_key_t,
-            max_key_count as usize,
-        )
-    };
-
-    let object_count_ref: &mut u16 = unsafe { &mut *object_count };
-
-    match nvm3::list_objects(cpc_nvm3_handle, buffer, object_count_ref) {
-        Ok(count) => {
-            log::debug!("Successfully listed {:?} NVM3 objects", count);
-            return 0;
-        }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
+    match nvm3::get_last_status(cpc_nvm3_handle) {
+        Ok((status_kind, status_value)) => {
+            unsafe {
+                *raw = status_value;
+                *kind = status_kind;
             }
-        },
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
-/// @brief Write a value to the specified counter.
+/// @brief Retrieve the last transaction ID allocated by the CPC NVM3 instance.
 ///
-/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
-/// @param[in]  cpc_nvm3_object_key  The key of the counter.
-/// @param[in]  value                   The value to write.
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] transaction_id    A pointer to the variable where the last allocated
+///                               transaction ID will be stored.
 ///
-/// @return On success, the function returns 0. On error, it returns a negative value.
-///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
-///         indicating the type of error that occurred. If the connection to the CPC
-///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Each request takes the ID following the last allocated one, wrapping from 255 to 0.
+///       The requests of the handshake done by `cpc_nvm3_open` use IDs as well. Once the
+///       transaction ID is seeded, this is the ID before the seed until the next request.
 #[no_mangle]
-pub extern "C" fn cpc_nvm3_write_counter(
+pub extern "C" fn cpc_nvm3_get_last_transaction_id(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
-    value: u32,
+    transaction_id: *mut u8,
 ) -> i32 {
-    match nvm3::write_counter(cpc_nvm3_handle, cpc_nvm3_object_key, value) {
-        Ok(_) => {
-            log::debug!(
-                "Successfully wrote to NVM3 counter {:?}",
-                cpc_nvm3_object_key
-            );
+    if transaction_id.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_last_transaction_id(cpc_nvm3_handle) {
+        Ok(last_transaction_id) => {
+            unsafe { *transaction_id = last_transaction_id };
             0
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
-/// @brief Read data from the specified counter.
+/// @brief Retrieve the protocol overhead of the data frames, which doesn't depend on any instance.
 ///
-/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
-/// @param[in]  cpc_nvm3_object_key  The key of the counter object to read data from.
-/// @param[out] value                   A pointer to the variable where the counter data will be stored.
-///                                     This value is optional, when a NULL pointer is provided, it
-///                                     will be ignored.
+/// @param[out] write_overhead    A pointer to the variable where the number of bytes of each write
+///                               frame that are not object data will be stored.
+/// @param[out] read_overhead     A pointer to the variable where the number of bytes of each read
+///                               response frame that are not object data will be stored.
 ///
-/// @return On success, the function returns 0. On error, it returns a negative value.
-///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
-///         indicating the type of error that occurred. If the connection to the CPC
-///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note Both values include the frame header. A write fragment carries at most the CPC maximum
+///       write size, reported by `cpc_nvm3_get_limits`, minus `write_overhead` bytes of data.
+///       The maximum fragment size reported by `cpc_nvm3_get_limits` can be smaller.
 #[no_mangle]
-pub extern "C" fn cpc_nvm3_read_counter(
-    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
-    value: *mut u32,
+pub extern "C" fn cpc_nvm3_get_protocol_overhead(
+    write_overhead: *mut u16,
+    read_overhead: *mut u16,
 ) -> i32 {
-    if value.is_null() {
+    if write_overhead.is_null() || read_overhead.is_null() {
         return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
     }
-    match nvm3::read_counter(cpc_nvm3_handle, cpc_nvm3_object_key) {
-        Ok(read_value) => {
-            unsafe { *value = read_value };
-            log::debug!("Successfully read NVM3 counter object");
-        }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                return error_code as i32;
-            }
-        },
+    let (write, read) = nvm3::get_protocol_overhead();
+    unsafe {
+        *write_overhead = write;
+        *read_overhead = read;
     }
     0
 }
 
-/// @brief Increment the specified counter.
+/// @brief Retrieve the statistics of the link to the CPC endpoint.
 ///
-/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
-/// @param[in]  cpc_nvm3_object_key  The key of the counter object to increment data from.
-/// @param[out] new_value            A pointer to the variable where the counter new value will be stored.
+/// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] reconnects        A pointer to the variable where the number of reconnections to
+///                               libcpc since the instance was initialized will be stored.
+/// @param[out] last_reconnected  A pointer to the variable set to true if the last operation
+///                               triggered a reconnection.
 ///
-/// @return On success, the function returns 0. On error, it returns a negative value.
-///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
-///         indicating the type of error that occurred. If the connection to the CPC
-///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///
+/// @note An operation that triggers a reconnection returns CPC_NVM3_TRY_AGAIN.
 #[no_mangle]
-pub extern "C" fn cpc_nvm3_increment_counter(
+pub extern "C" fn cpc_nvm3_get_link_stats(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
-    new_value: *mut u32,
+    reconnects: *mut u32,
+    last_reconnected: *mut bool,
 ) -> i32 {
-    match nvm3::increment_counter(cpc_nvm3_handle, cpc_nvm3_object_key) {
-        Ok(read_value) => {
-            log::debug!("Successfully incremented NVM3 counter");
-            if !new_value.is_null() {
-                unsafe { *new_value = read_value };
+    if reconnects.is_null() || last_reconnected.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+    match nvm3::get_link_stats(cpc_nvm3_handle) {
+        Ok((reconnect_count, last_op_reconnected)) => {
+            unsafe {
+                *reconnects = reconnect_count;
+                *last_reconnected = last_op_reconnected;
             }
             0
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
-/// @brief Retrieve the maximum allowable size for an object that can be written
-///        to the NVM3 instance on the remote device. The user must provide a
-///        valid handle obtained from the initialization process.
+/// @brief Retrieve the rolling average of the round trip of the commands.
 ///
 /// @param[in]  cpc_nvm3_handle   The handle to the CPC NVM3 instance.
+/// @param[out] average_us        A pointer to the variable where the average time from the
+///                               write of a request to the reception of its response, in
+///                               microseconds, will be stored.
 ///
 /// @return On success, the function returns 0.
 ///         On error, the function returns a negative value, corresponding to a specific
-///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. If no response was
+///         received since the instance was initialized, the function returns
+///         CPC_NVM3_FAILURE.
 ///
-/// @note Make sure to verify that the CPC NVM3 instance is opened and functional
-///       before calling this function, as it will fail otherwise.
+/// @note Each command weighs for 1/8 of the average. The round trip of each command is also
+///       logged at the TRACE level.
 #[no_mangle]
-pub extern "C" fn cpc_nvm3_get_maximum_write_size(
+pub extern "C" fn cpc_nvm3_get_round_trip_average(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
-    max_write: *mut u16,
+    average_us: *mut u32,
 ) -> i32 {
-    if max_write.is_null() {
+    if average_us.is_null() {
         return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
     }
-    match nvm3::get_maximum_write_size(cpc_nvm3_handle) {
-        Ok(maximum_write_size) => {
-            log::info!("Maximum write size is {} bytes", maximum_write_size);
-            unsafe { *max_write = maximum_write_size };
+    match nvm3::get_round_trip_average(cpc_nvm3_handle) {
+        Ok(average) => {
+            unsafe { *average_us = u32::try_from(average.as_micros()).unwrap_or(u32::MAX) };
             0
         }
-
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
@@ -614,12 +3298,42 @@ pub extern "C" fn cpc_nvm3_get_object_info(
             unsafe { *object_type = rxd_object_type };
             0
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Get the size of a data object, to size the buffer passed to `cpc_nvm3_read_data`.
+///
+/// @param[in]  cpc_nvm3_handle      The handle to the CPC NVM3 instance.
+/// @param[in]  cpc_nvm3_object_key  The key of the data object.
+/// @param[out] object_size          A pointer to the variable where the object size will be stored.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If there is no object with the key or
+///         the object is a counter, the function returns CPC_NVM3_INVALID_OBJECT_KEY.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_get_object_size(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: nvm3::cpc_nvm3_object_key_t,
+    object_size: *mut u16,
+) -> i32 {
+    if object_size.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    match nvm3::get_object_size(cpc_nvm3_handle, cpc_nvm3_object_key) {
+        Ok(size) => {
+            unsafe { *object_size = size };
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
@@ -642,12 +3356,127 @@ pub extern "C" fn cpc_nvm3_delete_object(
             log::debug!("Successfully deleted NVM3 object.");
             0
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Delete the objects of the CPC NVM3 instance in a bounded batch
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  max_deletions    Maximum number of objects to delete in this call. 0 deletes every object.
+/// @param[out] more_remaining   A pointer to the variable set to true if objects remain after this call.
+/// @param[out] deleted_count    A pointer to the variable where the number of deleted objects will be stored.
+///                              This value can be NULL.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note The instance is locked for the whole batch. Calling this function repeatedly with a
+///       small `max_deletions` until `more_remaining` is false keeps other operations responsive.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_delete_all_objects_bounded(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    max_deletions: u16,
+    more_remaining: *mut bool,
+    deleted_count: *mut u16,
+) -> i32 {
+    if more_remaining.is_null() {
+        return CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32;
+    }
+
+    let more_remaining_ref: &mut bool = unsafe { &mut *more_remaining };
+
+    match nvm3::delete_all_objects(cpc_nvm3_handle, max_deletions, more_remaining_ref) {
+        Ok(count) => {
+            log::debug!("Successfully deleted {} NVM3 objects", count);
+            if !deleted_count.is_null() {
+                unsafe { *deleted_count = count };
+            }
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Delete every object of a type, e.g. to clear the counters while keeping the data objects
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  object_type      The type of the objects to delete, CPC_NVM3_OBJECT_TYPE_COUNTER or
+///                              CPC_NVM3_OBJECT_TYPE_DATA.
+/// @param[out] deleted_count    A pointer to the variable where the number of deleted objects will be stored.
+///                              This value can be NULL.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If the connection to the CPC
+///         endpoint is lost, the function will return CPC_NVM3_TRY_AGAIN.
+///
+/// @note The type of every object is queried from the secondary, the instance is locked for the
+///       whole call. Objects deleted by someone else in the meantime are skipped.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_delete_objects_by_type(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    object_type: CpcNvm3ObjectType,
+    deleted_count: *mut u16,
+) -> i32 {
+    match nvm3::delete_objects_by_type(cpc_nvm3_handle, object_type) {
+        Ok(count) => {
+            log::debug!("Successfully deleted {} NVM3 objects", count);
+            if !deleted_count.is_null() {
+                unsafe { *deleted_count = count };
             }
-        },
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Move a data object to another key.
+///
+/// @param[in]  cpc_nvm3_handle  The handle to the CPC NVM3 instance.
+/// @param[in]  from_key         The key of the data object to move.
+/// @param[in]  to_key           The key the data object is moved to.
+/// @param[in]  overwrite        Whether an existing object at `to_key` can be overwritten.
+///
+/// @return On success, the function returns 0. On error, it returns a negative value.
+///         This negative number corresponds to a specific CpcNvm3ErrorCodes,
+///         indicating the type of error that occurred. If `from_key` is a counter, the
+///         function returns CPC_NVM3_WRONG_OBJECT_TYPE. If `to_key` already exists and
+///         `overwrite` is false, the function returns CPC_NVM3_INVALID_ARG.
+///
+/// @note The object at `from_key` is only deleted once the copy at `to_key` has been
+///       written and read back successfully, so a failure never loses the original data.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_move_object(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    from_key: nvm3::cpc_nvm3_object_key_t,
+    to_key: nvm3::cpc_nvm3_object_key_t,
+    overwrite: bool,
+) -> i32 {
+    match nvm3::move_object(cpc_nvm3_handle, from_key, to_key, overwrite) {
+        Ok(_) => {
+            log::debug!(
+                "Successfully moved NVM3 object {:?} to {:?}",
+                from_key,
+                to_key
+            );
+            0
+        }
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
@@ -655,12 +3484,18 @@ pub extern "C" fn cpc_nvm3_delete_object(
 /// of the provided arguments.
 ///
 /// @param[in]  cpc_nvm3_handle         The handle to the CPC NVM3 instance.
-/// @param[in]  seconds                 How many seconds to block.
-/// @param[in]  microseconds            How many microseconds to block.
+/// @param[in]  seconds                 How many seconds to block, it must not be negative.
+/// @param[in]  microseconds            How many microseconds to block, within [0, 999999].
 ///
 /// @return On success, the function returns 0.
 ///         On error, the function returns a negative value, corresponding to a specific
-///         CpcNvm3ErrorCodes, indicating the type of error that occurred.
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. An out of range
+///         argument or a zero timeout returns CPC_NVM3_INVALID_ARG.
+///
+/// @note A zero timeout would make libcpc block indefinitely. To return without waiting for
+///       the secondary, use `cpc_nvm3_set_nonblocking` instead.
+/// @note A reconnection opens a new endpoint, whose timeout is the default one set with
+///       `cpc_nvm3_set_default_cpc_timeout`.
 #[no_mangle]
 pub extern "C" fn cpc_nvm3_set_cpc_timeout(
     cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
@@ -669,12 +3504,67 @@ pub extern "C" fn cpc_nvm3_set_cpc_timeout(
 ) -> i32 {
     match nvm3::set_timeout(cpc_nvm3_handle, seconds, microseconds) {
         Ok(_) => 0,
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Set the timeout on CPC operations of the instances opened from now on. The timeout
+///        is the sum of the provided arguments, 5 seconds by default.
+///
+/// @param[in]  seconds                 How many seconds to block, it must not be negative.
+/// @param[in]  microseconds            How many microseconds to block, within [0, 999999].
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. An out of range
+///         argument or a zero timeout returns CPC_NVM3_INVALID_ARG.
+///
+/// @note The instances already opened keep their timeout until they reconnect, use
+///       `cpc_nvm3_set_cpc_timeout` to change it. The timeout also bounds each read of the
+///       handshake done by `cpc_nvm3_open`.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_default_cpc_timeout(seconds: i32, microseconds: i32) -> i32 {
+    match nvm3::set_default_read_timeout(seconds, microseconds) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("{}", err);
+            err.code() as i32
+        }
+    }
+}
+
+/// @brief Set the timeout applied while waiting for the response of a specific command. The
+///        timeout set with `cpc_nvm3_set_cpc_timeout` is restored once the response is received.
+///
+/// @param[in]  cpc_nvm3_handle         The handle to the CPC NVM3 instance.
+/// @param[in]  command_id              The command, one of the CPC_NVM3_COMMAND_* values.
+/// @param[in]  seconds                 How many seconds to block, it must not be negative.
+/// @param[in]  microseconds            How many microseconds to block, within [0, 999999].
+///
+/// @return On success, the function returns 0.
+///         On error, the function returns a negative value, corresponding to a specific
+///         CpcNvm3ErrorCodes, indicating the type of error that occurred. An unknown command
+///         or an out of range argument returns CPC_NVM3_INVALID_ARG.
+///
+/// @note A zero timeout removes the timeout of the command, which then uses the one set with
+///       `cpc_nvm3_set_cpc_timeout`. By default, CPC_NVM3_COMMAND_ENUMERATE_OBJECTS waits 15
+///       seconds, the other commands have no timeout of their own. The timeouts are reset by `cpc_nvm3_deinit`.
+#[no_mangle]
+pub extern "C" fn cpc_nvm3_set_command_timeout(
+    cpc_nvm3_handle: nvm3::cpc_nvm3_handle_t,
+    command_id: u8,
+    seconds: i32,
+    microseconds: i32,
+) -> i32 {
+    match nvm3::set_command_timeout(cpc_nvm3_handle, command_id, seconds, microseconds) {
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
 
@@ -702,11 +3592,9 @@ pub extern "C" fn cpc_nvm3_get_cpc_timeout(
             unsafe { *microseconds = configured_microseconds };
             0
         }
-        Err(err) => match err {
-            nvm3::CpcNvm3Error::ErrorCodeWithContext(error_code, context) => {
-                log::error!("{}", context);
-                error_code as i32
-            }
-        },
+        Err(err) => {
+            log::error!("[{}] {}", nvm3::instance_label(cpc_nvm3_handle), err);
+            err.code() as i32
+        }
     }
 }
@@ -0,0 +1,115 @@
+/*******************************************************************************
+* @file
+ * @brief Co-Processor Communication Protocol(CPC) NVM3 - Async adapter
+ *******************************************************************************
+ * # License
+ * <b>Copyright 2023 Silicon Laboratories Inc. www.silabs.com</b>
+ *******************************************************************************
+ *
+ * The licensor of this software is Silicon Laboratories Inc. Your use of this
+ * software is governed by the terms of Silicon Labs Master Software License
+ * Agreement (MSLA) available at
+ * www.silabs.com/about-us/legal/master-software-license-agreement. This
+ * software is distributed to you in Source Code format and is governed by the
+ * sections of the MSLA applicable to Source Code.
+ *
+ ******************************************************************************/
+use super::{cpc_nvm3_handle_t, cpc_nvm3_object_key_t, CpcNvm3Error};
+use crate::CpcNvm3ErrorCodes;
+
+// Every operation blocks on the CPC endpoint, so it runs on the blocking thread pool of
+// the runtime instead of tying up one of its worker threads.
+async fn run_blocking<T, F>(operation: F) -> Result<T, CpcNvm3Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, CpcNvm3Error> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(operation).await {
+        Ok(result) => result,
+        Err(err) => Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+            format!("The blocking NVM3 operation did not complete: {}", err),
+        )),
+    }
+}
+
+/// Async wrapper around an initialized CPC NVM3 handle. The handle is still opened,
+/// closed and de-initialized with the regular API.
+#[derive(Debug, Clone, Copy)]
+pub struct Nvm3 {
+    handle: cpc_nvm3_handle_t,
+}
+
+impl Nvm3 {
+    pub fn new(handle: cpc_nvm3_handle_t) -> Self {
+        Self { handle }
+    }
+
+    pub fn handle(&self) -> cpc_nvm3_handle_t {
+        self.handle
+    }
+
+    /// Read a data object of at most `max_size` bytes
+    pub async fn read_data(
+        &self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        max_size: u16,
+    ) -> Result<Vec<u8>, CpcNvm3Error> {
+        let handle = self.handle;
+        run_blocking(move || {
+            let mut buffer = vec![0u8; max_size as usize];
+            let mut data_size = 0;
+            super::read_data(handle, cpc_nvm3_object_key, &mut buffer, &mut data_size)?;
+            buffer.truncate(data_size as usize);
+            Ok(buffer)
+        })
+        .await
+    }
+
+    pub async fn write_data(
+        &self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        data: Vec<u8>,
+    ) -> Result<u16, CpcNvm3Error> {
+        let handle = self.handle;
+        run_blocking(move || super::write_data(handle, cpc_nvm3_object_key, &data)).await
+    }
+
+    pub async fn read_counter(
+        &self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<u32, CpcNvm3Error> {
+        let handle = self.handle;
+        run_blocking(move || super::read_counter(handle, cpc_nvm3_object_key)).await
+    }
+
+    pub async fn write_counter(
+        &self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        value: u32,
+    ) -> Result<(), CpcNvm3Error> {
+        let handle = self.handle;
+        run_blocking(move || super::write_counter(handle, cpc_nvm3_object_key, value)).await
+    }
+
+    pub async fn increment_counter(
+        &self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<u32, CpcNvm3Error> {
+        let handle = self.handle;
+        run_blocking(move || super::increment_counter(handle, cpc_nvm3_object_key)).await
+    }
+
+    pub async fn delete_object(
+        &self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<(), CpcNvm3Error> {
+        let handle = self.handle;
+        run_blocking(move || super::delete_object(handle, cpc_nvm3_object_key)).await
+    }
+
+    pub async fn collect_object_keys(&self) -> Result<Vec<cpc_nvm3_object_key_t>, CpcNvm3Error> {
+        let handle = self.handle;
+        run_blocking(move || super::collect_object_keys(handle)).await
+    }
+}
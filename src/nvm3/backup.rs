@@ -0,0 +1,198 @@
+/*******************************************************************************
+* @file
+ * @brief Co-Processor Communication Protocol(CPC) NVM3 - Backup file format
+ *******************************************************************************
+ * # License
+ * <b>Copyright 2023 Silicon Laboratories Inc. www.silabs.com</b>
+ *******************************************************************************
+ *
+ * The licensor of this software is Silicon Laboratories Inc. Your use of this
+ * software is governed by the terms of Silicon Labs Master Software License
+ * Agreement (MSLA) available at
+ * www.silabs.com/about-us/legal/master-software-license-agreement. This
+ * software is distributed to you in Source Code format and is governed by the
+ * sections of the MSLA applicable to Source Code.
+ *
+ ******************************************************************************/
+//! A backup file is laid out as follows, every integer being little endian:
+//!
+//! | Field          | Size | Description                                      |
+//! |----------------|------|--------------------------------------------------|
+//! | magic          | 4    | `CN3B`                                           |
+//! | format version | 1    | `CPC_NVM3_BACKUP_FORMAT_VERSION`                 |
+//! | lib version    | 3    | Major, minor and patch of the library that wrote it |
+//! | record count   | 4    | Number of records that follow                    |
+//! | records        | ...  | See below                                        |
+//! | file CRC       | 4    | CRC-32 of every byte before it                   |
+//!
+//! Each record holds the object key (4 bytes), the object type (1 byte), the payload
+//! length (2 bytes), the payload and a CRC-32 of the record bytes before it (4 bytes).
+//! The payload of a counter is its 4 bytes value.
+
+use super::{cpc_nvm3_object_key_t, CpcNvm3Error};
+use super::{CPC_NVM3_MAJOR_VERSION, CPC_NVM3_MINOR_VERSION, CPC_NVM3_PATCH_VERSION};
+use crate::CpcNvm3ErrorCodes;
+use nom::number::complete::{le_u16, le_u32};
+
+const CPC_NVM3_BACKUP_MAGIC: &[u8; 4] = b"CN3B";
+const CPC_NVM3_BACKUP_FORMAT_VERSION: u8 = 1;
+const CPC_NVM3_BACKUP_RECORD_DATA: u8 = 0;
+const CPC_NVM3_BACKUP_RECORD_COUNTER: u8 = 1;
+const CPC_NVM3_BACKUP_CRC_SIZE: usize = std::mem::size_of::<u32>();
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BackupRecord {
+    Data(cpc_nvm3_object_key_t, Vec<u8>),
+    Counter(cpc_nvm3_object_key_t, u32),
+}
+
+// CRC-32 (IEEE 802.3), the backups are small enough for the bitwise variant
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn corrupt(context: String) -> CpcNvm3Error {
+    CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_BACKUP_CORRUPT, context)
+}
+
+fn truncated(_: nom::Err<nom::error::Error<&[u8]>>) -> CpcNvm3Error {
+    corrupt("The backup is truncated".to_string())
+}
+
+pub fn encode(records: &[BackupRecord]) -> Result<Vec<u8>, CpcNvm3Error> {
+    let mut file = Vec::new();
+    file.extend_from_slice(CPC_NVM3_BACKUP_MAGIC);
+    file.push(CPC_NVM3_BACKUP_FORMAT_VERSION);
+    file.extend_from_slice(&[
+        CPC_NVM3_MAJOR_VERSION,
+        CPC_NVM3_MINOR_VERSION,
+        CPC_NVM3_PATCH_VERSION,
+    ]);
+    file.extend_from_slice(&(records.len() as u32).to_le_bytes());
+
+    for record in records {
+        let (key, record_type, payload) = match record {
+            BackupRecord::Data(key, data) => (*key, CPC_NVM3_BACKUP_RECORD_DATA, data.clone()),
+            BackupRecord::Counter(key, value) => (
+                *key,
+                CPC_NVM3_BACKUP_RECORD_COUNTER,
+                value.to_le_bytes().to_vec(),
+            ),
+        };
+        let payload_len: u16 = payload.len().try_into().map_err(|_| {
+            CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                format!("The object {} is too large to be backed up", key),
+            )
+        })?;
+
+        let record_start = file.len();
+        file.extend_from_slice(&key.to_le_bytes());
+        file.push(record_type);
+        file.extend_from_slice(&payload_len.to_le_bytes());
+        file.extend_from_slice(&payload);
+        let record_crc = crc32(&file[record_start..]);
+        file.extend_from_slice(&record_crc.to_le_bytes());
+    }
+
+    let file_crc = crc32(&file);
+    file.extend_from_slice(&file_crc.to_le_bytes());
+    Ok(file)
+}
+
+/// Decode a backup file, every check is done before a single record is returned so that
+/// a corrupted file never reaches the secondary.
+pub fn decode(file: &[u8]) -> Result<Vec<BackupRecord>, CpcNvm3Error> {
+    if file.len() < CPC_NVM3_BACKUP_MAGIC.len() + CPC_NVM3_BACKUP_CRC_SIZE
+        || &file[..CPC_NVM3_BACKUP_MAGIC.len()] != CPC_NVM3_BACKUP_MAGIC
+    {
+        return Err(corrupt("The file is not a CPC NVM3 backup".to_string()));
+    }
+
+    let (content, file_crc) = file.split_at(file.len() - CPC_NVM3_BACKUP_CRC_SIZE);
+    let expected_crc = crc32(content);
+    let actual_crc = u32::from_le_bytes([file_crc[0], file_crc[1], file_crc[2], file_crc[3]]);
+    if expected_crc != actual_crc {
+        return Err(corrupt(format!(
+            "The backup CRC {:#010x} does not match its content {:#010x}",
+            actual_crc, expected_crc
+        )));
+    }
+
+    let remaining = &content[CPC_NVM3_BACKUP_MAGIC.len()..];
+    let (remaining, format_version) = nom::number::complete::u8(remaining).map_err(truncated)?;
+    if format_version != CPC_NVM3_BACKUP_FORMAT_VERSION {
+        return Err(corrupt(format!(
+            "The backup format version {} is not supported. Expected {}",
+            format_version, CPC_NVM3_BACKUP_FORMAT_VERSION
+        )));
+    }
+    let (remaining, major_version) = nom::number::complete::u8(remaining).map_err(truncated)?;
+    let (remaining, minor_version) = nom::number::complete::u8(remaining).map_err(truncated)?;
+    let (remaining, patch_version) = nom::number::complete::u8(remaining).map_err(truncated)?;
+    log::debug!(
+        "Decoding a backup written by the CPC NVM3 library v{}.{}.{}",
+        major_version,
+        minor_version,
+        patch_version
+    );
+    let (mut remaining, record_count) = le_u32(remaining).map_err(truncated)?;
+
+    let mut records = Vec::new();
+    for index in 0..record_count {
+        let record_start = remaining;
+        let (rest, key) = le_u32(remaining).map_err(truncated)?;
+        let (rest, record_type) = nom::number::complete::u8(rest).map_err(truncated)?;
+        let (rest, payload_len) = le_u16(rest).map_err(truncated)?;
+        let (rest, payload) =
+            nom::bytes::complete::take(payload_len as usize)(rest).map_err(truncated)?;
+        let record_len = record_start.len() - rest.len();
+        let (rest, record_crc) = le_u32(rest).map_err(truncated)?;
+        if crc32(&record_start[..record_len]) != record_crc {
+            return Err(corrupt(format!(
+                "The CRC of record {} (object {}) does not match its content",
+                index, key
+            )));
+        }
+
+        let record = match record_type {
+            CPC_NVM3_BACKUP_RECORD_DATA => BackupRecord::Data(key, payload.to_vec()),
+            CPC_NVM3_BACKUP_RECORD_COUNTER => match payload.try_into() {
+                Ok(value) => BackupRecord::Counter(key, u32::from_le_bytes(value)),
+                Err(_) => {
+                    return Err(corrupt(format!(
+                        "The counter {} has a {} bytes value",
+                        key, payload_len
+                    )))
+                }
+            },
+            record_type => {
+                return Err(corrupt(format!(
+                    "The object {} has an unknown record type {}",
+                    key, record_type
+                )))
+            }
+        };
+        records.push(record);
+        remaining = rest;
+    }
+
+    if !remaining.is_empty() {
+        return Err(corrupt(format!(
+            "The backup has {} unexpected bytes after its last record",
+            remaining.len()
+        )));
+    }
+    Ok(records)
+}
@@ -0,0 +1,127 @@
+/*******************************************************************************
+* @file
+ * @brief Co-Processor Communication Protocol(CPC) NVM3 - Fair Mutex
+ *******************************************************************************
+ * # License
+ * <b>Copyright 2023 Silicon Laboratories Inc. www.silabs.com</b>
+ *******************************************************************************
+ *
+ * The licensor of this software is Silicon Laboratories Inc. Your use of this
+ * software is governed by the terms of Silicon Labs Master Software License
+ * Agreement (MSLA) available at
+ * www.silabs.com/about-us/legal/master-software-license-agreement. This
+ * software is distributed to you in Source Code format and is governed by the
+ * sections of the MSLA applicable to Source Code.
+ *
+ ******************************************************************************/
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, LockResult, Mutex, PoisonError, TryLockError, TryLockResult};
+
+struct Tickets {
+    next: u64,
+    serving: u64,
+    // Same as `std::sync::Mutex`, a guard dropped by a panicking thread poisons the lock
+    poisoned: bool,
+}
+
+// A ticket lock: every locker draws a ticket and the lock is granted in ticket order,
+// so no thread can be starved by another one repeatedly re-acquiring the lock.
+pub struct FairMutex<T> {
+    tickets: Mutex<Tickets>,
+    turn: Condvar,
+    data: UnsafeCell<T>,
+}
+
+// The data is only ever accessed through a guard, and only one guard can exist at a time
+unsafe impl<T: Send> Send for FairMutex<T> {}
+unsafe impl<T: Send> Sync for FairMutex<T> {}
+
+pub struct FairMutexGuard<'a, T> {
+    mutex: &'a FairMutex<T>,
+    // A guard taken while the thread was already panicking doesn't poison the lock
+    panicking: bool,
+}
+
+impl<T> FairMutex<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            tickets: Mutex::new(Tickets {
+                next: 0,
+                serving: 0,
+                poisoned: false,
+            }),
+            turn: Condvar::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> LockResult<FairMutexGuard<'_, T>> {
+        // The ticket counters are never left in an inconsistent state, so a poisoned
+        // inner mutex can safely be recovered
+        let mut tickets = self.tickets.lock().unwrap_or_else(PoisonError::into_inner);
+        let ticket = tickets.next;
+        tickets.next = tickets.next.wrapping_add(1);
+        while tickets.serving != ticket {
+            tickets = self
+                .turn
+                .wait(tickets)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        let poisoned = tickets.poisoned;
+        drop(tickets);
+        self.guard(poisoned)
+    }
+
+    pub fn try_lock(&self) -> TryLockResult<FairMutexGuard<'_, T>> {
+        let mut tickets = self.tickets.lock().unwrap_or_else(PoisonError::into_inner);
+        if tickets.next != tickets.serving {
+            return Err(TryLockError::WouldBlock);
+        }
+        tickets.next = tickets.next.wrapping_add(1);
+        let poisoned = tickets.poisoned;
+        drop(tickets);
+        self.guard(poisoned).map_err(TryLockError::from)
+    }
+
+    fn guard(&self, poisoned: bool) -> LockResult<FairMutexGuard<'_, T>> {
+        let guard = FairMutexGuard {
+            mutex: self,
+            panicking: std::thread::panicking(),
+        };
+        if poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+impl<T> Deref for FairMutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for FairMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for FairMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut tickets = self
+            .mutex
+            .tickets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !self.panicking && std::thread::panicking() {
+            tickets.poisoned = true;
+        }
+        tickets.serving = tickets.serving.wrapping_add(1);
+        drop(tickets);
+        self.mutex.turn.notify_all();
+    }
+}
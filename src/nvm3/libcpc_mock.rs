@@ -1,5 +1,6 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
+use std::time::Duration;
 
 pub use libcpc::cpc_endpoint_id;
 pub use libcpc::cpc_endpoint_read_flags_t_enum;
@@ -12,19 +13,44 @@ use crate::nvm3::CPC_NVM3_MAJOR_VERSION;
 use crate::nvm3::CPC_NVM3_MINOR_VERSION;
 use crate::nvm3::CPC_NVM3_PATCH_VERSION;
 
-const CPC_NVM3_MAX_WRITE_CAPABILITY: usize = 256;
+pub const CPC_NVM3_MAX_WRITE_CAPABILITY: usize = 256;
+pub const CPC_NVM3_MOCK_MAX_OBJECT_SIZE: u16 = 0x0200;
+
+thread_local! {
+    // The number of upcoming `init` calls that fail, like they do while CPCd is not running
+    static INIT_FAILURES: Cell<u32> = Cell::new(0);
+    // The frames read by the next opened endpoint once the handshake is over, for the
+    // operations that open their own instance
+    static NEXT_ENDPOINT_RX: RefCell<Vec<Vec<u8>>> = RefCell::new(Vec::new());
+    // Whether the next opened endpoint rejects the maximum object size property, like the
+    // secondaries that predate it
+    static REJECT_MAX_OBJECT_SIZE: Cell<bool> = Cell::new(false);
+}
 
 #[allow(non_camel_case_types)] // This will be used in a generated a C header file
 #[derive(Debug, Copy, Clone)]
 pub struct cpc_handle;
 
+// What the next read of the endpoint does
+#[derive(Debug)]
+enum MockRx {
+    Frame(Vec<u8>),
+    Error(std::io::ErrorKind),
+    // Block for the given time, then time out like a libcpc read without any data
+    Timeout(Duration),
+}
+
 #[allow(non_camel_case_types)] // This will be used in a generated a C header file
 #[derive(Debug)]
 pub struct cpc_endpoint {
-    // A queue of byte vectors for testing. `RefCell` enables interior mutability,
+    // A queue of reads for testing. `RefCell` enables interior mutability,
     // allowing us to modify the queue with an immutable reference to the `CpcNvm3Instance`
-    test_data_fifo_rx: RefCell<VecDeque<Vec<u8>>>,
-    _test_data_fifo_tx: RefCell<VecDeque<Vec<u8>>>,
+    test_data_fifo_rx: RefCell<VecDeque<MockRx>>,
+    // Every frame written to the endpoint, in order, for the tests to inspect
+    test_data_fifo_tx: RefCell<VecDeque<Vec<u8>>>,
+    // The read timeout as (seconds, microseconds), and the one each read was done with
+    read_timeout: Cell<(i32, i32)>,
+    test_read_timeouts: RefCell<Vec<(i32, i32)>>,
 }
 
 impl cpc_handle {
@@ -35,7 +61,9 @@ impl cpc_handle {
     ) -> Result<cpc_endpoint, Error> {
         let mut endpoint = cpc_endpoint {
             test_data_fifo_rx: RefCell::new(VecDeque::new()),
-            _test_data_fifo_tx: RefCell::new(VecDeque::new()),
+            test_data_fifo_tx: RefCell::new(VecDeque::new()),
+            read_timeout: Cell::new((0, 0)),
+            test_read_timeouts: RefCell::new(Vec::new()),
         };
 
         let version_response = vec![
@@ -72,13 +100,71 @@ impl cpc_handle {
         // so it makes sense to prepare it this response right away.
         endpoint.push_rx(maximum_write_response);
 
+        let mut maximum_object_response = vec![
+            0x05, // cmd
+            0x03, // len
+            0x00, // len
+            0x00, // unique_id
+            0x00, // unique_id
+            0x00, // unique_id
+            0x00, // unique_id
+            0x03, // seq
+            0x01, // prop
+        ];
+        maximum_object_response.extend_from_slice(&CPC_NVM3_MOCK_MAX_OBJECT_SIZE.to_le_bytes());
+        if REJECT_MAX_OBJECT_SIZE.with(|reject| reject.replace(false)) {
+            maximum_object_response = vec![
+                0x02, // cmd
+                0x05, // len
+                0x00, // len
+                0x00, // unique_id
+                0x00, // unique_id
+                0x00, // unique_id
+                0x00, // unique_id
+                0x03, // seq
+                0x00, // sl_status
+                0x01, // SL_STATUS_FAIL
+                0x00, // SL_STATUS_FAIL
+                0x00, // SL_STATUS_FAIL
+                0x00, // SL_STATUS_FAIL
+            ];
+        }
+        // The maximum object size is the last property queried while opening
+        endpoint.push_rx(maximum_object_response);
+
+        for rx_data in NEXT_ENDPOINT_RX.with(|frames| frames.take()) {
+            endpoint.push_rx(rx_data);
+        }
+
         Ok(endpoint)
     }
 }
 
 impl cpc_endpoint {
     pub fn push_rx(&mut self, rx_data: Vec<u8>) {
-        self.test_data_fifo_rx.borrow_mut().push_back(rx_data);
+        self.test_data_fifo_rx
+            .borrow_mut()
+            .push_back(MockRx::Frame(rx_data));
+    }
+
+    pub fn push_rx_error(&mut self, kind: std::io::ErrorKind) {
+        self.test_data_fifo_rx
+            .borrow_mut()
+            .push_back(MockRx::Error(kind));
+    }
+
+    pub fn push_rx_timeout(&mut self, delay: Duration) {
+        self.test_data_fifo_rx
+            .borrow_mut()
+            .push_back(MockRx::Timeout(delay));
+    }
+
+    pub fn pop_tx(&mut self) -> Option<Vec<u8>> {
+        self.test_data_fifo_tx.borrow_mut().pop_front()
+    }
+
+    pub fn take_read_timeouts(&mut self) -> Vec<(i32, i32)> {
+        self.test_read_timeouts.take()
     }
 
     pub fn close(&self) -> Result<(), Error> {
@@ -87,17 +173,40 @@ impl cpc_endpoint {
 
     pub fn write(
         &self,
-        _data: &Vec<u8>,
+        data: &Vec<u8>,
         _flags: &[cpc_endpoint_write_flags_t_enum],
     ) -> Result<(), Error> {
+        self.test_data_fifo_tx.borrow_mut().push_back(data.clone());
         Ok(())
     }
 
-    pub fn read(&self, _flags: &[cpc_endpoint_read_flags_t_enum]) -> Result<Vec<u8>, Error> {
-        let mut test_data_fifo: std::cell::RefMut<VecDeque<Vec<u8>>> =
+    pub fn read(&self, flags: &[cpc_endpoint_read_flags_t_enum]) -> Result<Vec<u8>, Error> {
+        self.test_read_timeouts
+            .borrow_mut()
+            .push(self.read_timeout.get());
+        let mut test_data_fifo: std::cell::RefMut<VecDeque<MockRx>> =
             self.test_data_fifo_rx.borrow_mut();
+        let nonblocking = flags.iter().any(|flag| {
+            matches!(
+                flag,
+                cpc_endpoint_read_flags_t_enum::CPC_ENDPOINT_READ_FLAG_NON_BLOCKING
+            )
+        });
         let test_data = match test_data_fifo.pop_front() {
-            Some(test_data) => test_data,
+            Some(MockRx::Frame(test_data)) => test_data,
+            Some(MockRx::Error(kind)) => return Err(Error::Errno(std::io::Error::from(kind))),
+            Some(MockRx::Timeout(delay)) => {
+                std::thread::sleep(delay);
+                return Err(Error::Errno(std::io::Error::from(
+                    std::io::ErrorKind::WouldBlock,
+                )));
+            }
+            // Like libcpc, a non-blocking read without data fails right away
+            None if nonblocking => {
+                return Err(Error::Errno(std::io::Error::from(
+                    std::io::ErrorKind::WouldBlock,
+                )))
+            }
             None => return Err(Error::Errno(std::io::Error::from_raw_os_error(-1))),
         };
         log::debug!("Read {:?}", test_data);
@@ -105,14 +214,17 @@ impl cpc_endpoint {
     }
 
     pub fn get_read_timeout(&self) -> Result<cpc_timeval_t, Error> {
+        let (seconds, microseconds) = self.read_timeout.get();
         let timeval = cpc_timeval_t {
-            seconds: 0,
-            microseconds: 0,
+            seconds,
+            microseconds,
         };
         return Ok(timeval);
     }
 
-    pub fn set_read_timeout(&self, _timeval: cpc_timeval_t) -> Result<(), Error> {
+    pub fn set_read_timeout(&self, timeval: cpc_timeval_t) -> Result<(), Error> {
+        self.read_timeout
+            .set((timeval.seconds, timeval.microseconds));
         Ok(())
     }
 
@@ -121,11 +233,29 @@ impl cpc_endpoint {
     }
 }
 
+pub fn fail_next_inits(count: u32) {
+    INIT_FAILURES.with(|failures| failures.set(count));
+}
+
+pub fn reject_max_object_size_on_next_open() {
+    REJECT_MAX_OBJECT_SIZE.with(|reject| reject.set(true));
+}
+
+pub fn push_rx_on_next_open(rx_data: Vec<u8>) {
+    NEXT_ENDPOINT_RX.with(|frames| frames.borrow_mut().push(rx_data));
+}
+
 pub fn init(
     _instance_name: &str,
     _enable_tracing: bool,
     _reset_callback: std::option::Option<unsafe extern "C" fn()>,
 ) -> Result<cpc_handle, Error> {
+    if INIT_FAILURES.with(|failures| failures.get()) > 0 {
+        INIT_FAILURES.with(|failures| failures.set(failures.get() - 1));
+        return Err(Error::Errno(std::io::Error::from(
+            std::io::ErrorKind::ConnectionRefused,
+        )));
+    }
     let handle = cpc_handle {};
     Ok(handle)
 }
@@ -14,6 +14,11 @@
  * sections of the MSLA applicable to Source Code.
  *
  ******************************************************************************/
+#[cfg(feature = "async")]
+pub mod r#async;
+mod backup;
+#[cfg(feature = "fair-lock")]
+mod fair_mutex;
 #[cfg(test)]
 mod libcpc_mock;
 #[cfg(test)]
@@ -22,8 +27,10 @@ mod tests;
 use crate::protocol;
 use crate::protocol::*;
 use crate::CpcNvm3ErrorCodes;
+use crate::CpcNvm3Limits;
 use crate::CpcNvm3LogLevel;
 use crate::CpcNvm3ObjectType;
+use crate::CpcNvm3SessionInfo;
 use chrono::Local;
 use libc::STDOUT_FILENO;
 use log::{LevelFilter, Log, Metadata, Record};
@@ -35,8 +42,13 @@ use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::os::unix::io::FromRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 // Configure the mock CPC endpoint and handle if we are running tests
@@ -52,46 +64,161 @@ const CPC_NVM3_PATCH_VERSION: u8 = pkg_version::pkg_version_patch!();
 const CPC_NVM3_OBJECT_KEY_SIZE: usize = std::mem::size_of::<cpc_nvm3_object_key_t>();
 
 const CPC_NVM3_READ_TIMEOUT_S: i32 = 5;
+const CPC_NVM3_OPEN_TIMEOUT_S: u64 = 15;
+const CPC_NVM3_ENUMERATE_TIMEOUT_S: u64 = 15;
 const CPC_ENDPOINT_TX_WINDOW: u8 = 1;
+const CPC_NVM3_MAX_EMPTY_READS: u8 = 3;
+const CPC_NVM3_MAX_RESYNC_ATTEMPTS: u8 = 3;
+const CPC_NVM3_MAX_MISMATCHED_RESPONSES: u16 = 32;
+const CPC_NVM3_DESYNC_THRESHOLD: u16 = 8;
+const CPC_NVM3_MAX_LABEL_LEN: usize = 32;
+// The most keys an enumeration can request, the bound of its reassembly by default
+const CPC_NVM3_MAX_ENUMERATION_SIZE: usize = u16::MAX as usize * CPC_NVM3_OBJECT_KEY_SIZE;
 
 lazy_static::lazy_static! {
+    // Whether `FORWARDING_LOGGER` was installed, which `log` only allows once per process
     static ref LOGGER_INITIALIZED: Mutex<bool> = Mutex::new(false);
+    // Where `FORWARDING_LOGGER` writes the records, replaced by `init_logger` after a
+    // `shutdown_logger`
+    static ref LOGGER_SINK: RwLock<Option<FileLogger>> = RwLock::new(None);
     static ref CPC_NVM_LIB_INSTANCE_KEY: Mutex<u32> = Mutex::new(1);
+    // The read timeout of the endpoints opened from now on, see `set_default_read_timeout`
+    static ref CPC_NVM3_DEFAULT_READ_TIMEOUT: Mutex<Duration> =
+        Mutex::new(Duration::from_secs(CPC_NVM3_READ_TIMEOUT_S as u64));
 
     // We use Arc<Mutex<...>> to safely share the mutable instances across multiple threads.
     // Arc is an atomic reference count that manages the lifetime and shared ownership of the instances
-    static ref CPC_NVM3_LIB_INSTANCES: Mutex<HashMap<cpc_nvm3_handle_t, Arc<Mutex<CpcNvm3Instance>>>> = Mutex::new(HashMap::new());
+    static ref CPC_NVM3_LIB_INSTANCES: Mutex<HashMap<cpc_nvm3_handle_t, Arc<InstanceMutex>>> = Mutex::new(HashMap::new());
+
+    // The interrupt flag of each instance, kept apart so that it can be raised while
+    // an operation holds the instance lock
+    static ref CPC_NVM3_LIB_INTERRUPTS: Mutex<HashMap<cpc_nvm3_handle_t, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
 }
 
+// Every operation on a handle serializes through the instance lock. `std::sync::Mutex`
+// has the lowest overhead but makes no fairness guarantee, so a thread hammering a shared
+// handle can starve the others. The `fair-lock` feature swaps in a ticket lock that hands
+// the lock over in FIFO order, at the cost of waking every waiter on each unlock.
+#[cfg(not(feature = "fair-lock"))]
+type InstanceMutex = Mutex<CpcNvm3Instance>;
+#[cfg(not(feature = "fair-lock"))]
+type InstanceGuard<'a> = std::sync::MutexGuard<'a, CpcNvm3Instance>;
+#[cfg(feature = "fair-lock")]
+type InstanceMutex = fair_mutex::FairMutex<CpcNvm3Instance>;
+#[cfg(feature = "fair-lock")]
+type InstanceGuard<'a> = fair_mutex::FairMutexGuard<'a, CpcNvm3Instance>;
+
+// The structured variants carry what the caller can act on, every other error is an error
+// code along with a description. `code` gives the error code of any variant, which is what
+// the C API returns, and the description is the `Display` of the error.
 #[derive(Error, Debug)]
 pub enum CpcNvm3Error {
-    #[error("CPC NVM3 Error")]
+    #[error("{1}")]
     ErrorCodeWithContext(CpcNvm3ErrorCodes, String),
+    #[error("The CPC NVM3 instance is not opened. Call cpc_nvm3_open first.")]
+    NotOpen,
+    #[error("The NVM3 object key {0} is invalid or does not exist")]
+    InvalidKey(cpc_nvm3_object_key_t),
+    #[error("The provided buffer is too small, {needed} are needed but it holds {got}")]
+    BufferTooSmall { needed: usize, got: usize },
+    #[error("The operation did not complete before its deadline")]
+    Timeout,
+    #[error("libcpc error: {0}")]
+    Cpc(cpc::Error),
+    #[error("{}", protocol_error_context(.0))]
+    Protocol(ProtocolError),
+    #[error(
+        "Received too many mismatched responses ({responses}) for {command}. {}",
+        protocol_error_context(.last_error)
+    )]
+    TooManyMismatchedResponses {
+        responses: u16,
+        command: &'static str,
+        last_error: ProtocolError,
+    },
+}
+
+impl CpcNvm3Error {
+    pub fn code(&self) -> CpcNvm3ErrorCodes {
+        match self {
+            CpcNvm3Error::ErrorCodeWithContext(error_code, _) => *error_code,
+            CpcNvm3Error::NotOpen => CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
+            CpcNvm3Error::InvalidKey(_) => CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
+            CpcNvm3Error::BufferTooSmall { .. } => CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL,
+            CpcNvm3Error::Timeout => CpcNvm3ErrorCodes::CPC_NVM3_TIMEOUT,
+            CpcNvm3Error::Cpc(_) => CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR,
+            CpcNvm3Error::Protocol(error) => protocol_error_code(error),
+            CpcNvm3Error::TooManyMismatchedResponses { .. } => CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+        }
+    }
+
+    // Whether the error only concerns the object that was accessed, the secondary can still
+    // be reached for the other objects
+    fn is_object_error(&self) -> bool {
+        matches!(self, CpcNvm3Error::InvalidKey(_))
+            || self.code() == CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE
+    }
 }
 
 impl From<cpc::Error> for CpcNvm3Error {
     fn from(error: cpc::Error) -> Self {
         match error {
-            cpc::Error::Errno(errno) => {
-                if errno.kind() == std::io::ErrorKind::WouldBlock {
-                    return CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
-                        format!("libcpc error: {} Try again", errno),
-                    );
-                }
+            cpc::Error::Errno(errno) if errno.kind() == std::io::ErrorKind::WouldBlock => {
                 CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR,
-                    format!("libcpc error: {}", errno),
+                    CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+                    format!("libcpc error: {} Try again", errno),
                 )
             }
-            error => CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR,
-                format!("libcpc error: {}", error),
-            ),
+            error => CpcNvm3Error::Cpc(error),
         }
     }
 }
 
+// Every ECode a command doesn't handle itself goes through here. The ECodes the host can
+// act upon get their own error code, the others are reported as a generic failure.
+impl From<ECode> for CpcNvm3Error {
+    fn from(e_code: ECode) -> Self {
+        let error_code = match e_code {
+            ECode::NotOpened => CpcNvm3ErrorCodes::CPC_NVM3_SECONDARY_NOT_READY,
+            ECode::StorageFull => CpcNvm3ErrorCodes::CPC_NVM3_STORAGE_FULL,
+            ECode::NvmAccess => CpcNvm3ErrorCodes::CPC_NVM3_IO_ERROR,
+            ECode::Parameter => CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            ECode::ObjectSizeNotSupported => CpcNvm3ErrorCodes::CPC_NVM3_OBJECT_TOO_LARGE,
+            ECode::ObjectIsNotData | ECode::ObjectIsNotACounter => {
+                CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE
+            }
+            ECode::Unknown => CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
+            _ => CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+        };
+        CpcNvm3Error::ErrorCodeWithContext(
+            error_code,
+            format!("The secondary reported: {}", e_code),
+        )
+    }
+}
+
+// An object was about to be accessed as another type than its own, `object_type`
+fn wrong_object_type(
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    object_type: CpcNvm3ObjectType,
+) -> CpcNvm3Error {
+    let context = match object_type {
+        CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER => format!(
+            "The object {} is a counter, access it with the counter functions",
+            cpc_nvm3_object_key
+        ),
+        CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA => format!(
+            "The object {} is a data object, access it with the data functions",
+            cpc_nvm3_object_key
+        ),
+        _ => format!(
+            "The object {} has an unexpected type {}",
+            cpc_nvm3_object_key, object_type
+        ),
+    };
+    CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE, context)
+}
+
 impl From<CpcNvm3LogLevel> for log::LevelFilter {
     fn from(level: CpcNvm3LogLevel) -> Self {
         match level {
@@ -105,49 +232,51 @@ impl From<CpcNvm3LogLevel> for log::LevelFilter {
     }
 }
 
+// The protocol errors are kept as is for the Rust callers to match on them, the C API only
+// gets their error code
 impl From<ProtocolError> for CpcNvm3Error {
     fn from(error: ProtocolError) -> Self {
-        match error {
-            ProtocolError::Bug(context) => CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                format!("Bug: {}", context),
-            ),
-            ProtocolError::UnknownProcotolError => CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                "Unknown protocol error".to_string(),
-            ),
-            ProtocolError::InvalidTransactionId(expected_id, received_id) => {
-                let context = format!(
-                    "Received a response with an invalid transaction id: expected {}, received {}",
-                    expected_id, received_id
-                );
-                CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, context)
-            }
-            ProtocolError::InvalidCommandId => {
-                let context = format!("Received a response with an invalid command id");
-                CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, context)
-            }
-            ProtocolError::InvalidUniqueId(expected_id, received_id) => {
-                let context = format!(
-                    "Received a response with an invalid unique id: expected {}, received {}",
-                    expected_id, received_id
-                );
-                CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, context)
-            }
-            ProtocolError::InvalidResponseLen(expected_id, received_id) => {
-                let context = format!(
-                    "Received a response with an invalid length field: expected {}, received {}",
-                    expected_id, received_id
-                );
-                CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, context)
-            }
-            ProtocolError::SerializationError(context) => {
-                CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, context)
-            }
-            ProtocolError::DeserializationError(context) => {
-                CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, context)
-            }
+        CpcNvm3Error::Protocol(error)
+    }
+}
+
+fn protocol_error_code(error: &ProtocolError) -> CpcNvm3ErrorCodes {
+    match error {
+        ProtocolError::Bug(_) | ProtocolError::UnknownProcotolError => {
+            CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR
+        }
+        _ => CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+    }
+}
+
+fn protocol_error_context(error: &ProtocolError) -> String {
+    match error {
+        ProtocolError::Bug(context) => format!("Bug: {}", context),
+        ProtocolError::UnknownProcotolError => "Unknown protocol error".to_string(),
+        ProtocolError::InvalidTransactionId(expected_id, received_id) => format!(
+            "Received a response with an invalid transaction id: expected {}, received {}",
+            expected_id, received_id
+        ),
+        ProtocolError::InvalidCommandId => {
+            format!("Received a response with an invalid command id")
+        }
+        ProtocolError::InvalidUniqueId(expected_id, received_id) => format!(
+            "Received a response with an invalid unique id: expected {}, received {}",
+            expected_id, received_id
+        ),
+        ProtocolError::InvalidResponseLen(expected_id, received_id) => format!(
+            "Received a response with an invalid length field: expected {}, received {}",
+            expected_id, received_id
+        ),
+        ProtocolError::SerializationError(context) => context.clone(),
+        ProtocolError::DeserializationError(context) => context.clone(),
+        ProtocolError::InvalidObjectType(object_type) => {
+            format!("Received an unknown object type {}", object_type)
         }
+        ProtocolError::TrailingBytes(cmd, trailing_len) => format!(
+            "Received a {:?} response with {} unexpected trailing bytes",
+            cmd, trailing_len
+        ),
     }
 }
 
@@ -156,41 +285,141 @@ extern "C" {
     pub fn cpc_deinit(handle: *mut libcpc::cpc_handle_t) -> ::std::os::raw::c_int;
 }
 
+// A dropped response is retried, the reason it was dropped is kept in case it is the last
 enum RxParseOutcome<R, E> {
     Parsed(R),
-    Retry,
+    Retry(ProtocolError),
     Error(E),
 }
 
 struct CpcNvm3Instance {
+    label: String,
     transaction_id: u8,
     unique_id: u32,
+    requested_unique_id: Option<u32>,
+    cpcd_instance_name: Option<String>,
+    // Major, minor and patch version of the NVM3 protocol of the secondary
+    secondary_version: Option<[u8; 3]>,
     maximum_write_fragment_size: Option<u16>,
     maximum_write_size: Option<u16>,
+    maximum_object_size: Option<u16>,
+    // The secondary did not report its maximum object size, the maximum write size stands in
+    maximum_object_size_approximate: bool,
+    cpc_max_write_size: Option<u16>,
+    features: Option<u32>,
     cpc_endpoint: Option<cpc::cpc_endpoint>,
     cpc_handle: Option<cpc::cpc_handle>,
+    last_activity: Instant,
+    keepalive: Option<Keepalive>,
+    last_status: Option<(u8, u32)>,
+    nonblocking: bool,
+    // In non-blocking mode, the last request sent and the one whose response was not
+    // available yet. The retry of the pending request collects its response instead of
+    // sending it again.
+    last_request: Option<Vec<u8>>,
+    pending_request: Option<Vec<u8>>,
+    // The transaction ID carried by the response of the pending request and the one its
+    // retry expects, while the retry waits for the response
+    resumed_transaction_id: Option<(u8, u8)>,
+    // Whether a response was received since the last request was sent. A request that got
+    // part of a fragmented response can't be resumed, so it is never kept pending.
+    response_received: bool,
+    reconnect_count: u32,
+    last_op_reconnected: bool,
+    // When the last request was sent, until its first response is received. Only the
+    // first fragment of a response closes the round trip of its command.
+    request_sent_at: Option<Instant>,
+    // The rolling average of the command round trips, in microseconds
+    round_trip_average_us: Option<u64>,
+    interrupted: Arc<AtomicBool>,
+    max_mismatched_responses: u16,
+    // How many consecutive responses can carry an unexpected transaction ID before the
+    // instance is considered out of sync with the secondary, 0 disables the detection
+    desync_threshold: u16,
+    reconnect_on_desync: bool,
+    // Whether fixed size responses with bytes past their last field are rejected
+    strict_parsing: bool,
+    // Whether a lost connection to the endpoint is restored before failing the operation
+    auto_reconnect: bool,
+    // The most bytes the fragments of a single response can add up to, 0 bounds reads by
+    // the maximum object size and enumerations by `CPC_NVM3_MAX_ENUMERATION_SIZE`
+    max_reassembly: u32,
+    // The read timeout applied while waiting for the response of a command, by command ID.
+    // The other commands use the read timeout of the endpoint.
+    command_timeouts: HashMap<u8, Duration>,
+}
+
+// The keepalive thread stops as soon as its sender is dropped, which happens when the
+// keepalive is disabled or when the instance itself is dropped.
+struct Keepalive {
+    stop: mpsc::Sender<()>,
+    thread: std::thread::JoinHandle<()>,
 }
 
 impl CpcNvm3Instance {
     pub fn new() -> Self {
         Self {
+            label: String::new(),
             unique_id: 0,
+            requested_unique_id: None,
+            cpcd_instance_name: None,
+            secondary_version: None,
             transaction_id: 0,
             maximum_write_fragment_size: None,
             maximum_write_size: None,
+            maximum_object_size: None,
+            maximum_object_size_approximate: false,
+            cpc_max_write_size: None,
+            features: None,
             cpc_endpoint: None,
             cpc_handle: None,
+            last_activity: Instant::now(),
+            keepalive: None,
+            last_status: None,
+            nonblocking: false,
+            last_request: None,
+            pending_request: None,
+            resumed_transaction_id: None,
+            response_received: false,
+            reconnect_count: 0,
+            last_op_reconnected: false,
+            request_sent_at: None,
+            round_trip_average_us: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            max_mismatched_responses: CPC_NVM3_MAX_MISMATCHED_RESPONSES,
+            desync_threshold: CPC_NVM3_DESYNC_THRESHOLD,
+            reconnect_on_desync: false,
+            strict_parsing: false,
+            auto_reconnect: true,
+            max_reassembly: 0,
+            command_timeouts: default_command_timeouts(),
         }
     }
 
-    #[cfg(test)]
+    // A unique ID set with `set_unique_id` only replaces the one of the running session
+    // when the connection is established again
+    fn apply_requested_unique_id(&mut self) {
+        if let Some(unique_id) = self.requested_unique_id {
+            self.unique_id = unique_id;
+        }
+    }
+
+    // The new endpoint starts with the libcpc read timeout, it gets the configured default
+    // like the endpoint of a newly opened instance
     fn reconnect(&mut self) -> Result<(), CpcNvm3Error> {
+        self.reopen_endpoint()?;
+        self.set_read_timeout(default_read_timeout())
+    }
+
+    #[cfg(test)]
+    fn reopen_endpoint(&mut self) -> Result<(), CpcNvm3Error> {
         Ok(())
     }
 
     #[cfg(not(test))]
-    fn reconnect(&mut self) -> Result<(), CpcNvm3Error> {
+    fn reopen_endpoint(&mut self) -> Result<(), CpcNvm3Error> {
         log::info!("Attempting to reconnect to libcpc");
+        self.apply_requested_unique_id();
 
         // Close the endpoint if it was not done previously
         match self.cpc_endpoint {
@@ -228,10 +457,7 @@ impl CpcNvm3Instance {
                 log::debug!("Successfully reconnected to libcpc");
                 Ok(())
             }
-            None => Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                format!("Can't reconnect to a closed CPC endpoint"),
-            )),
+            None => Err(CpcNvm3Error::NotOpen),
         }
     }
 
@@ -239,6 +465,7 @@ impl CpcNvm3Instance {
         &mut self,
         cpcd_instance_name: &str,
         enable_cpc_traces: bool,
+        deadline: Instant,
     ) -> Result<(), CpcNvm3Error> {
         log::info!(
             "Opening [CPC NVM3 v{}.{}.{}]",
@@ -253,6 +480,7 @@ impl CpcNvm3Instance {
                 format!("Tried to open already opened instance"),
             ));
         }
+        self.apply_requested_unique_id();
 
         unsafe extern "C" fn reset_callback() {
             log::debug!("LibCPC reset received");
@@ -265,12 +493,13 @@ impl CpcNvm3Instance {
                 Some(reset_callback),
             ) {
                 Ok(cpc_handle) => cpc_handle,
-                Err(err) => {
-                    Err(CpcNvm3Error::ErrorCodeWithContext(
-                                CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR,
-                                format!("Failed to init libCPC. CPCd with ({}) needs to run and be connected to a secondary. {}", cpcd_instance_name, err.to_string()),
-                            ))
-                }?,
+                Err(err) => Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR,
+                    format!(
+                        "Failed to init libCPC. CPCd with ({}) needs to run and be connected to a secondary. {}",
+                        cpcd_instance_name, err
+                    ),
+                ))?,
             };
             self.cpc_handle = Some(cpc_handle);
 
@@ -291,6 +520,8 @@ impl CpcNvm3Instance {
 
             // Get the maximum write fragment size
             let cpc_max_write_size = cpc_endpoint.get_max_write_size()? as u16;
+            self.cpc_max_write_size = Some(cpc_max_write_size);
+            log::debug!("CPC maximum write size is {} bytes", cpc_max_write_size);
             let nvm3_write_overhead = protocol::CmdWriteData::get_overhead();
             self.maximum_write_fragment_size = Some(cpc_max_write_size - nvm3_write_overhead);
             log::debug!(
@@ -298,13 +529,6 @@ impl CpcNvm3Instance {
                 self.maximum_write_fragment_size.unwrap_or(0)
             );
 
-            // Configure the timeout on the endpoint
-            let timeout = cpc::cpc_timeval_t {
-                seconds: CPC_NVM3_READ_TIMEOUT_S,
-                microseconds: 0,
-            };
-            cpc_endpoint.set_read_timeout(timeout)?;
-
             // Configuration is completed, we can assign the endpoint to the instance
             self.cpc_endpoint = Some(cpc_endpoint);
 
@@ -314,7 +538,7 @@ impl CpcNvm3Instance {
             self.write(&get_version_command.serialize()?)?;
             log::debug!("Queried the NVM3 protocol version from the secondary");
 
-            let secondary_version = self.get_response(&get_version_command)?;
+            let secondary_version = self.get_handshake_response(&get_version_command, deadline)?;
 
             log::info!(
                 "[CPC Secondary NVM3 API v{}.{}.{}]",
@@ -330,6 +554,11 @@ impl CpcNvm3Instance {
                     "Major versions do not match".to_string(),
                 ));
             }
+            self.secondary_version = Some([
+                secondary_version.major_version,
+                secondary_version.minor_version,
+                secondary_version.patch_version,
+            ]);
 
             // Get the maximum write size
             log::debug!("Fetching maximum write size");
@@ -342,7 +571,7 @@ impl CpcNvm3Instance {
             let bytestream = get_maximum_write_command.serialize()?;
             self.write(&bytestream)?;
 
-            let response = self.get_response(&get_maximum_write_command)?;
+            let response = self.get_handshake_response(&get_maximum_write_command, deadline)?;
             match response {
                 PropValueGetResponse::Value(property_value) => match property_value {
                     PropertyValue::MaxWriteSize(property_value) => {
@@ -363,12 +592,63 @@ impl CpcNvm3Instance {
                     ));
                 }
             };
+
+            // Get the maximum object size
+            log::debug!("Fetching maximum object size");
+            let get_maximum_object_command = PropValueGet::new(
+                self.unique_id,
+                &mut self.transaction_id,
+                protocol::PropertyType::MaxObjectSize,
+            );
+
+            let bytestream = get_maximum_object_command.serialize()?;
+            self.write(&bytestream)?;
+
+            let response = self.get_handshake_response(&get_maximum_object_command, deadline)?;
+            match response {
+                PropValueGetResponse::Value(PropertyValue::MaxObjectSize(max_object_size)) => {
+                    log::debug!("Maximum object size is {} bytes", max_object_size);
+                    self.maximum_object_size = Some(max_object_size);
+                    self.maximum_object_size_approximate = false
+                }
+                PropValueGetResponse::Value(property_value) => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        format!("Unexpected property value {}", property_value),
+                    ));
+                }
+                PropValueGetResponse::StatusCode(status_code) => {
+                    // Secondaries that predate the property only bound objects by what a
+                    // single write can carry
+                    log::warn!(
+                        "The secondary does not report its maximum object size ({}), using the maximum write size instead",
+                        status_code
+                    );
+                    self.maximum_object_size = self.maximum_write_size;
+                    self.maximum_object_size_approximate = true
+                }
+            };
+
+            // The features are learned from the properties answered above, no request is
+            // dedicated to them
+            let mut features = crate::CPC_NVM3_FEATURES_BASE;
+            if !self.maximum_object_size_approximate {
+                features |= crate::CPC_NVM3_FEATURE_MAX_OBJECT_SIZE;
+            }
+            log::debug!("Supported features are {:#010x}", features);
+            self.features = Some(features);
+            // The handshake is completed, restore the regular timeout on the endpoint
+            self.set_read_timeout(default_read_timeout())?;
+
             log::info!("Successfuly opened NVM3 instance");
             Ok(())
         };
 
         match result() {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.cpcd_instance_name = Some(cpcd_instance_name.to_string());
+                Ok(())
+            }
             Err(err) => {
                 #[cfg(not(test))]
                 if let Some(cpc_handle) = &mut self.cpc_handle {
@@ -385,25 +665,117 @@ impl CpcNvm3Instance {
                 self.cpc_handle = None;
                 self.maximum_write_fragment_size = None;
                 self.maximum_write_size = None;
+                self.maximum_object_size = None;
+                self.maximum_object_size_approximate = false;
+                self.cpc_max_write_size = None;
+                self.features = None;
+                self.cpcd_instance_name = None;
+                self.secondary_version = None;
                 Err(err)
             }
         }
     }
 
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), CpcNvm3Error> {
+        let timeval = cpc::cpc_timeval_t {
+            seconds: timeout.as_secs() as i32,
+            microseconds: timeout.subsec_micros() as i32,
+        };
+        match &self.cpc_endpoint {
+            Some(endpoint) => Ok(endpoint.set_read_timeout(timeval)?),
+            None => Err(CpcNvm3Error::NotOpen),
+        }
+    }
+
+    fn get_read_timeout(&self) -> Result<Duration, CpcNvm3Error> {
+        match &self.cpc_endpoint {
+            Some(endpoint) => {
+                let timeval = endpoint.get_read_timeout()?;
+                Ok(Duration::from_secs(timeval.seconds as u64)
+                    + Duration::from_micros(timeval.microseconds as u64))
+            }
+            None => Err(CpcNvm3Error::NotOpen),
+        }
+    }
+
+    // Same as `get_response`, but a read never blocks past the open deadline
+    fn get_handshake_response<C: Command>(
+        &mut self,
+        command: &C,
+        deadline: Instant,
+    ) -> Result<C::Response, CpcNvm3Error> {
+        let timeout_error = || {
+            log::warn!(
+                "The handshake with the secondary did not complete before the open deadline"
+            );
+            CpcNvm3Error::Timeout
+        };
+
+        // The deadline bounds the handshake, the command timeouts don't apply to it
+        match self.read_response(command, Some(deadline)) {
+            Err(CpcNvm3Error::Timeout) => Err(timeout_error()),
+            Err(err)
+                if err.code() == CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN
+                    && Instant::now() >= deadline =>
+            {
+                Err(timeout_error())
+            }
+            result => result,
+        }
+    }
+
+    // The operation in flight is lost with the connection, it fails with the returned error
+    fn reconnect_and_retry(&mut self) -> CpcNvm3Error {
+        self.forget_pending_request();
+        self.reconnect_count = self.reconnect_count.wrapping_add(1);
+        self.last_op_reconnected = true;
+        if let Err(err) = self.reconnect() {
+            return err;
+        }
+        CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+            "reconnected to libcpc try again".to_string(),
+        )
+    }
+
+    // An endpoint lost by an earlier operation is only reopened with the automatic reconnection
+    fn reconnect_lost_endpoint(&mut self) -> Result<(), CpcNvm3Error> {
+        if self.cpc_handle.is_none() {
+            return Err(CpcNvm3Error::NotOpen);
+        }
+        if !self.auto_reconnect {
+            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_CONNECTION_LOST,
+                "The CPC endpoint was lost, the automatic reconnection is disabled".to_string(),
+            ));
+        }
+        self.reconnect()
+    }
+
+    // The response of a request sent before a reconnection never arrives
+    fn forget_pending_request(&mut self) {
+        self.last_request = None;
+        self.pending_request = None;
+        self.resumed_transaction_id = None;
+    }
+
     fn handle_libcpc_error(&mut self, err: libcpc::Error) -> CpcNvm3Error {
         match err {
             libcpc::Error::Errno(err) => match err.kind() {
                 std::io::ErrorKind::ConnectionReset
                 | std::io::ErrorKind::BrokenPipe
                 | std::io::ErrorKind::Interrupted => {
-                    log::debug!("libcpc errno {} occured, attempting to reconnect", err);
-                    if let Err(err) = self.reconnect() {
-                        return err;
+                    if !self.auto_reconnect {
+                        return CpcNvm3Error::ErrorCodeWithContext(
+                            CpcNvm3ErrorCodes::CPC_NVM3_CONNECTION_LOST,
+                            format!(
+                                "libcpc errno {} occured, the automatic reconnection is disabled",
+                                err
+                            ),
+                        );
                     }
-                    return CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
-                        "reconnected to libcpc try again".to_string(),
-                    );
+                    log::debug!("libcpc errno {} occured, attempting to reconnect", err);
+                    return self.reconnect_and_retry();
                 }
                 std::io::ErrorKind::WouldBlock => {
                     return CpcNvm3Error::ErrorCodeWithContext(
@@ -442,16 +814,36 @@ impl CpcNvm3Instance {
     fn write(&mut self, data: &Vec<u8>) -> Result<(), CpcNvm3Error> {
         // Check if the endpoint was previously disconnected
         if self.cpc_endpoint.is_none() {
-            if self.cpc_handle.is_none() {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                    format!(
-                        "CPC Write failed. The CPC is not initialized. Call cpc_nvm3_open first."
-                    ),
-                ));
+            self.reconnect_lost_endpoint()?;
+        }
+
+        // Every command starts with a write and a reconnection aborts the command, so
+        // this is where a new operation begins as far as the link stats are concerned
+        self.last_op_reconnected = false;
+
+        if let Some(pending_request) = self.pending_request.take() {
+            if protocol::is_same_request(&pending_request, data) {
+                log::debug!(
+                    "[{}] Collecting the response of the pending request instead of sending it again",
+                    self.label
+                );
+                self.resumed_transaction_id = protocol::frame_transaction_id(&pending_request)
+                    .zip(protocol::frame_transaction_id(data));
+                self.response_received = false;
+                // The pending request was sent by an earlier call, its round trip includes
+                // the time the caller took to retry
+                self.request_sent_at = None;
+                self.pending_request = Some(pending_request);
+                return Ok(());
             }
-            self.reconnect()?;
+            log::debug!(
+                "[{}] Another request is sent, the response of the pending request will be dropped",
+                self.label
+            );
         }
+        self.resumed_transaction_id = None;
+        self.response_received = false;
+        self.last_request = self.nonblocking.then(|| data.clone());
 
         match &self.cpc_endpoint {
             Some(cpc_endpoint) => {
@@ -460,14 +852,11 @@ impl CpcNvm3Instance {
                 if let Err(err) = cpc_endpoint.write(data, &write_flags) {
                     return Err(self.handle_libcpc_error(err));
                 }
-                log::debug!("Wrote {:?} ", data);
-            }
-            None => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                    format!("The CPC endpoint is not initialized. Call cpc_nvm3_open first."),
-                ))
+                self.last_activity = Instant::now();
+                self.request_sent_at = Some(self.last_activity);
+                log::debug!("[{}] Wrote {:?} ", self.label, data);
             }
+            None => return Err(CpcNvm3Error::NotOpen),
         }
         Ok(())
     }
@@ -475,32 +864,50 @@ impl CpcNvm3Instance {
     fn read(&mut self) -> Result<Vec<u8>, CpcNvm3Error> {
         // Check if the endpoint was previously disconnected
         if self.cpc_endpoint.is_none() {
-            if self.cpc_handle.is_none() {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                    format!(
-                        "CPC Write failed. The CPC is not initialized. Call cpc_nvm3_open first."
-                    ),
-                ));
-            }
-            self.reconnect()?;
+            self.reconnect_lost_endpoint()?;
         }
 
         match &self.cpc_endpoint {
             Some(cpc_endpoint) => {
-                let read_flags = [cpc::cpc_endpoint_read_flags_t_enum::CPC_ENDPOINT_READ_FLAG_NONE];
+                let read_flags = if self.nonblocking {
+                    [cpc::cpc_endpoint_read_flags_t_enum::CPC_ENDPOINT_READ_FLAG_NON_BLOCKING]
+                } else {
+                    [cpc::cpc_endpoint_read_flags_t_enum::CPC_ENDPOINT_READ_FLAG_NONE]
+                };
                 let data = match cpc_endpoint.read(&read_flags) {
                     Ok(data) => data,
-                    Err(err) => return Err(self.handle_libcpc_error(err)),
+                    Err(err) => {
+                        // A timed out read is how an interrupt gets noticed. A frame that
+                        // was received is still processed, and any other failure is
+                        // reported as usual.
+                        let would_block = matches!(
+                            &err,
+                            libcpc::Error::Errno(errno)
+                                if errno.kind() == std::io::ErrorKind::WouldBlock
+                        );
+                        if would_block && self.interrupted.swap(false, Ordering::SeqCst) {
+                            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                                CpcNvm3ErrorCodes::CPC_NVM3_CANCELLED,
+                                "The operation was interrupted".to_string(),
+                            ));
+                        }
+                        // The response is still on its way, the retry of the operation
+                        // collects it
+                        if would_block
+                            && self.nonblocking
+                            && !self.response_received
+                            && self.pending_request.is_none()
+                        {
+                            self.pending_request = self.last_request.clone();
+                        }
+                        return Err(self.handle_libcpc_error(err));
+                    }
                 };
 
-                log::debug!("Read {:?} ", data);
+                log::debug!("[{}] Read {:?} ", self.label, data);
                 Ok(data)
             }
-            None => Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                format!("The CPC endpoint is not initialized. Call cpc_nvm3_open first."),
-            )),
+            None => Err(CpcNvm3Error::NotOpen),
         }
     }
 
@@ -521,342 +928,517 @@ impl CpcNvm3Instance {
                 }
             }
             None => {
-                Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                    format!("The CPC endpoint is not initialized. Call cpc_nvm3_open first."),
-                ))
+                // Closing twice is reported rather than ignored so that the caller
+                // notices the mismatched open/close calls
+                Err(CpcNvm3Error::NotOpen)
             }?,
         }
         self.cpc_endpoint = None;
         self.cpc_handle = None;
+        self.forget_pending_request();
         Ok(())
     }
 
     pub fn get_maximum_write_size(&mut self) -> Result<u16, CpcNvm3Error> {
         match self.maximum_write_size {
             Some(maximum_write_size) => Ok(maximum_write_size),
-            None => {
-                Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                        format!(
-                            "Could not get maximum write size since the CPC NVM3 instance is not opened"
-                        ),
-                    ))
-            }?,
+            None => { Err(CpcNvm3Error::NotOpen) }?,
+        }
+    }
+
+    pub fn get_maximum_object_size(&mut self) -> Result<u16, CpcNvm3Error> {
+        match self.maximum_object_size {
+            Some(maximum_object_size) => Ok(maximum_object_size),
+            None => Err(CpcNvm3Error::NotOpen),
         }
     }
 
     pub fn get_maximum_write_fragment_size(&mut self) -> Result<u16, CpcNvm3Error> {
         match self.maximum_write_fragment_size {
             Some(maximum_write_fragment_size) => Ok(maximum_write_fragment_size),
-            None => {
-                Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-                        format!(
-                            "Could not get maximum write size since the CPC NVM3 instance is not opened"
-                        ),
-                    ))
-            }?,
+            None => { Err(CpcNvm3Error::NotOpen) }?,
         }
     }
 
-    fn parse_response<C: Command>(
-        &mut self,
-        command: &C,
-        input: &[u8],
-    ) -> RxParseOutcome<C::Response, CpcNvm3Error> {
-        match command.parse_response(input) {
-            Ok(response) => RxParseOutcome::Parsed(response),
-            Err(err) => match err {
-                ProtocolError::InvalidCommandId => {
-                    log::debug!("Dropping response with invalid command ID");
-                    RxParseOutcome::Retry
-                }
-                ProtocolError::InvalidTransactionId(expected, actual) => {
-                    log::debug!(
-                        "Dropping response with invalid transaction ID {}. Expected {}",
-                        actual,
-                        expected
-                    );
-                    RxParseOutcome::Retry
-                }
-                ProtocolError::InvalidUniqueId(expected, actual) => {
-                    log::debug!(
-                        "Dropping response with invalid unique ID {}. Expected {}",
-                        actual,
-                        expected
-                    );
-                    RxParseOutcome::Retry
-                }
-                _ => RxParseOutcome::Error(err.into()),
-            },
+    pub fn get_cpc_max_write_size(&mut self) -> Result<u16, CpcNvm3Error> {
+        match self.cpc_max_write_size {
+            Some(cpc_max_write_size) => Ok(cpc_max_write_size),
+            None => Err(CpcNvm3Error::NotOpen),
         }
     }
 
-    pub fn get_response<C: Command>(&mut self, command: &C) -> Result<C::Response, CpcNvm3Error> {
-        loop {
-            let rx_packet = self.read()?;
-            match self.parse_response(command, &rx_packet) {
-                RxParseOutcome::Parsed(response) => return Ok(response),
-                RxParseOutcome::Retry => continue,
-                RxParseOutcome::Error(err) => return Err(err),
-            }
+    pub fn get_features(&mut self) -> Result<u32, CpcNvm3Error> {
+        match self.features {
+            Some(features) => Ok(features),
+            None => Err(CpcNvm3Error::NotOpen),
         }
     }
-}
 
-#[allow(non_camel_case_types)] // This will be used in a generated a C header file
-pub type cpc_nvm3_handle_t = u32;
-#[allow(non_camel_case_types)] // This will be used in a generated a C header file
-pub type cpc_nvm3_object_key_t = u32;
+    fn write_data(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        data: &[u8],
+    ) -> Result<u16, CpcNvm3Error> {
+        self.write_data_acknowledged(cpc_nvm3_object_key, data, &mut 0)
+    }
 
-fn find_next_available_handle() -> Result<cpc_nvm3_handle_t, CpcNvm3Error> {
-    match CPC_NVM_LIB_INSTANCE_KEY.lock() {
-        Ok(mut id) => {
-            if *id == u32::MAX {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    format!("Instance key could not be incremented. Limit reached."),
-                ));
-            }
-            *id += 1;
-            Ok(*id)
+    // Same as `write_data`, `acknowledged` is the length of the fragments the secondary
+    // acknowledged so far, which is also kept when the write fails
+    fn write_data_acknowledged(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        data: &[u8],
+        acknowledged: &mut u16,
+    ) -> Result<u16, CpcNvm3Error> {
+        *acknowledged = 0;
+        self.check_write_length(data.len())?;
+        let fragment_size = self.get_maximum_write_fragment_size()? as usize;
+
+        let mut fragment_count: u16 = 0;
+        for (offset, fragment, last_fragment) in protocol::fragment(data, fragment_size)? {
+            fragment_count += 1;
+            self.write_fragment(
+                cpc_nvm3_object_key,
+                offset,
+                last_fragment,
+                fragment.to_vec(),
+            )?;
+            *acknowledged += fragment.len() as u16;
         }
-        Err(err) => Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-            format!("Failed to lock NVM3 instance map. Error: {}", err),
-        )),
+        log::debug!("Write completed in {} fragment(s)", fragment_count);
+        Ok(fragment_count)
     }
-}
-
-fn get_instance(
-    cpc_nvm3_handle: cpc_nvm3_handle_t,
-) -> Result<Arc<Mutex<CpcNvm3Instance>>, CpcNvm3Error> {
-    let instances = match CPC_NVM3_LIB_INSTANCES.lock() {
-        Ok(guard) => guard,
-        Err(err) => {
-            Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            ))
-        }?,
-    };
-
-    let instance_mutex = match instances.get(&cpc_nvm3_handle) {
-        Some(instance) => instance,
-        None => {
-            Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_NOT_INITIALIZED,
-                format!("Could not find the provided instance"),
-            ))
-        }?,
-    };
 
-    Ok(Arc::clone(instance_mutex))
-}
+    // Write `length` bytes to an object, one fragment at a time. `fill_fragment` receives
+    // the offset of each fragment and must fill it entirely, so only a single fragment of
+    // the object is ever held in memory.
+    fn write_fragments(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        length: usize,
+        mut fill_fragment: impl FnMut(usize, &mut [u8]) -> Result<(), CpcNvm3Error>,
+    ) -> Result<u16, CpcNvm3Error> {
+        self.check_write_length(length)?;
+        let fragment_size = self.get_maximum_write_fragment_size()? as usize;
+
+        let mut fragment_count: u16 = 0;
+        for (offset, fragment_len, last_fragment) in
+            protocol::fragment_offsets(length, fragment_size)?
+        {
+            fragment_count += 1;
+            let mut data_fragment = vec![0u8; fragment_len];
+            fill_fragment(offset as usize, &mut data_fragment)?;
+            self.write_fragment(cpc_nvm3_object_key, offset, last_fragment, data_fragment)?;
+        }
+        log::debug!("Write completed in {} fragment(s)", fragment_count);
+        Ok(fragment_count)
+    }
 
-pub struct FileLogger {
-    level: log::LevelFilter,
-    prefix: String,
-    file: Mutex<File>,
-}
+    fn check_write_length(&mut self, length: usize) -> Result<(), CpcNvm3Error> {
+        if let Some(maximum_object_size) = self.maximum_object_size {
+            if length > maximum_object_size as usize {
+                return Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                    format!(
+                        "Requested a write ({}) that exceeds the maximum object size ({})",
+                        length, maximum_object_size
+                    ),
+                ));
+            }
+        }
 
-impl FileLogger {
-    pub fn new(level: log::LevelFilter, prefix: String, file: File) -> Self {
-        FileLogger {
-            level,
-            prefix,
-            file: Mutex::new(file),
+        if length > self.get_maximum_write_size()? as usize {
+            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                format!(
+                    "Requested a write ({}) that exceeds the maximum write size ({})",
+                    length,
+                    self.get_maximum_write_size()?
+                ),
+            ));
         }
+        Ok(())
     }
-}
 
-impl Log for FileLogger {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.level
-    }
+    fn write_fragment(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        offset: u16,
+        last_fragment: bool,
+        data_fragment: Vec<u8>,
+    ) -> Result<(), CpcNvm3Error> {
+        // The status response carries no object key, only its transaction ID ties it to
+        // this write. Logging the key lets a capture be matched against the log.
+        log::debug!(
+            "[{}] Writing object {} at offset {}",
+            self.label,
+            cpc_nvm3_object_key,
+            offset
+        );
 
-    fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            let mut file_guard = self.file.lock().unwrap();
-
-            let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-            write!(
-                file_guard,
-                "{} {} - {}: {}\n",
-                timestamp,
-                self.prefix,
-                record.level(),
-                record.args()
-            )
-            .unwrap();
+        let write_data_command = CmdWriteData::new(
+            self.unique_id,
+            &mut self.transaction_id,
+            cpc_nvm3_object_key,
+            offset,
+            last_fragment as u8,
+            data_fragment,
+        );
+        let write_data = write_data_command.serialize()?;
+        self.write(&write_data)?;
+        let response = self.get_response(&write_data_command)?;
 
-            file_guard.flush().unwrap();
+        match response {
+            StatusCode::SlStatus(sl_status) => match sl_status {
+                SlStatus::Ok => log::debug!("Received write complete acknowledgement"),
+                SlStatus::Fail => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        "Writing to NVM3 instance failed".to_string(),
+                    ))
+                }
+                SlStatus::Busy => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+                        "NVM3 is busy with another write operation, try again".to_string(),
+                    ))
+                }
+                SlStatus::Unknown => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        format!("Received an unexpected sl_status code {}", sl_status),
+                    ))
+                }
+            },
+            StatusCode::ECode(ecode) => match ecode {
+                ECode::KeyInvalid => return Err(CpcNvm3Error::InvalidKey(cpc_nvm3_object_key)),
+                _ => return Err(ecode.into()),
+            },
+            StatusCode::Unknown => {
+                return Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
+                    format!("Unknown response type received"),
+                ))
+            }
         }
+        Ok(())
     }
 
-    fn flush(&self) {
-        let mut file_guard = self.file.lock().unwrap();
-        file_guard.flush().unwrap();
+    fn read_data(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        max_read_size: u16,
+    ) -> Result<Vec<u8>, CpcNvm3Error> {
+        let read_command = CmdReadData::new(
+            self.unique_id,
+            &mut self.transaction_id,
+            cpc_nvm3_object_key,
+            max_read_size,
+        );
+
+        self.write(&read_command.serialize()?)?;
+        self.with_command_timeout(&read_command, |instance| {
+            instance.read_data_fragments(&read_command, cpc_nvm3_object_key, max_read_size)
+        })
     }
-}
 
-pub fn init_logger(
-    prefix: Option<&str>,
-    level: CpcNvm3LogLevel,
-    file_path: Option<&str>,
-    append: bool,
-) -> Result<(), CpcNvm3Error> {
-    let mut logger_initialized = LOGGER_INITIALIZED.lock().map_err(|_| {
-        CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-            "Failed to lock logger initialization status".to_string(),
-        )
-    })?;
+    // Reassemble the fragments of the response to `read_command`
+    fn read_data_fragments(
+        &mut self,
+        read_command: &CmdReadData,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        max_read_size: u16,
+    ) -> Result<Vec<u8>, CpcNvm3Error> {
+        let mut continue_reading = true;
+        let default_reassembly = self.maximum_object_size.unwrap_or(u16::MAX) as usize;
+        // A response larger than the requested size or than the reassembly limit is
+        // rejected, so reserving the smaller of the two up front avoids growing the buffer
+        // as the fragments of a large object arrive.
+        let mut data = Vec::with_capacity(
+            (max_read_size as usize).min(self.reassembly_limit(default_reassembly)),
+        );
 
-    if !*logger_initialized {
-        let log_file = if let Some(path) = file_path {
-            OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(append) // This will set the file to append mode.
-                .open(path) // Open or create the file at the provided path.
-                .map_err(|e| {
-                    CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                        format!("Failed to open or create log file: {:?}", e),
-                    )
-                })?
-        } else {
-            // Fall back to STDOUT if no file path is provided.
-            unsafe { File::from_raw_fd(STDOUT_FILENO) }
-        };
-        log::set_boxed_logger(Box::new(FileLogger::new(
-            level.into(),
-            prefix.unwrap_or("").to_string(),
-            log_file,
-        )))
-        .map_err(|_| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                "Failed to set logger".to_string(),
-            )
-        })?;
+        while continue_reading {
+            let response = self.read_response(read_command, None)?;
+
+            // Response can either be an error (StatusIs) or a success with the data
+            let received_data = match response {
+                CmdReadDataResponse::Data(segment, last_fragment) => {
+                    continue_reading = !last_fragment;
+                    if !last_fragment {
+                        log::debug!(
+                            "Received {} bytes. Another fragment is available, reading again",
+                            segment.len()
+                        );
+                    }
+                    Ok(segment)
+                }
+                CmdReadDataResponse::StatusCode(status_code) => match status_code {
+                    StatusCode::SlStatus(sl_status) => match sl_status {
+                        SlStatus::Ok | SlStatus::Fail | SlStatus::Unknown => {
+                            Err(CpcNvm3Error::ErrorCodeWithContext(
+                                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                                format!("Received an unexpected sl_status code {}", status_code),
+                            ))
+                        }
+                        SlStatus::Busy => {
+                            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                                CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+                                "NVM3 is busy with another operation, try again".to_string(),
+                            ))
+                        }
+                    },
 
-        log::set_max_level(LevelFilter::from(level));
-        *logger_initialized = true;
-    }
-    Ok(())
-}
+                    StatusCode::ECode(e_code) => match e_code {
+                        ECode::KeyNotFound => Err(CpcNvm3Error::InvalidKey(cpc_nvm3_object_key)),
+                        ECode::ReadDataSize | ECode::SizeTooSmall => {
+                            // The secondary doesn't tell the size of the object, query it so
+                            // that the caller can size its buffer
+                            let (object_size, _) = self.get_object_info(cpc_nvm3_object_key)?;
+                            Err(CpcNvm3Error::BufferTooSmall {
+                                needed: object_size as usize,
+                                got: max_read_size as usize,
+                            })
+                        }
+                        _ => Err(e_code.into()),
+                    },
 
-pub fn init() -> Result<cpc_nvm3_handle_t, CpcNvm3Error> {
-    let handle = find_next_available_handle()?;
-    let mut cpc_nvm3_instance = CpcNvm3Instance::new();
-    #[cfg(not(test))]
-    {
-        cpc_nvm3_instance.unique_id = std::process::id();
+                    StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
+                        format!("Unknown response type received"),
+                    )),
+                },
+            }?;
+            self.check_reassembly(data.len() + received_data.len(), default_reassembly)?;
+            // The callers size their buffer with the requested size
+            if data.len() + received_data.len() > max_read_size as usize {
+                return Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                    format!(
+                        "The secondary sent more than the {} bytes requested",
+                        max_read_size
+                    ),
+                ));
+            }
+            data.extend(received_data);
+        }
+        Ok(data)
     }
 
-    // Push key/value to the instance map
-    let mut map = match CPC_NVM3_LIB_INSTANCES.lock() {
-        Ok(m) => m,
-        Err(err) => {
-            Err(CpcNvm3Error::ErrorCodeWithContext(
+    // A secondary that never sends the last fragment would otherwise have the response
+    // grow without bound
+    fn check_reassembly(&self, length: usize, default_limit: usize) -> Result<(), CpcNvm3Error> {
+        let limit = self.reassembly_limit(default_limit);
+        if length > limit {
+            return Err(CpcNvm3Error::ErrorCodeWithContext(
                 CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("Failed to NVM3 instance map. Err{}", err),
-            ))
-        }?,
-    };
-    map.insert(handle, Arc::new(Mutex::new(cpc_nvm3_instance)));
-
-    log::debug!("cpc_nvm3_init was successful, assigned handle {}", handle);
+                format!(
+                    "The fragments of the response add up to more than {} bytes",
+                    limit
+                ),
+            ));
+        }
+        Ok(())
+    }
 
-    Ok(handle)
-}
+    fn reassembly_limit(&self, default_limit: usize) -> usize {
+        match self.max_reassembly {
+            0 => default_limit,
+            max_reassembly => max_reassembly as usize,
+        }
+    }
 
-pub fn open(
-    cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpcd_instance_name: &str,
-    enable_cpc_traces: bool,
-) -> Result<(), CpcNvm3Error> {
-    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut cpc_nvm3_instance = instance_arc_mutex.lock().map_err(|err| {
-        CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, format!("{}", err))
-    })?;
+    fn write_counter(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        value: u32,
+    ) -> Result<(), CpcNvm3Error> {
+        let write_counter_command = CmdWriteCounter::new(
+            self.unique_id,
+            &mut self.transaction_id,
+            cpc_nvm3_object_key,
+            value,
+        );
+        let write_data = write_counter_command.serialize()?;
+        self.write(&write_data)?;
+        let response = self.get_response(&write_counter_command)?;
 
-    cpc_nvm3_instance.open(cpcd_instance_name, enable_cpc_traces)?;
+        match response {
+            StatusCode::SlStatus(sl_status) => match sl_status {
+                SlStatus::Ok => log::debug!("Received write counter acknowledgement"),
+                SlStatus::Fail => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        "Writing counter to NVM3 instance failed".to_string(),
+                    ))
+                }
+                SlStatus::Busy => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+                        "NVM3 is busy with another operation, try again".to_string(),
+                    ))
+                }
+                SlStatus::Unknown => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        format!("Received an unexpected sl_status code {}", sl_status),
+                    ))
+                }
+            },
+            StatusCode::ECode(ecode) => match ecode {
+                ECode::KeyInvalid => return Err(CpcNvm3Error::InvalidKey(cpc_nvm3_object_key)),
+                _ => return Err(ecode.into()),
+            },
+            StatusCode::Unknown => {
+                return Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
+                    format!("Unknown response type received"),
+                ))
+            }
+        }
+        Ok(())
+    }
 
-    log::debug!(
-        "cpc_nvm3_open was successful, on handle {}",
-        cpc_nvm3_handle
-    );
+    fn read_counter(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<u32, CpcNvm3Error> {
+        let read_counter_command = CmdReadCounter::new(
+            self.unique_id,
+            &mut self.transaction_id,
+            cpc_nvm3_object_key,
+        );
+        self.write(&read_counter_command.serialize()?)?;
+        let response = self.get_response(&read_counter_command)?;
 
-    Ok(())
-}
+        Ok(process_read_counter_response(
+            cpc_nvm3_object_key,
+            response,
+        )?)
+    }
 
-pub fn write_data(
-    cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
-    data: &[u8],
-) -> Result<(), CpcNvm3Error> {
-    log::debug!("Writing to NVM3 instance");
+    fn ping(&mut self) -> Result<(), CpcNvm3Error> {
+        let noop_command = CmdNoop::new(self.unique_id, &mut self.transaction_id);
+        self.write(&noop_command.serialize()?)?;
 
-    let mut last_fragment = false;
-    let mut offset = 0;
-    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
+        match self.get_response(&noop_command)? {
+            StatusCode::SlStatus(SlStatus::Ok) => Ok(()),
+            StatusCode::ECode(e_code) => Err(e_code.into()),
+            status_code => Err(CpcNvm3Error::ErrorCodeWithContext(
                 CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
-    let fragment_size = instance.get_maximum_write_fragment_size()? as usize;
-
-    if data.len() as u16 > instance.get_maximum_write_size()? {
-        return Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
-            format!(
-                "Requested a write ({}) that is larger than the maximum write size ({})",
-                data.len(),
-                instance.get_maximum_write_size()?
-            ),
-        ));
+                format!("Ping failed with status code: {}", status_code),
+            )),
+        }
     }
 
-    while !last_fragment {
-        if data.len() - offset <= fragment_size {
-            last_fragment = true;
-        }
+    fn increment_counter(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<u32, CpcNvm3Error> {
+        let increment_counter_command = CmdIncrementCounter::new(
+            self.unique_id,
+            &mut self.transaction_id,
+            cpc_nvm3_object_key,
+        );
+        self.write(&increment_counter_command.serialize()?)?;
+        let response = self.get_response(&increment_counter_command)?;
 
-        log::debug!("Writing at offset {}", offset);
+        Ok(process_read_counter_response(
+            cpc_nvm3_object_key,
+            response,
+        )?)
+    }
 
-        let data_fragment = &data[offset..(offset + fragment_size).min(data.len())];
-        let mut write_data_command = CmdWriteData::new(
-            instance.unique_id,
-            &mut instance.transaction_id,
+    fn get_object_info(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<(u16, CpcNvm3ObjectType), CpcNvm3Error> {
+        let get_object_info_command = CmdGetObjectInfo::new(
+            self.unique_id,
+            &mut self.transaction_id,
             cpc_nvm3_object_key,
-            offset as u16,
-            last_fragment as u8,
-            data_fragment.to_vec(),
         );
-        let write_data = write_data_command.serialize()?;
-        instance.write(&write_data)?;
-        let response = instance.get_response(&write_data_command)?;
+        self.request_object_info(cpc_nvm3_object_key, get_object_info_command)
+    }
+
+    fn get_object_info_strict(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<(u16, CpcNvm3ObjectType), CpcNvm3Error> {
+        let get_object_info_command = CmdGetObjectInfoStrict::new(
+            self.unique_id,
+            &mut self.transaction_id,
+            cpc_nvm3_object_key,
+        );
+        self.request_object_info(cpc_nvm3_object_key, get_object_info_command)
+    }
+
+    fn request_object_info<C>(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+        get_object_info_command: C,
+    ) -> Result<(u16, CpcNvm3ObjectType), CpcNvm3Error>
+    where
+        C: Command<Response = CmdGetObjectInfoResponse> + Serializer,
+    {
+        let write_data = get_object_info_command.serialize()?;
+        self.write(&write_data)?;
 
+        let response = self.get_response(&get_object_info_command)?;
         match response {
+            CmdGetObjectInfoResponse::StatusCode(status_code) => match status_code {
+                StatusCode::SlStatus(sl_status) => match sl_status {
+                    SlStatus::Ok | SlStatus::Fail | SlStatus::Busy | SlStatus::Unknown => {
+                        Err(CpcNvm3Error::ErrorCodeWithContext(
+                            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                            format!("Received an unexpected sl_status code {}", status_code),
+                        ))
+                    }
+                },
+
+                StatusCode::ECode(e_code) => match e_code {
+                    ECode::KeyNotFound => Err(CpcNvm3Error::InvalidKey(cpc_nvm3_object_key)),
+                    _ => Err(e_code.into()),
+                },
+
+                StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
+                    format!("Unknown response type received"),
+                )),
+            },
+            CmdGetObjectInfoResponse::ObjectInfo {
+                object_type,
+                object_size,
+            } => Ok((object_size, object_type)),
+        }
+    }
+
+    fn delete_object(
+        &mut self,
+        cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    ) -> Result<(), CpcNvm3Error> {
+        let delete_object_command = CmdDeleteObject::new(
+            self.unique_id,
+            &mut self.transaction_id,
+            cpc_nvm3_object_key,
+        );
+        let write_data = delete_object_command.serialize()?;
+        self.write(&write_data)?;
+
+        let parsed_response = self.get_response(&delete_object_command)?;
+        match parsed_response {
             StatusCode::SlStatus(sl_status) => match sl_status {
-                SlStatus::Ok => log::debug!("Received write complete acknowledgement"),
+                SlStatus::Ok => log::debug!("Received delete object acknowledgement"),
                 SlStatus::Fail => {
                     return Err(CpcNvm3Error::ErrorCodeWithContext(
                         CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                        "Writing to NVM3 instance failed".to_string(),
+                        "Deletion of NVM3 object failed".to_string(),
                     ))
                 }
                 SlStatus::Busy => {
                     return Err(CpcNvm3Error::ErrorCodeWithContext(
                         CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
-                        "NVM3 is busy with another write operation, try again".to_string(),
+                        "NVM3 is busy with another operation, try again".to_string(),
                     ))
                 }
                 SlStatus::Unknown => {
@@ -867,18 +1449,10 @@ pub fn write_data(
                 }
             },
             StatusCode::ECode(ecode) => match ecode {
-                ECode::KeyInvalid => {
-                    return Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
-                        format!("{}", ecode.to_string()),
-                    ))
-                }
-                _ => {
-                    return Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                        format!("{}", ecode.to_string()),
-                    ))
+                ECode::KeyInvalid | ECode::KeyNotFound => {
+                    return Err(CpcNvm3Error::InvalidKey(cpc_nvm3_object_key))
                 }
+                _ => return Err(ecode.into()),
             },
             StatusCode::Unknown => {
                 return Err(CpcNvm3Error::ErrorCodeWithContext(
@@ -887,77 +1461,2251 @@ pub fn write_data(
                 ))
             }
         }
-        offset += fragment_size;
+
+        Ok(())
     }
-    Ok(())
-}
 
-pub fn deinit(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
-    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance = instance_arc_mutex.lock().map_err(|err| {
-        CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, format!("{}", err))
-    })?;
+    fn get_object_count(&mut self) -> Result<u16, CpcNvm3Error> {
+        let get_object_count_command =
+            CmdGetObjectCount::new(self.unique_id, &mut self.transaction_id);
+        let write_data = get_object_count_command.serialize()?;
+        self.write(&write_data)?;
+
+        let response = self.get_response(&get_object_count_command)?;
+        match response {
+            CmdGetObjectCountResponse::StatusCode(status_code) => match status_code {
+                StatusCode::SlStatus(sl_status) => match sl_status {
+                    SlStatus::Ok | SlStatus::Fail | SlStatus::Busy | SlStatus::Unknown => {
+                        Err(CpcNvm3Error::ErrorCodeWithContext(
+                            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                            format!("Received an unexpected sl_status code {}", status_code),
+                        ))
+                    }
+                },
+
+                StatusCode::ECode(e_code) => match e_code {
+                    ECode::KeyNotFound => Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
+                        format!("{}", status_code),
+                    )),
+                    _ => Err(e_code.into()),
+                },
+
+                StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
+                    format!("Unknown response type received"),
+                )),
+            },
+            CmdGetObjectCountResponse::ObjectCount { object_count } => Ok(object_count),
+        }
+    }
+
+    fn enumerate_objects(
+        &mut self,
+        max_key_count: u16,
+    ) -> Result<Vec<cpc_nvm3_object_key_t>, CpcNvm3Error> {
+        self.enumerate_objects_with_progress(max_key_count, &mut |_| true)
+    }
+
+    /// Enumerate the objects, calling `progress` with the number of keys received so far
+    /// after each fragment. The enumeration is cancelled as soon as `progress` returns false.
+    fn enumerate_objects_with_progress(
+        &mut self,
+        max_key_count: u16,
+        progress: &mut dyn FnMut(u16) -> bool,
+    ) -> Result<Vec<cpc_nvm3_object_key_t>, CpcNvm3Error> {
+        log::debug!(
+            "Sending object enumeration request with a limit of {} objects",
+            max_key_count
+        );
+        let enumerate_objects_command =
+            CmdEnumerateObjects::new(self.unique_id, &mut self.transaction_id, max_key_count);
+
+        self.write(&enumerate_objects_command.serialize()?)?;
+        self.with_command_timeout(&enumerate_objects_command, |instance| {
+            instance.enumerate_objects_fragments(
+                &enumerate_objects_command,
+                max_key_count,
+                progress,
+            )
+        })
+    }
+
+    // Reassemble the keys of the response to `enumerate_objects_command`
+    fn enumerate_objects_fragments(
+        &mut self,
+        enumerate_objects_command: &CmdEnumerateObjects,
+        max_key_count: u16,
+        progress: &mut dyn FnMut(u16) -> bool,
+    ) -> Result<Vec<cpc_nvm3_object_key_t>, CpcNvm3Error> {
+        let mut continue_reading = true;
+        // Only the reassembly limit bounds the response, a secondary may send more keys than
+        // requested. Up to that limit, the reservation covers the requested key count.
+        let mut data = Vec::with_capacity(
+            (max_key_count as usize * CPC_NVM3_OBJECT_KEY_SIZE)
+                .min(self.reassembly_limit(CPC_NVM3_MAX_ENUMERATION_SIZE)),
+        );
+
+        while continue_reading {
+            let response = self.read_response(enumerate_objects_command, None)?;
+            let (received_data, last_fragment) = process_enumerate_objects_response(response)?;
+            continue_reading = !last_fragment;
+            self.check_reassembly(
+                data.len() + received_data.len(),
+                CPC_NVM3_MAX_ENUMERATION_SIZE,
+            )?;
+            data.extend(received_data);
+
+            // The fragments still in flight after a cancellation carry a stale transaction
+            // ID, so the next command drops them.
+            if !progress((data.len() / CPC_NVM3_OBJECT_KEY_SIZE) as u16) {
+                return Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_CANCELLED,
+                    "The object enumeration was cancelled".to_string(),
+                ));
+            }
+        }
+
+        let num_objects = data.len() / CPC_NVM3_OBJECT_KEY_SIZE;
+        if num_objects * CPC_NVM3_OBJECT_KEY_SIZE != data.len() {
+            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                "The data length is not a multiple of the object size".to_string(),
+            ));
+        }
+
+        match extract_object_keys(&data) {
+            Ok((remaining, keys)) => {
+                if keys.len() != num_objects || remaining.len() != 0 {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        "Number of deserialized keys doesn't match the expected number."
+                            .to_string(),
+                    ));
+                }
+                Ok(keys)
+            }
+            Err(e) => Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                format!("Failed to deserialize keys: {:?}", e),
+            )),
+        }
+    }
+
+    // Unlike `enumerate_objects`, the keys are handed to `f` as each fragment arrives. A key
+    // split across two fragments is held back until its remaining bytes are received.
+    fn for_each_object_key(
+        &mut self,
+        f: &mut dyn FnMut(cpc_nvm3_object_key_t) -> bool,
+    ) -> Result<(), CpcNvm3Error> {
+        let enumerate_objects_command =
+            CmdEnumerateObjects::new(self.unique_id, &mut self.transaction_id, u16::MAX);
+
+        self.write(&enumerate_objects_command.serialize()?)?;
+        self.with_command_timeout(&enumerate_objects_command, |instance| {
+            instance.for_each_object_key_fragment(&enumerate_objects_command, f)
+        })
+    }
+
+    // Hand the keys of each fragment of the response to `enumerate_objects_command` to `f`
+    fn for_each_object_key_fragment(
+        &mut self,
+        enumerate_objects_command: &CmdEnumerateObjects,
+        f: &mut dyn FnMut(cpc_nvm3_object_key_t) -> bool,
+    ) -> Result<(), CpcNvm3Error> {
+        let mut pending = Vec::with_capacity(CPC_NVM3_OBJECT_KEY_SIZE);
+        let mut continue_reading = true;
+        while continue_reading {
+            let response = self.read_response(enumerate_objects_command, None)?;
+            let (received_data, last_fragment) = process_enumerate_objects_response(response)?;
+            continue_reading = !last_fragment;
+            pending.extend(received_data);
+
+            let whole_keys_len = pending.len() - pending.len() % CPC_NVM3_OBJECT_KEY_SIZE;
+            let keys = match extract_object_keys(&pending[..whole_keys_len]) {
+                Ok((_, keys)) => keys,
+                Err(e) => {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        format!("Failed to deserialize keys: {:?}", e),
+                    ))
+                }
+            };
+            pending.drain(..whole_keys_len);
+
+            for key in keys {
+                // Same as a cancelled enumeration, the fragments still in flight are
+                // dropped by the next command
+                if !f(key) {
+                    return Ok(());
+                }
+            }
+        }
+
+        if !pending.is_empty() {
+            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                "The data length is not a multiple of the object size".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // The enumeration limit starts one above the object count so that a single round trip
+    // normally returns every key
+    fn collect_object_keys(&mut self) -> Result<Vec<cpc_nvm3_object_key_t>, CpcNvm3Error> {
+        let mut max_key_count = self.get_object_count()?.saturating_add(1);
+        loop {
+            let keys = self.enumerate_objects(max_key_count)?;
+            if keys.len() < max_key_count as usize || max_key_count == u16::MAX {
+                return Ok(keys);
+            }
+            max_key_count = max_key_count.saturating_mul(2);
+        }
+    }
+
+    fn parse_response<C: Command>(
+        &mut self,
+        command: &C,
+        input: &[u8],
+        resync_attempts: &mut u8,
+        transaction_id_mismatches: &mut u16,
+    ) -> RxParseOutcome<C::Response, CpcNvm3Error> {
+        let result = command.parse_response(input);
+        // Only consecutive mismatches hint at a desynchronization
+        match result {
+            Err(ProtocolError::InvalidTransactionId(_, _)) => {
+                *transaction_id_mismatches = transaction_id_mismatches.saturating_add(1)
+            }
+            _ => *transaction_id_mismatches = 0,
+        }
+        match result {
+            Ok(response) => {
+                if self.strict_parsing {
+                    if let Err(err) = protocol::check_trailing_bytes(input) {
+                        return RxParseOutcome::Error(err.into());
+                    }
+                }
+                if let Some(raw_status) = StatusIs::raw_status(input) {
+                    self.last_status = Some(raw_status);
+                }
+                RxParseOutcome::Parsed(response)
+            }
+            Err(err) => match err {
+                ProtocolError::InvalidCommandId => {
+                    log::debug!("Dropping response with invalid command ID");
+                    RxParseOutcome::Retry(err)
+                }
+                ProtocolError::InvalidTransactionId(expected, actual) => {
+                    log::debug!(
+                        "Dropping response with invalid transaction ID {}. Expected {}",
+                        actual,
+                        expected
+                    );
+                    RxParseOutcome::Retry(err)
+                }
+                ProtocolError::InvalidUniqueId(expected, actual) => {
+                    log::debug!(
+                        "Dropping response with invalid unique ID {}. Expected {}",
+                        actual,
+                        expected
+                    );
+                    RxParseOutcome::Retry(err)
+                }
+                // A stray or concatenated frame, drop it to resync on the next one
+                ProtocolError::InvalidResponseLen(expected, actual)
+                    if *resync_attempts < CPC_NVM3_MAX_RESYNC_ATTEMPTS =>
+                {
+                    *resync_attempts += 1;
+                    log::warn!(
+                        "Dropping response with invalid length {}. Expected {} ({}/{})",
+                        actual,
+                        expected,
+                        resync_attempts,
+                        CPC_NVM3_MAX_RESYNC_ATTEMPTS
+                    );
+                    RxParseOutcome::Retry(err)
+                }
+                _ => RxParseOutcome::Error(err.into()),
+            },
+        }
+    }
+
+    // Read the response of a command answered in a single frame. The commands whose
+    // response is fragmented read each fragment with `read_response` under one
+    // `with_command_timeout`.
+    pub fn get_response<C: Command>(&mut self, command: &C) -> Result<C::Response, CpcNvm3Error> {
+        self.with_command_timeout(command, |instance| instance.read_response(command, None))
+    }
+
+    // Some commands take much longer than a regular operation. Their timeout is applied once
+    // for the whole response, whatever its number of fragments, and the read timeout of the
+    // endpoint is restored once `receive` returns. A failed restore doesn't discard what was
+    // received, it is only logged.
+    fn with_command_timeout<C: Command, T>(
+        &mut self,
+        command: &C,
+        receive: impl FnOnce(&mut Self) -> Result<T, CpcNvm3Error>,
+    ) -> Result<T, CpcNvm3Error> {
+        let timeout = match self.command_timeouts.get(&command.command_id()).copied() {
+            Some(timeout) => timeout,
+            None => return receive(self),
+        };
+        let previous_timeout = self.get_read_timeout()?;
+        self.set_read_timeout(timeout)?;
+        let result = receive(self);
+        if let Err(err) = self.set_read_timeout(previous_timeout) {
+            log::warn!(
+                "[{}] Failed to restore the read timeout after {}: {}",
+                self.label,
+                command_name::<C>(),
+                err
+            );
+        }
+        result
+    }
+
+    // With a deadline, each read waits at most for the time left before it, capped by the
+    // regular read timeout, so that dropped frames can't extend the operation past it
+    fn read_response<C: Command>(
+        &mut self,
+        command: &C,
+        deadline: Option<Instant>,
+    ) -> Result<C::Response, CpcNvm3Error> {
+        let mut empty_reads = 0;
+        let mut resync_attempts = 0;
+        let mut mismatched_responses: u16 = 0;
+        let mut transaction_id_mismatches: u16 = 0;
+        loop {
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                // A zero timeout makes the read block indefinitely, so it can't be used as a bound
+                if remaining.is_zero() {
+                    return Err(CpcNvm3Error::Timeout);
+                }
+                self.set_read_timeout(remaining.min(default_read_timeout()))?;
+            }
+            let rx_packet = self.read()?;
+            // The response of a resumed request carries the transaction ID it was sent with
+            let rx_packet = match self.resumed_transaction_id {
+                Some((carried, expected))
+                    if protocol::frame_transaction_id(&rx_packet) == Some(carried) =>
+                {
+                    protocol::with_transaction_id(&rx_packet, expected)
+                }
+                _ => rx_packet,
+            };
+            // An empty frame carries no header to validate, drop it and read again
+            if rx_packet.is_empty() {
+                empty_reads += 1;
+                log::warn!(
+                    "Received an empty frame from the CPC endpoint ({}/{})",
+                    empty_reads,
+                    CPC_NVM3_MAX_EMPTY_READS
+                );
+                if empty_reads >= CPC_NVM3_MAX_EMPTY_READS {
+                    return Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+                        "Only received empty frames from the CPC endpoint, try again".to_string(),
+                    ));
+                }
+                continue;
+            }
+            match self.parse_response(
+                command,
+                &rx_packet,
+                &mut resync_attempts,
+                &mut transaction_id_mismatches,
+            ) {
+                RxParseOutcome::Parsed(response) => {
+                    self.response_received = true;
+                    self.pending_request = None;
+                    if let Some(request_sent_at) = self.request_sent_at.take() {
+                        let round_trip_us = request_sent_at.elapsed().as_micros() as u64;
+                        log::trace!(
+                            "[{}] {} round trip took {} us",
+                            self.label,
+                            command_name::<C>(),
+                            round_trip_us
+                        );
+                        self.record_round_trip(round_trip_us);
+                    }
+                    return Ok(response);
+                }
+                // A secondary that restarted its sequence mid-session answers every command
+                // with a transaction ID of its own, reconnecting starts a new session
+                RxParseOutcome::Retry(err)
+                    if self.desync_threshold != 0
+                        && transaction_id_mismatches == self.desync_threshold =>
+                {
+                    log::warn!(
+                        "[{}] {} consecutive responses carried an unexpected transaction ID, the secondary is out of sync",
+                        self.label,
+                        transaction_id_mismatches
+                    );
+                    if self.reconnect_on_desync {
+                        if !self.auto_reconnect {
+                            self.forget_pending_request();
+                            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                                CpcNvm3ErrorCodes::CPC_NVM3_CONNECTION_LOST,
+                                "The secondary is out of sync, the automatic reconnection is disabled"
+                                    .to_string(),
+                            ));
+                        }
+                        return Err(self.reconnect_and_retry());
+                    }
+                    if mismatched_responses < self.max_mismatched_responses {
+                        mismatched_responses += 1;
+                        continue;
+                    }
+                    return Err(self.too_many_mismatched_responses::<C>(mismatched_responses, err));
+                }
+                // Each dropped frame was read within the timeout, so a fast enough stream of
+                // frames for someone else would otherwise keep the operation going forever
+                RxParseOutcome::Retry(_)
+                    if mismatched_responses < self.max_mismatched_responses =>
+                {
+                    mismatched_responses += 1;
+                    continue;
+                }
+                RxParseOutcome::Retry(err) => {
+                    return Err(self.too_many_mismatched_responses::<C>(mismatched_responses, err))
+                }
+                RxParseOutcome::Error(err) => return Err(err),
+            }
+        }
+    }
+
+    // Each round trip weighs for 1/8 of the average, the same smoothing as the TCP round trip
+    // time, so that a single slow command doesn't hide the usual latency
+    fn record_round_trip(&mut self, round_trip_us: u64) {
+        self.round_trip_average_us = Some(match self.round_trip_average_us {
+            Some(average_us) => (average_us * 7 + round_trip_us) / 8,
+            None => round_trip_us,
+        });
+    }
+
+    // The operation fails with the reason the last response was dropped
+    fn too_many_mismatched_responses<C: Command>(
+        &self,
+        mismatched_responses: u16,
+        last_error: ProtocolError,
+    ) -> CpcNvm3Error {
+        let err = CpcNvm3Error::TooManyMismatchedResponses {
+            responses: mismatched_responses + 1,
+            command: command_name::<C>(),
+            last_error,
+        };
+        log::warn!("[{}] {}", self.label, err);
+        err
+    }
+}
+
+fn default_command_timeouts() -> HashMap<u8, Duration> {
+    HashMap::from([(
+        crate::CPC_NVM3_COMMAND_ENUMERATE_OBJECTS,
+        Duration::from_secs(CPC_NVM3_ENUMERATE_TIMEOUT_S),
+    )])
+}
+
+// The command type name without its module path, e.g. `CmdReadData`
+fn command_name<C>() -> &'static str {
+    let name = std::any::type_name::<C>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[allow(non_camel_case_types)] // This will be used in a generated a C header file
+pub type cpc_nvm3_handle_t = u32;
+#[allow(non_camel_case_types)] // This will be used in a generated a C header file
+pub type cpc_nvm3_object_key_t = u32;
+
+fn find_next_available_handle() -> Result<cpc_nvm3_handle_t, CpcNvm3Error> {
+    match CPC_NVM_LIB_INSTANCE_KEY.lock() {
+        Ok(mut id) => {
+            if *id == u32::MAX {
+                return Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                    format!("Instance key could not be incremented. Limit reached."),
+                ));
+            }
+            *id += 1;
+            Ok(*id)
+        }
+        Err(err) => Err(poisoned("NVM3 instance key", err)),
+    }
+}
+
+// An interrupt only targets the operation in flight, whoever takes the lock next starts
+// with the flag cleared
+fn lock_instance(instance_mutex: &InstanceMutex) -> Result<InstanceGuard<'_>, CpcNvm3Error> {
+    let instance = instance_mutex
+        .lock()
+        .map_err(|err| poisoned("NVM3 instance", err))?;
+    instance.interrupted.store(false, Ordering::SeqCst);
+    Ok(instance)
+}
+
+// Same as `lock_instance`, but returns `None` instead of waiting when the lock is held
+fn try_lock_instance(
+    instance_mutex: &InstanceMutex,
+) -> Result<Option<InstanceGuard<'_>>, CpcNvm3Error> {
+    match instance_mutex.try_lock() {
+        Ok(guard) => {
+            guard.interrupted.store(false, Ordering::SeqCst);
+            Ok(Some(guard))
+        }
+        Err(std::sync::TryLockError::WouldBlock) => Ok(None),
+        Err(std::sync::TryLockError::Poisoned(err)) => Err(poisoned("NVM3 instance", err)),
+    }
+}
+
+// A panic while a lock is held leaves what it guards in an unknown state, reported with a
+// code of its own so that the crash can be told apart from a failed operation
+fn poisoned<T>(lock_name: &str, _: PoisonError<T>) -> CpcNvm3Error {
+    CpcNvm3Error::ErrorCodeWithContext(
+        CpcNvm3ErrorCodes::CPC_NVM3_INTERNAL_POISONED,
+        format!(
+            "The {} lock was poisoned by a panic in another thread",
+            lock_name
+        ),
+    )
+}
+
+fn get_instance(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<Arc<InstanceMutex>, CpcNvm3Error> {
+    let instances = CPC_NVM3_LIB_INSTANCES
+        .lock()
+        .map_err(|err| poisoned("NVM3 instance map", err))?;
+
+    let instance_mutex = match instances.get(&cpc_nvm3_handle) {
+        Some(instance) => instance,
+        None => {
+            Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_NOT_INITIALIZED,
+                format!(
+                    "Could not find CPC NVM3 instance {}. The handle was never returned by \
+                     cpc_nvm3_init or it has already been released with cpc_nvm3_deinit.",
+                    cpc_nvm3_handle
+                ),
+            ))
+        }?,
+    };
+
+    Ok(Arc::clone(instance_mutex))
+}
+
+pub struct FileLogger {
+    level: log::LevelFilter,
+    prefix: String,
+    file: Mutex<File>,
+}
+
+impl FileLogger {
+    pub fn new(level: log::LevelFilter, prefix: String, file: File) -> Self {
+        FileLogger {
+            level,
+            prefix,
+            file: Mutex::new(file),
+        }
+    }
+}
+
+impl FileLogger {
+    #[cfg(not(feature = "json-logs"))]
+    fn format_record(&self, record: &Record) -> String {
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        format!(
+            "{} {} - {}: {}\n",
+            timestamp,
+            self.prefix,
+            record.level(),
+            record.args()
+        )
+    }
+
+    #[cfg(feature = "json-logs")]
+    fn format_record(&self, record: &Record) -> String {
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        let line = serde_json::json!({
+            "ts": timestamp.to_string(),
+            "level": record.level().as_str(),
+            "prefix": self.prefix,
+            "target": record.target(),
+            "msg": record.args().to_string(),
+        });
+        format!("{}\n", line)
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    // Logging is best effort, a failure is reported on stderr rather than panicking in the
+    // middle of whatever operation logged. A panic while writing a line leaves the file as
+    // usable as any failed write, so a poisoned lock is taken over.
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = self.format_record(record);
+
+            let mut file_guard = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Err(err) = file_guard
+                .write_all(line.as_bytes())
+                .and_then(|_| file_guard.flush())
+            {
+                let _ = writeln!(std::io::stderr(), "Failed to write a log record: {}", err);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let mut file_guard = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(err) = file_guard.flush() {
+            let _ = writeln!(std::io::stderr(), "Failed to flush the log file: {}", err);
+        }
+    }
+}
+
+// The logger installed in `log`, it hands the records to the current `LOGGER_SINK`
+struct ForwardingLogger;
+
+static FORWARDING_LOGGER: ForwardingLogger = ForwardingLogger;
+
+impl Log for ForwardingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match LOGGER_SINK.read() {
+            Ok(sink) => sink
+                .as_ref()
+                .map_or(false, |logger| logger.enabled(metadata)),
+            Err(_) => false,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(sink) = LOGGER_SINK.read() {
+            if let Some(logger) = sink.as_ref() {
+                logger.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(sink) = LOGGER_SINK.read() {
+            if let Some(logger) = sink.as_ref() {
+                logger.flush();
+            }
+        }
+    }
+}
+
+pub fn init_logger(
+    prefix: Option<&str>,
+    level: CpcNvm3LogLevel,
+    file_path: Option<&str>,
+    append: bool,
+) -> Result<(), CpcNvm3Error> {
+    let mut logger_initialized = LOGGER_INITIALIZED
+        .lock()
+        .map_err(|err| poisoned("logger initialization", err))?;
+    let mut sink = LOGGER_SINK.write().map_err(|err| poisoned("logger", err))?;
+
+    if sink.is_none() {
+        let log_file = if let Some(path) = file_path {
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append) // This will set the file to append mode.
+                .open(path) // Open or create the file at the provided path.
+                .map_err(|e| {
+                    CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        format!("Failed to open or create log file: {:?}", e),
+                    )
+                })?
+        } else {
+            // Fall back to STDOUT if no file path is provided. The logger owns a duplicate so
+            // that shutting it down doesn't close the standard output of the host.
+            let stdout_fd = unsafe { libc::dup(STDOUT_FILENO) };
+            if stdout_fd < 0 {
+                return Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                    format!(
+                        "Failed to duplicate the standard output: {}",
+                        std::io::Error::last_os_error()
+                    ),
+                ));
+            }
+            unsafe { File::from_raw_fd(stdout_fd) }
+        };
+
+        if !*logger_initialized {
+            log::set_logger(&FORWARDING_LOGGER).map_err(|_| {
+                CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                    "Failed to set logger".to_string(),
+                )
+            })?;
+            *logger_initialized = true;
+        }
+
+        *sink = Some(FileLogger::new(
+            level.into(),
+            prefix.unwrap_or("").to_string(),
+            log_file,
+        ));
+        log::set_max_level(LevelFilter::from(level));
+    }
+    Ok(())
+}
+
+/// Flush and close the output of the logger, the records are dropped until `init_logger`
+/// is called again with a new output.
+pub fn shutdown_logger() -> Result<(), CpcNvm3Error> {
+    let mut sink = LOGGER_SINK.write().map_err(|err| poisoned("logger", err))?;
+
+    if let Some(logger) = sink.take() {
+        logger.flush();
+    }
+    log::set_max_level(LevelFilter::Off);
+    Ok(())
+}
+
+// Serializes the tests that set up the global logger, a test that shuts it down would
+// otherwise race with the others installing theirs
+#[cfg(test)]
+pub(crate) static TEST_LOGGER_LOCK: Mutex<()> = Mutex::new(());
+
+// Log the tests to the standard output, the first test to run installs the logger
+#[cfg(test)]
+pub(crate) fn init_test_logger() {
+    let _logger_lock = TEST_LOGGER_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let _ = init_logger(None, CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None, false);
+}
+
+pub fn init() -> Result<cpc_nvm3_handle_t, CpcNvm3Error> {
+    let handle = find_next_available_handle()?;
+    let mut cpc_nvm3_instance = CpcNvm3Instance::new();
+    cpc_nvm3_instance.label = handle.to_string();
+    #[cfg(not(test))]
+    {
+        cpc_nvm3_instance.unique_id = std::process::id();
+    }
+
+    // Push key/value to the instance map
+    let mut map = CPC_NVM3_LIB_INSTANCES
+        .lock()
+        .map_err(|err| poisoned("NVM3 instance map", err))?;
+    let interrupted = Arc::clone(&cpc_nvm3_instance.interrupted);
+    map.insert(handle, Arc::new(InstanceMutex::new(cpc_nvm3_instance)));
+    match CPC_NVM3_LIB_INTERRUPTS.lock() {
+        Ok(mut interrupts) => interrupts.insert(handle, interrupted),
+        Err(err) => {
+            map.remove(&handle);
+            return Err(poisoned("NVM3 interrupt map", err));
+        }
+    };
+
+    log::debug!("cpc_nvm3_init was successful, assigned handle {}", handle);
+
+    Ok(handle)
+}
+
+/// Interrupt the operation in flight on the handle, which then returns `CPC_NVM3_CANCELLED`.
+/// It does not wait for the lock held by that operation. The interrupt is noticed when a read
+/// of the operation times out, so within the read timeout, and does nothing when no
+/// operation is in flight. An operation whose reads keep receiving frames runs to
+/// completion, and a read failing for another reason reports its own error.
+pub fn interrupt(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
+    let interrupts = CPC_NVM3_LIB_INTERRUPTS
+        .lock()
+        .map_err(|err| poisoned("NVM3 interrupt map", err))?;
+    match interrupts.get(&cpc_nvm3_handle) {
+        Some(interrupted) => {
+            log::debug!("[{}] Interrupting NVM3 instance", cpc_nvm3_handle);
+            interrupted.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        None => Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_NOT_INITIALIZED,
+            format!("Could not find CPC NVM3 instance {}", cpc_nvm3_handle),
+        )),
+    }
+}
+
+/// Whether the handle refers to an initialized instance, i.e. it was returned by `init`
+/// and has not been released by `deinit` since.
+pub fn handle_is_valid(cpc_nvm3_handle: cpc_nvm3_handle_t) -> bool {
+    get_instance(cpc_nvm3_handle).is_ok()
+}
+
+/// Set the label prefixed to the logs of the operations on this instance. An empty label
+/// restores the default, which is the handle number.
+pub fn set_instance_label(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    label: &str,
+) -> Result<(), CpcNvm3Error> {
+    if label.len() > CPC_NVM3_MAX_LABEL_LEN {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            format!(
+                "The instance label is {} bytes long, the maximum is {} bytes",
+                label.len(),
+                CPC_NVM3_MAX_LABEL_LEN
+            ),
+        ));
+    }
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    instance.label = match label {
+        "" => cpc_nvm3_handle.to_string(),
+        label => label.to_string(),
+    };
+    log::debug!(
+        "Instance {} is now labeled [{}]",
+        cpc_nvm3_handle,
+        instance.label
+    );
+    Ok(())
+}
+
+/// Set the unique ID identifying this host to the secondary, in place of the process ID.
+/// Hosts sharing a secondary through different cpcd instances can collide on the process
+/// ID, so a deployment can assign globally unique IDs instead. The ID must not be 0.
+///
+/// The ID is applied by the next `open`, or by the next reconnection to libcpc. Changing it
+/// while the instance is opened has no effect until then.
+pub fn set_unique_id(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    unique_id: u32,
+) -> Result<(), CpcNvm3Error> {
+    if unique_id == 0 {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The unique ID must not be 0".to_string(),
+        ));
+    }
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Setting unique ID to {:#010x}",
+        instance.label,
+        unique_id
+    );
+
+    instance.requested_unique_id = Some(unique_id);
+    Ok(())
+}
+
+/// Get the name of the cpcd instance given to the last successful `open` of this instance.
+pub fn get_instance_name(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<String, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching cpcd instance name", instance.label);
+
+    instance
+        .cpcd_instance_name
+        .clone()
+        .ok_or(CpcNvm3Error::NotOpen)
+}
+
+/// Make the next request of the instance carry the transaction ID `seed`, the following ones
+/// counting up from there. This is meant for protocol captures compared byte for byte, a
+/// response still in flight for an earlier request could otherwise be mistaken for the
+/// response of a new one.
+#[cfg(any(test, feature = "debug-api"))]
+pub fn set_transaction_id_seed(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    seed: u8,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Seeding the transaction ID with {}",
+        instance.label,
+        seed
+    );
+
+    // The transaction ID is incremented before being used by each request
+    instance.transaction_id = seed.wrapping_sub(1);
+    Ok(())
+}
+
+/// Get the last transaction ID allocated by the instance, to match frame captures with the
+/// logs. It wraps from 255 to 0. Once seeded with `set_transaction_id_seed`, this is the ID
+/// before the seed until the next request is sent.
+pub fn get_last_transaction_id(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u8, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching last transaction ID", instance.label);
+
+    Ok(instance.transaction_id)
+}
+
+/// Get the label of an instance for logging. This falls back to the handle number when
+/// the instance can't be found or is locked by another thread, logging an error never
+/// waits for an operation in progress.
+pub fn instance_label(cpc_nvm3_handle: cpc_nvm3_handle_t) -> String {
+    match get_instance(cpc_nvm3_handle) {
+        Ok(instance_arc_mutex) => match try_lock_instance(&instance_arc_mutex) {
+            Ok(Some(instance)) => instance.label.clone(),
+            _ => cpc_nvm3_handle.to_string(),
+        },
+        Err(_) => cpc_nvm3_handle.to_string(),
+    }
+}
+
+pub fn open(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpcd_instance_name: &str,
+    enable_cpc_traces: bool,
+) -> Result<(), CpcNvm3Error> {
+    open_ex(
+        cpc_nvm3_handle,
+        cpcd_instance_name,
+        enable_cpc_traces,
+        Duration::from_secs(CPC_NVM3_OPEN_TIMEOUT_S),
+    )
+}
+
+/// Same as `open`, but the call must complete within `open_timeout`, otherwise
+/// `CPC_NVM3_TIMEOUT` is returned. The deadline starts on entry, so it also covers waiting
+/// for the instance and connecting to CPCd. While opening, each read, including those of the
+/// responses dropped along the way, waits at most for the time left before the deadline,
+/// capped by the regular read timeout.
+pub fn open_ex(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpcd_instance_name: &str,
+    enable_cpc_traces: bool,
+    open_timeout: Duration,
+) -> Result<(), CpcNvm3Error> {
+    let deadline = Instant::now() + open_timeout;
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut cpc_nvm3_instance = lock_instance(&instance_arc_mutex)?;
+
+    cpc_nvm3_instance.open(cpcd_instance_name, enable_cpc_traces, deadline)?;
+
+    log::debug!(
+        "cpc_nvm3_open was successful, on handle {}",
+        cpc_nvm3_handle
+    );
+
+    Ok(())
+}
+
+/// Same as `open`, but an attempt failing because CPCd or the NVM3 endpoint is not reachable
+/// yet is made again, `max_attempts` times at most with `retry_delay` between two attempts.
+/// This covers hosts booting before CPCd is ready. The error of the last attempt is returned.
+pub fn open_with_retry(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpcd_instance_name: &str,
+    enable_cpc_traces: bool,
+    max_attempts: u32,
+    retry_delay: Duration,
+) -> Result<(), CpcNvm3Error> {
+    if max_attempts == 0 {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The number of attempts must not be 0".to_string(),
+        ));
+    }
+
+    let mut attempt = 1;
+    loop {
+        match open(cpc_nvm3_handle, cpcd_instance_name, enable_cpc_traces) {
+            Err(err)
+                if attempt < max_attempts
+                    && matches!(
+                        err.code(),
+                        CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR
+                            | CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN
+                    ) =>
+            {
+                log::warn!(
+                    "[{}] Open attempt {} of {} failed, retrying in {} ms. {}",
+                    instance_label(cpc_nvm3_handle),
+                    attempt,
+                    max_attempts,
+                    retry_delay.as_millis(),
+                    err
+                );
+                std::thread::sleep(retry_delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Write a data object and return the number of bytes written, which is the whole `data`.
+/// The secondary commits the object once its last fragment is received, so a failed write
+/// leaves no partial object behind.
+pub fn write_data(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    data: &[u8],
+) -> Result<u16, CpcNvm3Error> {
+    write_data_ex(cpc_nvm3_handle, cpc_nvm3_object_key, data)?;
+    Ok(data.len() as u16)
+}
+
+/// Same as `write_data`, but returns the number of fragments that were
+/// required to transfer the data to the secondary.
+pub fn write_data_ex(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    data: &[u8],
+) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Writing to NVM3 instance", instance.label);
+
+    instance.write_data(cpc_nvm3_object_key, data)
+}
+
+/// Same as `write_data_ex`, and `bytes_written` is set to the length of the fragments the
+/// secondary acknowledged, also when the write fails. NVM3 only commits the object with its
+/// last fragment, so the object is written when `bytes_written` is the whole `data`. Fewer
+/// bytes tell how far the transfer went before it failed.
+pub fn write_data_acknowledged(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    data: &[u8],
+    bytes_written: &mut u16,
+) -> Result<u16, CpcNvm3Error> {
+    *bytes_written = 0;
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Writing to NVM3 instance", instance.label);
+
+    instance.write_data_acknowledged(cpc_nvm3_object_key, data, bytes_written)
+}
+
+/// Write `length` bytes read from `reader` to a data object. The data is read one fragment
+/// at a time, right before the fragment is sent, so the object is never buffered entirely.
+///
+/// If `reader` fails or ends before `length` bytes, the write is aborted before its last
+/// fragment is sent.
+pub fn write_data_from_reader(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    reader: &mut dyn std::io::Read,
+    length: u16,
+) -> Result<u16, CpcNvm3Error> {
+    if length == 0 {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The length of the data to write must not be 0".to_string(),
+        ));
+    }
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Writing {} bytes from a reader to NVM3 instance",
+        instance.label,
+        length
+    );
+
+    instance.write_fragments(
+        cpc_nvm3_object_key,
+        length as usize,
+        |offset, fragment| match reader.read_exact(fragment) {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                    format!(
+                        "The data ended before the {} bytes to write were read",
+                        length
+                    ),
+                ))
+            }
+            Err(err) => Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                format!(
+                    "Failed to read the data to write at offset {}: {}",
+                    offset, err
+                ),
+            )),
+        },
+    )
+}
+
+/// Append `data` to the end of a data object, creating the object if it doesn't exist yet.
+/// Returns the size of the object once the data is appended. An object that would grow past
+/// the largest object that can be written is left untouched with `CPC_NVM3_OBJECT_TOO_LARGE`.
+///
+/// The append is not atomic. The secondary only commits an object once its last fragment is
+/// received, from a buffer that starts at offset 0, so the current content is read back and
+/// written again ahead of `data`. The instance lock is held throughout, but a write from
+/// another host sharing the secondary in between is overwritten by the append.
+pub fn append_data(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    data: &[u8],
+) -> Result<u16, CpcNvm3Error> {
+    if data.is_empty() {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The data to append must not be empty".to_string(),
+        ));
+    }
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Appending to NVM3 data object", instance.label);
+
+    let current_size = match instance.get_object_info(cpc_nvm3_object_key) {
+        Ok((object_size, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA)) => object_size,
+        Ok((_, object_type)) => return Err(wrong_object_type(cpc_nvm3_object_key, object_type)),
+        Err(CpcNvm3Error::InvalidKey(_)) => 0,
+        Err(err) => return Err(err),
+    };
+
+    // Checked before reading the current content, which would be wasted otherwise
+    let total_size = current_size as usize + data.len();
+    let maximum_size = instance
+        .get_maximum_object_size()?
+        .min(instance.get_maximum_write_size()?);
+    if total_size > maximum_size as usize {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_OBJECT_TOO_LARGE,
+            format!(
+                "Appending {} bytes to the object {} of {} bytes exceeds the largest object that can be written ({})",
+                data.len(),
+                cpc_nvm3_object_key,
+                current_size,
+                maximum_size
+            ),
+        ));
+    }
+
+    let mut object = if current_size > 0 {
+        instance.read_data(cpc_nvm3_object_key, current_size)?
+    } else {
+        Vec::new()
+    };
+    object.extend_from_slice(data);
+
+    instance.write_data(cpc_nvm3_object_key, &object)?;
+    Ok(object.len() as u16)
+}
+
+/// Write several data objects while holding the instance lock. The batch stops at the
+/// first failing entry, so the returned results only cover the entries up to and including
+/// that failure. Every entry before it was written and can be skipped when retrying.
+///
+/// Each successful entry holds the number of bytes written for that object. `bytes_written`
+/// needs a slot per entry and gets the length of the fragments the secondary acknowledged
+/// for each of them, which tells how far the failing entry went before the batch stopped.
+/// Its object is not committed though, and the entries that were not attempted get 0.
+pub fn write_data_batch(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    entries: &[(cpc_nvm3_object_key_t, &[u8])],
+    bytes_written: &mut [u16],
+) -> Result<Vec<Result<u16, CpcNvm3Error>>, CpcNvm3Error> {
+    if bytes_written.len() < entries.len() {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            format!(
+                "{} bytes written slots were provided for {} entries",
+                bytes_written.len(),
+                entries.len()
+            ),
+        ));
+    }
+    bytes_written.fill(0);
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Writing {} data objects to NVM3 instance",
+        instance.label,
+        entries.len()
+    );
+
+    let mut results = Vec::with_capacity(entries.len());
+    for ((key, data), acknowledged) in entries.iter().zip(bytes_written.iter_mut()) {
+        let result = instance
+            .write_data_acknowledged(*key, data, acknowledged)
+            .map(|_| data.len() as u16);
+        let failed = result.is_err();
+        results.push(result);
+        if failed {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+pub fn deinit(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
+    deinit_instance(cpc_nvm3_handle, false)
+}
+
+/// Same as `deinit`, but an instance that is still opened is closed first instead of
+/// being refused. This is meant for cleanup paths where the open state is not known, and
+/// it is the only way to release an instance whose lock was poisoned by a panic.
+pub fn deinit_force(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
+    deinit_instance(cpc_nvm3_handle, true)
+}
+
+fn deinit_instance(cpc_nvm3_handle: cpc_nvm3_handle_t, force: bool) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    // Everything the instance holds is released, so a forced de-init also accepts an
+    // instance poisoned by a panic, which can't be used for anything else
+    let mut instance = if force {
+        instance_arc_mutex
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    } else {
+        lock_instance(&instance_arc_mutex)?
+    };
 
     log::debug!("Deinit NVM3 instance");
+    if force && instance.cpc_endpoint.is_some() {
+        log::warn!(
+            "[{}] The NVM3 instance is still opened, closing it before de-init",
+            instance.label
+        );
+        instance.close()?;
+    }
+
     // About to de-init the instance, make sure the cpc endpoint is also closed.
     if instance.cpc_endpoint.is_some() || instance.cpc_handle.is_some() {
         return Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_NOT_CLOSED,
-            format!(
-                "Failed to de-init NVM3 instance. It is still opened. Call cpc_nvm3_close first."
+            CpcNvm3ErrorCodes::CPC_NVM3_NOT_CLOSED,
+            format!(
+                "Failed to de-init NVM3 instance. It is still opened. Call cpc_nvm3_close first."
+            ),
+        ));
+    };
+
+    instance.transaction_id = 0;
+    instance.maximum_write_fragment_size = None;
+    instance.maximum_write_size = None;
+    instance.maximum_object_size = None;
+    instance.maximum_object_size_approximate = false;
+    instance.cpc_max_write_size = None;
+    instance.features = None;
+    instance.cpcd_instance_name = None;
+    instance.secondary_version = None;
+    instance.last_status = None;
+    instance.nonblocking = false;
+    instance.forget_pending_request();
+    instance.reconnect_count = 0;
+    instance.last_op_reconnected = false;
+    instance.request_sent_at = None;
+    instance.round_trip_average_us = None;
+    instance.max_mismatched_responses = CPC_NVM3_MAX_MISMATCHED_RESPONSES;
+    instance.desync_threshold = CPC_NVM3_DESYNC_THRESHOLD;
+    instance.reconnect_on_desync = false;
+    instance.strict_parsing = false;
+    instance.auto_reconnect = true;
+    instance.max_reassembly = 0;
+    instance.command_timeouts = default_command_timeouts();
+
+    if let Ok(mut interrupts) = CPC_NVM3_LIB_INTERRUPTS.lock() {
+        interrupts.remove(&cpc_nvm3_handle);
+    }
+    match CPC_NVM3_LIB_INSTANCES.lock() {
+        Ok(mut map) => {
+            map.remove(&cpc_nvm3_handle);
+            Ok(())
+        }
+        Err(err) => Err(poisoned("NVM3 instance map", err)),
+    }
+}
+
+pub fn close(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
+    // Close the CPC endpoint
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+    instance.close()?;
+    instance.cpc_endpoint = None;
+    Ok(())
+}
+
+/// Make sure every write acknowledged so far is committed to flash. The protocol has no
+/// command for it since the secondary only acknowledges a write once NVM3 stored the object,
+/// so an opened instance reports `CPC_NVM3_NOT_SUPPORTED` and there is nothing to wait for.
+pub fn sync(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Syncing NVM3 instance", instance.label);
+
+    if instance.cpc_endpoint.is_none() {
+        return Err(CpcNvm3Error::NotOpen);
+    }
+    Err(CpcNvm3Error::ErrorCodeWithContext(
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_SUPPORTED,
+        "The secondary has no sync command, acknowledged writes are already committed".to_string(),
+    ))
+}
+
+/// Send a no-op command to the secondary and wait for its acknowledgement
+pub fn ping(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Pinging NVM3 instance", instance.label);
+
+    instance.ping()
+}
+
+/// Periodically ping the secondary so the endpoint never stays idle for longer than
+/// `interval`. A background thread wakes up every `interval` and pings only if nothing
+/// was written in the meantime. When a user operation holds the instance lock, the ping
+/// is skipped rather than waiting for the lock. Enabling the keepalive again replaces the
+/// previous interval.
+pub fn enable_keepalive(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    interval: Duration,
+) -> Result<(), CpcNvm3Error> {
+    if interval.is_zero() {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The keepalive interval must not be 0".to_string(),
+        ));
+    }
+
+    disable_keepalive(cpc_nvm3_handle)?;
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    if instance.cpc_endpoint.is_none() {
+        return Err(CpcNvm3Error::NotOpen);
+    }
+
+    log::debug!(
+        "[{}] Enabling keepalive every {} ms",
+        instance.label,
+        interval.as_millis()
+    );
+
+    let (stop, stopped) = mpsc::channel::<()>();
+    let thread = std::thread::spawn(move || {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+            // The instance is looked up on every tick so the thread never keeps it alive
+            let instance_arc_mutex = match get_instance(cpc_nvm3_handle) {
+                Ok(instance_arc_mutex) => instance_arc_mutex,
+                Err(_) => return,
+            };
+            let mut instance = match try_lock_instance(&instance_arc_mutex) {
+                Ok(Some(instance)) => instance,
+                Ok(None) => continue,
+                Err(_) => return,
+            };
+            if instance.cpc_endpoint.is_none() || instance.last_activity.elapsed() < interval {
+                continue;
+            }
+            log::debug!("[{}] Sending keepalive", instance.label);
+            if let Err(err) = instance.ping() {
+                log::warn!("[{}] Keepalive failed. {}", instance.label, err);
+            }
+        }
+    });
+
+    instance.keepalive = Some(Keepalive { stop, thread });
+    Ok(())
+}
+
+/// Stop the keepalive started by `enable_keepalive`. This does nothing if it was not enabled.
+pub fn disable_keepalive(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let keepalive = lock_instance(&instance_arc_mutex)?.keepalive.take();
+
+    if let Some(keepalive) = keepalive {
+        log::debug!("[{}] Disabling keepalive", instance_label(cpc_nvm3_handle));
+        drop(keepalive.stop);
+        // The lock is released so that a keepalive in progress can complete
+        let _ = keepalive.thread.join();
+    }
+    Ok(())
+}
+
+pub fn get_object_count(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Getting objects count from NVM3 instance",
+        instance.label
+    );
+
+    instance.get_object_count()
+}
+
+/// Count the objects of the given type. The enumeration does not report the object types,
+/// so this costs one object info request per object on top of the enumeration. An object
+/// deleted between the enumeration and its info request is not counted.
+pub fn get_object_count_by_type(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    object_type: CpcNvm3ObjectType,
+) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Counting objects of type {} from NVM3 instance",
+        instance.label,
+        object_type
+    );
+
+    let mut count = 0;
+    for key in instance.collect_object_keys()? {
+        match instance.get_object_info(key) {
+            Ok((_, key_type)) if key_type == object_type => count += 1,
+            Ok(_) => {}
+            Err(CpcNvm3Error::InvalidKey(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(count)
+}
+
+fn process_enumerate_objects_response(
+    response: CmdEnumerateObjectsResponse,
+) -> Result<(Vec<u8>, bool), CpcNvm3Error> {
+    // Response can either be an error (StatusIs) or a success with the data
+    match response {
+        CmdEnumerateObjectsResponse::Data(segment, last_fragment) => {
+            if !last_fragment {
+                log::debug!(
+                    "Received {} bytes. Another fragment is available, fetching object list again",
+                    segment.len()
+                );
+            }
+            Ok((segment, last_fragment))
+        }
+        CmdEnumerateObjectsResponse::StatusCode(status_code) => match status_code {
+            StatusCode::SlStatus(sl_status) => match sl_status {
+                SlStatus::Ok | SlStatus::Fail | SlStatus::Unknown => {
+                    Err(CpcNvm3Error::ErrorCodeWithContext(
+                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+                        format!("Received an unexpected sl_status code {}", status_code),
+                    ))
+                }
+                SlStatus::Busy => Err(CpcNvm3Error::ErrorCodeWithContext(
+                    CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+                    "NVM3 is busy with another operation, try again".to_string(),
+                )),
+            },
+
+            // A nearly full storage can make the enumeration itself fail, From<ECode> tells
+            // these apart so that the operator can be warned
+            StatusCode::ECode(e_code) => Err(e_code.into()),
+
+            StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
+                format!("Unknown response type received"),
+            )),
+        },
+    }
+}
+
+pub fn extract_object_keys(input: &[u8]) -> nom::IResult<&[u8], Vec<cpc_nvm3_object_key_t>> {
+    many0(le_u32)(input)
+}
+
+pub fn list_objects(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_keys_ptr: &mut [cpc_nvm3_object_key_t],
+    object_count: &mut u16,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Listing objects from NVM3 instance", instance.label);
+
+    let keys = instance.enumerate_objects(cpc_nvm3_object_keys_ptr.len() as u16)?;
+    if keys.len() > cpc_nvm3_object_keys_ptr.len() {
+        return Err(CpcNvm3Error::BufferTooSmall {
+            needed: keys.len(),
+            got: cpc_nvm3_object_keys_ptr.len(),
+        });
+    };
+
+    cpc_nvm3_object_keys_ptr[..keys.len()].copy_from_slice(&keys);
+    *object_count = keys.len() as u16;
+    Ok(())
+}
+
+/// Same as `list_objects`, but `progress` is called with the running key count after each
+/// received fragment. Returning false from `progress` cancels the enumeration, in which case
+/// `CPC_NVM3_CANCELLED` is returned and the buffer is left untouched.
+pub fn list_objects_with_progress(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_keys_ptr: &mut [cpc_nvm3_object_key_t],
+    object_count: &mut u16,
+    mut progress: impl FnMut(u16) -> bool,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Listing objects from NVM3 instance", instance.label);
+
+    let keys = instance
+        .enumerate_objects_with_progress(cpc_nvm3_object_keys_ptr.len() as u16, &mut progress)?;
+    if keys.len() > cpc_nvm3_object_keys_ptr.len() {
+        return Err(CpcNvm3Error::BufferTooSmall {
+            needed: keys.len(),
+            got: cpc_nvm3_object_keys_ptr.len(),
+        });
+    };
+
+    cpc_nvm3_object_keys_ptr[..keys.len()].copy_from_slice(&keys);
+    *object_count = keys.len() as u16;
+    Ok(())
+}
+
+/// List every object key without requiring the caller to size a buffer up front.
+///
+/// The enumeration limit starts one above the object count reported by the secondary so
+/// that a single round trip normally returns every key. If the secondary still fills the
+/// limit, objects were created in the meantime and the enumeration is repeated with a
+/// larger limit.
+pub fn collect_object_keys(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+) -> Result<Vec<cpc_nvm3_object_key_t>, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Collecting objects from NVM3 instance", instance.label);
+
+    instance.collect_object_keys()
+}
+
+/// Call `f` with each object key as the enumeration fragments arrive from the secondary,
+/// without requiring a buffer for the keys. The enumeration stops early, successfully, as
+/// soon as `f` returns false. `f` runs while holding the instance lock.
+pub fn for_each_object_key(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    mut f: impl FnMut(cpc_nvm3_object_key_t) -> bool,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Streaming object keys from NVM3 instance",
+        instance.label
+    );
+
+    instance.for_each_object_key(&mut f)
+}
+
+/// The content of an object visited by `snapshot`
+#[derive(Debug, PartialEq, Eq)]
+pub enum ObjectValue<'a> {
+    Data(&'a [u8]),
+    Counter(u32),
+}
+
+/// Visit every object of the instance in a single enumeration. For each key, the type and
+/// size of the object are queried and its content is read, then `f` is called with the key,
+/// the type and the content. The instance lock is held for the whole snapshot so that no
+/// other operation of this process can modify the objects in between, which also blocks
+/// them until the snapshot completes. An object that vanished before it was read, or that
+/// can't be read as its type, is logged and skipped so that it doesn't abort the snapshot,
+/// and the number of skipped objects is returned. Any other error, e.g. a lost connection,
+/// aborts the snapshot.
+pub fn snapshot(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    mut f: impl FnMut(cpc_nvm3_object_key_t, CpcNvm3ObjectType, ObjectValue),
+) -> Result<usize, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Taking a snapshot of NVM3 instance", instance.label);
+
+    let mut skipped = 0;
+    for key in instance.collect_object_keys()? {
+        let result = match instance.get_object_info(key) {
+            Ok((object_size, object_type @ CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA)) => {
+                instance
+                    .read_data(key, object_size)
+                    .map(|data| f(key, object_type, ObjectValue::Data(&data)))
+            }
+            Ok((_, object_type @ CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER)) => instance
+                .read_counter(key)
+                .map(|value| f(key, object_type, ObjectValue::Counter(value))),
+            Ok((_, object_type)) => Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE,
+                format!("The object has an unexpected type {}", object_type),
+            )),
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(_) => {}
+            Err(err) if err.is_object_error() => {
+                log::warn!(
+                    "[{}] Skipping object {:?} from the snapshot. {}",
+                    instance.label,
+                    key,
+                    err
+                );
+                skipped += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(skipped)
+}
+
+/// Read every object of the instance while holding the instance lock. `on_data` is called
+/// with the content of each data object and `on_counter` with the value of each counter.
+/// Objects are skipped, or the export aborted, as with `snapshot`, and the number of skipped
+/// objects is returned.
+pub fn export_all(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    mut on_data: impl FnMut(cpc_nvm3_object_key_t, &[u8]),
+    mut on_counter: impl FnMut(cpc_nvm3_object_key_t, u32),
+) -> Result<usize, CpcNvm3Error> {
+    snapshot(cpc_nvm3_handle, |key, _, value| match value {
+        ObjectValue::Data(data) => on_data(key, data),
+        ObjectValue::Counter(value) => on_counter(key, value),
+    })
+}
+
+/// Export every object of the instance to the existing directory `dir`, one file per key
+/// named after its decimal value. A data object is written as is to `<key>.bin` and a counter
+/// as its 4 bytes little endian value to `<key>.cnt`. Each object is written as soon as it's
+/// read, so a single object is held in memory at a time. An object that can't be read, as
+/// with `snapshot`, or written is logged and skipped, and the number of exported objects is
+/// returned.
+pub fn export_all_to_dir(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    dir: &std::path::Path,
+) -> Result<usize, CpcNvm3Error> {
+    if !dir.is_dir() {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            format!("{} is not a directory", dir.display()),
+        ));
+    }
+
+    // The callbacks run under the instance lock, which `instance_label` would wait for
+    let label = instance_label(cpc_nvm3_handle);
+    let exported = std::cell::Cell::new(0);
+    let export_file = |key: cpc_nvm3_object_key_t, extension: &str, content: &[u8]| {
+        let path = dir.join(format!("{}.{}", key, extension));
+        match std::fs::write(&path, content) {
+            Ok(_) => exported.set(exported.get() + 1),
+            Err(err) => log::warn!(
+                "[{}] Skipping object {:?} from the export, failed to write {}: {}",
+                label,
+                key,
+                path.display(),
+                err
             ),
+        }
+    };
+    export_all(
+        cpc_nvm3_handle,
+        |key, data| export_file(key, "bin", data),
+        |key, value| export_file(key, "cnt", &value.to_le_bytes()),
+    )?;
+    Ok(exported.get())
+}
+
+/// Back up every object of the instance to the file at `path`, in the format described in
+/// the `backup` module. Objects that can't be read are skipped as with `snapshot`, and the
+/// number of skipped objects is returned. A link error aborts the backup and no file is
+/// written.
+pub fn backup_to_file(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    path: &std::path::Path,
+) -> Result<usize, CpcNvm3Error> {
+    let (file, skipped) = backup(cpc_nvm3_handle)?;
+    std::fs::write(path, file).map_err(|err| {
+        CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+            format!("Failed to write the backup to {}: {}", path.display(), err),
+        )
+    })?;
+    Ok(skipped)
+}
+
+/// Same as `backup_to_file`, for hosts without a file system. The backup is written to the
+/// start of `buffer` and its length to `used_len`. A buffer too small for it is left
+/// untouched and `used_len` still holds the length it needs, so that the caller can size
+/// the buffer with a first call.
+pub fn backup_to_buffer(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    buffer: &mut [u8],
+    used_len: &mut usize,
+) -> Result<usize, CpcNvm3Error> {
+    let (file, skipped) = backup(cpc_nvm3_handle)?;
+    *used_len = file.len();
+    if file.len() > buffer.len() {
+        return Err(CpcNvm3Error::BufferTooSmall {
+            needed: file.len(),
+            got: buffer.len(),
+        });
+    }
+    buffer[..file.len()].copy_from_slice(&file);
+    Ok(skipped)
+}
+
+// Encode every object of the instance and return the backup along with the number of
+// objects that could not be read
+fn backup(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(Vec<u8>, usize), CpcNvm3Error> {
+    let records = std::cell::RefCell::new(Vec::new());
+    let skipped = export_all(
+        cpc_nvm3_handle,
+        |key, data| {
+            records
+                .borrow_mut()
+                .push(backup::BackupRecord::Data(key, data.to_vec()))
+        },
+        |key, value| {
+            records
+                .borrow_mut()
+                .push(backup::BackupRecord::Counter(key, value))
+        },
+    )?;
+
+    let file = backup::encode(&records.into_inner())?;
+    Ok((file, skipped))
+}
+
+/// Write every object of the backup file at `path` back to the instance and return the
+/// number of restored objects. The whole file is validated before the first write, so a
+/// file with a wrong magic, format version or CRC is rejected with `CPC_NVM3_BACKUP_CORRUPT`
+/// without touching the secondary. The restore stops at the first object that fails to be
+/// written.
+pub fn restore_from_file(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    path: &std::path::Path,
+) -> Result<usize, CpcNvm3Error> {
+    let file = std::fs::read(path).map_err(|err| {
+        CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+            format!("Failed to read the backup from {}: {}", path.display(), err),
+        )
+    })?;
+    let records = backup::decode(&file)?;
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Restoring {} objects to NVM3 instance",
+        instance.label,
+        records.len()
+    );
+
+    for record in &records {
+        match record {
+            backup::BackupRecord::Data(key, data) => instance.write_data(*key, data).map(|_| ())?,
+            backup::BackupRecord::Counter(key, value) => instance.write_counter(*key, *value)?,
+        }
+    }
+    Ok(records.len())
+}
+
+/// List one page of objects. Page `page_index` holds the keys at positions
+/// `page_index * page_size` up to `(page_index + 1) * page_size` in the order the
+/// secondary enumerates them, and `more` tells whether any object follows this page.
+///
+/// The enumeration command has no offset nor continuation token, so the secondary is still
+/// asked for every key up to the end of the requested page. Only the caller's buffer is
+/// bounded by `page_size`. Walking every page therefore transfers O(n²) keys for n objects,
+/// an `ObjectCursor` or `for_each_object_key` visits all of them in a single enumeration.
+/// Objects created or deleted between two calls can shift the pages.
+pub fn list_objects_paged(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    page_size: u16,
+    page_index: u16,
+    cpc_nvm3_object_keys_ptr: &mut [cpc_nvm3_object_key_t],
+    object_count: &mut u16,
+    more: &mut bool,
+) -> Result<(), CpcNvm3Error> {
+    if page_size == 0 {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The page size must not be 0".to_string(),
         ));
+    }
+    if cpc_nvm3_object_keys_ptr.len() < page_size as usize {
+        return Err(CpcNvm3Error::BufferTooSmall {
+            needed: page_size as usize,
+            got: cpc_nvm3_object_keys_ptr.len(),
+        });
+    }
+
+    let page_start = page_index as usize * page_size as usize;
+    let page_end = page_start + page_size as usize;
+    // Ask for one more key than the page holds to know if another page follows
+    let max_key_count = match u16::try_from(page_end + 1) {
+        Ok(max_key_count) => max_key_count,
+        Err(_) => {
+            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                format!(
+                    "Page {} of {} objects is beyond the maximum number of objects",
+                    page_index, page_size
+                ),
+            ))
+        }
     };
 
-    instance.transaction_id = 0;
-    instance.maximum_write_fragment_size = None;
-    instance.maximum_write_size = None;
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Listing page {} of {} objects from NVM3 instance",
+        instance.label,
+        page_index,
+        page_size
+    );
+
+    let keys = instance.enumerate_objects(max_key_count)?;
+    let page = match keys.get(page_start..) {
+        Some(keys) => &keys[..keys.len().min(page_size as usize)],
+        None => &[],
+    };
+
+    cpc_nvm3_object_keys_ptr[..page.len()].copy_from_slice(page);
+    *object_count = page.len() as u16;
+    *more = keys.len() > page_end;
+    Ok(())
+}
+
+/// A cursor over the objects of a CPC NVM3 instance, returned by `open_object_cursor`.
+/// The first call to `next_page` enumerates every key at once, as `collect_object_keys`
+/// does, and the following calls carry on from the last key handed out. Listing n objects
+/// page by page thus transfers n keys, where `list_objects_paged` transfers O(n²) of them.
+/// Objects created or deleted after the first page are not reflected by the cursor.
+///
+/// The price is memory: every key is kept until the cursor is dropped, O(n) whatever the
+/// page size. Only the first `next_page` can fail on the link, the other pages never query
+/// the secondary.
+pub struct ObjectCursor {
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    page_size: u16,
+    keys: Option<Vec<cpc_nvm3_object_key_t>>,
+    // The number of keys handed out so far
+    position: usize,
+}
+
+impl ObjectCursor {
+    /// Fill `buffer` with the next page of object keys and return how many were written.
+    /// Once every object has been listed, this returns 0.
+    pub fn next_page(&mut self, buffer: &mut [cpc_nvm3_object_key_t]) -> Result<u16, CpcNvm3Error> {
+        if buffer.len() < self.page_size as usize {
+            return Err(CpcNvm3Error::BufferTooSmall {
+                needed: self.page_size as usize,
+                got: buffer.len(),
+            });
+        }
+
+        // Nothing is kept from a failed enumeration, the first page can be requested again
+        let keys = match self.keys.take() {
+            Some(keys) => keys,
+            None => collect_object_keys(self.cpc_nvm3_handle)?,
+        };
+        let keys = self.keys.insert(keys);
+        let remaining = &keys[self.position..];
+        let page = &remaining[..remaining.len().min(self.page_size as usize)];
+
+        buffer[..page.len()].copy_from_slice(page);
+        self.position += page.len();
+        Ok(page.len() as u16)
+    }
+
+    /// Whether another call to `next_page` can return objects.
+    pub fn has_more(&self) -> bool {
+        match &self.keys {
+            Some(keys) => self.position < keys.len(),
+            None => true,
+        }
+    }
+}
+
+pub fn open_object_cursor(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    page_size: u16,
+) -> Result<ObjectCursor, CpcNvm3Error> {
+    get_instance(cpc_nvm3_handle)?;
+
+    if page_size == 0 {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The page size must not be 0".to_string(),
+        ));
+    }
+
+    Ok(ObjectCursor {
+        cpc_nvm3_handle,
+        page_size,
+        keys: None,
+        position: 0,
+    })
+}
+
+pub fn read_data(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    buffer: &mut [u8],
+    data_size: &mut u16,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Reading data from NVM3 instance", instance.label);
+
+    let data = instance.read_data(cpc_nvm3_object_key, buffer.len() as u16)?;
+    if data.len() > buffer.len() {
+        return Err(CpcNvm3Error::BufferTooSmall {
+            needed: data.len(),
+            got: buffer.len(),
+        });
+    };
+
+    buffer[..data.len()].copy_from_slice(&data);
+    *data_size = data.len() as u16;
+
+    Ok(())
+}
+
+/// Same as `read_data`, but an object larger than `buffer` is not an error: the buffer is
+/// filled with the first bytes of the object. Returns the full size of the object, the read
+/// was truncated when it is larger than the buffer.
+///
+/// The secondary rejects a read smaller than the object, so the object size is queried
+/// first and the whole object is read.
+pub fn read_data_truncating(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    buffer: &mut [u8],
+) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Reading data from NVM3 instance, truncated to {} bytes",
+        instance.label,
+        buffer.len()
+    );
+
+    let object_size = match instance.get_object_info(cpc_nvm3_object_key)? {
+        (object_size, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA) => object_size,
+        (_, object_type) => return Err(wrong_object_type(cpc_nvm3_object_key, object_type)),
+    };
+
+    let data = instance.read_data(cpc_nvm3_object_key, object_size)?;
+    let copied_len = data.len().min(buffer.len());
+    buffer[..copied_len].copy_from_slice(&data[..copied_len]);
+    if copied_len < data.len() {
+        log::debug!(
+            "[{}] Truncated the {} bytes object {} to {} bytes",
+            instance.label,
+            data.len(),
+            cpc_nvm3_object_key,
+            copied_len
+        );
+    }
+
+    Ok(data.len() as u16)
+}
+
+/// Read several data objects in order, their data concatenated in `buffer`, while holding
+/// the instance lock. `data_size` is set to the total size read. The read stops at the first
+/// object that fails, a missing object being reported as `CpcNvm3Error::InvalidKey` with
+/// its key. An object that doesn't fit in what is left of `buffer` is reported as
+/// `CpcNvm3Error::BufferTooSmall` for the whole buffer, `needed` counting the objects
+/// read before it.
+pub fn read_concat(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_keys: &[cpc_nvm3_object_key_t],
+    buffer: &mut [u8],
+    data_size: &mut usize,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Reading {} concatenated objects from NVM3 instance",
+        instance.label,
+        cpc_nvm3_object_keys.len()
+    );
+
+    let mut offset = 0;
+    for key in cpc_nvm3_object_keys {
+        let remaining = buffer.len() - offset;
+        let data = instance
+            .read_data(*key, remaining.min(u16::MAX as usize) as u16)
+            .map_err(|err| match err {
+                CpcNvm3Error::BufferTooSmall { needed, .. } => CpcNvm3Error::BufferTooSmall {
+                    needed: offset + needed,
+                    got: buffer.len(),
+                },
+                err => err,
+            })?;
+        buffer[offset..offset + data.len()].copy_from_slice(&data);
+        offset += data.len();
+    }
+    *data_size = offset;
+
+    Ok(())
+}
+
+/// Read a data object through a short-lived instance that is initialized, opened, closed and
+/// de-initialized around the read. Every call pays for the whole handshake with the secondary,
+/// keep an instance around for anything but a single read.
+pub fn read_data_oneshot(
+    cpcd_instance_name: &str,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    buffer: &mut [u8],
+    data_size: &mut u16,
+) -> Result<(), CpcNvm3Error> {
+    let cpc_nvm3_handle = init()?;
+    let result = open(cpc_nvm3_handle, cpcd_instance_name, false)
+        .and_then(|_| read_data(cpc_nvm3_handle, cpc_nvm3_object_key, buffer, data_size));
+    // The instance is released whatever happened, the first error is the one reported
+    let cleanup = deinit_force(cpc_nvm3_handle);
+    result.and(cleanup)
+}
+
+/// Read a whole data object without guessing its size. The size is queried first and the
+/// object is read into a buffer of exactly that size, both under the same lock so the
+/// object can't be resized by another thread in between. Counters are rejected with
+/// `CPC_NVM3_WRONG_OBJECT_TYPE`, use `read_counter` instead.
+pub fn read_object(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<Vec<u8>, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Reading object from NVM3 instance", instance.label);
+
+    match instance.get_object_info(cpc_nvm3_object_key)? {
+        (object_size, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA) => {
+            instance.read_data(cpc_nvm3_object_key, object_size)
+        }
+        (_, object_type) => Err(wrong_object_type(cpc_nvm3_object_key, object_type)),
+    }
+}
+
+/// Read several data objects while holding the instance lock. Each entry pairs a key with
+/// the buffer receiving its data, and a successful result holds the size that was read.
+/// Like `write_data_batch`, the batch stops at the first failing entry.
+pub fn read_data_batch(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    entries: &mut [(cpc_nvm3_object_key_t, &mut [u8])],
+) -> Result<Vec<Result<u16, CpcNvm3Error>>, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Reading {} data objects from NVM3 instance",
+        instance.label,
+        entries.len()
+    );
+
+    let mut results = Vec::with_capacity(entries.len());
+    for (key, buffer) in entries.iter_mut() {
+        let result = instance
+            .read_data(*key, buffer.len() as u16)
+            .and_then(|data| {
+                if data.len() > buffer.len() {
+                    return Err(CpcNvm3Error::BufferTooSmall {
+                        needed: data.len(),
+                        got: buffer.len(),
+                    });
+                }
+                buffer[..data.len()].copy_from_slice(&data);
+                Ok(data.len() as u16)
+            });
+        let failed = result.is_err();
+        results.push(result);
+        if failed {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// Read a data object, transform it with `f` and write the result back, all while
+/// holding the instance lock. If the object does not exist yet, `f` receives an empty
+/// slice and the returned data is used to create it.
+///
+/// The instance lock only serializes the operations issued through this handle. Other
+/// handles, or other processes connected to the same secondary, can still modify the
+/// object between the read and the write.
+pub fn update_data<F>(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    f: F,
+) -> Result<(), CpcNvm3Error>
+where
+    F: FnOnce(&[u8]) -> Vec<u8>,
+{
+    try_update_data(cpc_nvm3_handle, cpc_nvm3_object_key, |current_data| {
+        Ok(f(current_data))
+    })
+}
+
+/// Same as `update_data`, but `f` can abort the update by returning an error,
+/// in which case nothing is written.
+pub fn try_update_data<F>(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    f: F,
+) -> Result<(), CpcNvm3Error>
+where
+    F: FnOnce(&[u8]) -> Result<Vec<u8>, CpcNvm3Error>,
+{
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Updating NVM3 data object {:?}",
+        instance.label,
+        cpc_nvm3_object_key
+    );
+
+    let maximum_write_size = instance.get_maximum_write_size()?;
+    let current_data = match instance.read_data(cpc_nvm3_object_key, maximum_write_size) {
+        Ok(data) => data,
+        Err(err) if err.code() == CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY => {
+            log::debug!(
+                "NVM3 data object {:?} does not exist, it will be created",
+                cpc_nvm3_object_key
+            );
+            vec![]
+        }
+        Err(err) => return Err(err),
+    };
+
+    let new_data = f(&current_data)?;
+    if new_data.is_empty() {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The updated data object must not be empty".to_string(),
+        ));
+    }
+
+    instance.write_data(cpc_nvm3_object_key, &new_data)?;
+    Ok(())
+}
+
+pub fn write_counter(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    value: u32,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Writing to NVM3 counter", instance.label);
+
+    instance.write_counter(cpc_nvm3_object_key, value)
+}
+
+/// Create a counter, failing with `CPC_NVM3_ALREADY_EXISTS` if an object already exists with
+/// the key. The check and the write are done under the same lock, another thread of this
+/// process can't create the object in between.
+pub fn create_counter(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    initial_value: u32,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Creating NVM3 counter", instance.label);
+
+    match instance.get_object_info(cpc_nvm3_object_key) {
+        Ok((_, object_type)) => Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_ALREADY_EXISTS,
+            format!(
+                "The object {} already exists as a {}",
+                cpc_nvm3_object_key, object_type
+            ),
+        )),
+        Err(CpcNvm3Error::InvalidKey(_)) => {
+            instance.write_counter(cpc_nvm3_object_key, initial_value)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Write an existing counter, failing with `CPC_NVM3_INVALID_OBJECT_KEY` if there is no
+/// object with the key, and with `CPC_NVM3_WRONG_OBJECT_TYPE` if the object is a data object.
+pub fn update_counter(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    value: u32,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Updating NVM3 counter", instance.label);
 
-    match CPC_NVM3_LIB_INSTANCES.lock() {
-        Ok(mut map) => {
-            map.remove(&cpc_nvm3_handle);
-            Ok(())
+    match instance.get_object_info(cpc_nvm3_object_key)? {
+        (_, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER) => {
+            instance.write_counter(cpc_nvm3_object_key, value)
         }
-        Err(err) => Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-            format!("Failed to lock NVM3 instance map. Err{}", err),
-        )),
+        (_, object_type) => Err(wrong_object_type(cpc_nvm3_object_key, object_type)),
     }
 }
 
-pub fn close(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
-    // Close the CPC endpoint
+/// Write a counter and read it back while holding the instance lock, returning the value
+/// stored on the secondary.
+pub fn write_counter_readback(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    value: u32,
+) -> Result<u32, CpcNvm3Error> {
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance = instance_arc_mutex.lock().map_err(|err| {
-        CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, format!("{}", err))
-    })?;
-    instance.close()?;
-    instance.cpc_endpoint = None;
-    Ok(())
-}
-
-pub fn get_object_count(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u16, CpcNvm3Error> {
-    log::debug!("Getting objects count from NVM3 instance");
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
+    log::debug!(
+        "[{}] Writing to NVM3 counter and reading it back",
+        instance.label
+    );
 
-    let get_object_count_command =
-        CmdGetObjectCount::new(instance.unique_id, &mut instance.transaction_id);
-    let write_data = get_object_count_command.serialize()?;
-    instance.write(&write_data)?;
+    instance.write_counter(cpc_nvm3_object_key, value)?;
+    let stored_value = instance.read_counter(cpc_nvm3_object_key)?;
+    if stored_value != value {
+        log::warn!(
+            "NVM3 counter {:?} holds {} after writing {}",
+            cpc_nvm3_object_key,
+            stored_value,
+            value
+        );
+    }
+    Ok(stored_value)
+}
 
-    let response = instance.get_response(&get_object_count_command)?;
+fn process_read_counter_response(
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    response: CmdCounterValueResponse,
+) -> Result<u32, CpcNvm3Error> {
+    // Response can either be an error (StatusIs) or a success with the data
     match response {
-        CmdGetObjectCountResponse::StatusCode(status_code) => match status_code {
+        CmdCounterValueResponse::Data(data) => Ok(data),
+        CmdCounterValueResponse::StatusCode(status_code) => match status_code {
             StatusCode::SlStatus(sl_status) => match sl_status {
-                SlStatus::Ok | SlStatus::Fail | SlStatus::Busy | SlStatus::Unknown => {
+                SlStatus::Ok | SlStatus::Fail | SlStatus::Unknown | SlStatus::Busy => {
                     Err(CpcNvm3Error::ErrorCodeWithContext(
                         CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
                         format!("Received an unexpected sl_status code {}", status_code),
@@ -966,14 +3714,8 @@ pub fn get_object_count(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u16, CpcNv
             },
 
             StatusCode::ECode(e_code) => match e_code {
-                ECode::KeyNotFound => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
-                    format!("{}", status_code),
-                )),
-                _ => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    format!("Get object count failed with status code: {}", status_code),
-                )),
+                ECode::KeyNotFound => Err(CpcNvm3Error::InvalidKey(cpc_nvm3_object_key)),
+                _ => Err(e_code.into()),
             },
 
             StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
@@ -981,525 +3723,777 @@ pub fn get_object_count(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u16, CpcNv
                 format!("Unknown response type received"),
             )),
         },
-        CmdGetObjectCountResponse::ObjectCount { object_count } => Ok(object_count),
     }
 }
 
-pub fn extract_object_keys(input: &[u8]) -> nom::IResult<&[u8], Vec<cpc_nvm3_object_key_t>> {
-    many0(le_u32)(input)
+pub fn read_counter(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<u32, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Reading counter from NVM3 instance", instance.label);
+
+    instance.read_counter(cpc_nvm3_object_key)
 }
 
-pub fn list_objects(
+/// Same as `read_counter`, but the value is interpreted as a two's complement signed integer.
+pub fn read_counter_signed(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpc_nvm3_object_keys_ptr: &mut [cpc_nvm3_object_key_t],
-    object_count: &mut u16,
-) -> Result<(), CpcNvm3Error> {
-    log::debug!("Listing objects from NVM3 instance");
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<i32, CpcNvm3Error> {
+    Ok(read_counter(cpc_nvm3_handle, cpc_nvm3_object_key)? as i32)
+}
 
+/// Read a 4 bytes data object as a little endian `u32`, for the values stored as data
+/// objects rather than counters. An object of any other size is rejected with
+/// `CPC_NVM3_INVALID_ARG`.
+pub fn read_u32_data(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<u32, CpcNvm3Error> {
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance = instance_arc_mutex.lock().map_err(|err| {
-        CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, format!("{}", err))
-    })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
     log::debug!(
-        "Sending object enumeration request with a limit of {} objects",
-        cpc_nvm3_object_keys_ptr.len()
+        "[{}] Reading a 4 bytes data object from NVM3 instance",
+        instance.label
     );
-    let mut enumerate_objects_command = CmdEnumerateObjects::new(
-        instance.unique_id,
-        &mut instance.transaction_id,
-        cpc_nvm3_object_keys_ptr.len() as u16,
+
+    match instance.get_object_info(cpc_nvm3_object_key)? {
+        (_, object_type @ CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER) => {
+            return Err(wrong_object_type(cpc_nvm3_object_key, object_type))
+        }
+        (object_size, _) if object_size as usize != std::mem::size_of::<u32>() => {
+            return Err(CpcNvm3Error::ErrorCodeWithContext(
+                CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                format!(
+                    "The object {} is {} bytes, expected {} bytes",
+                    cpc_nvm3_object_key,
+                    object_size,
+                    std::mem::size_of::<u32>()
+                ),
+            ))
+        }
+        _ => {}
+    }
+
+    let data = instance.read_data(cpc_nvm3_object_key, std::mem::size_of::<u32>() as u16)?;
+    match <[u8; 4]>::try_from(data.as_slice()) {
+        Ok(bytes) => Ok(u32::from_le_bytes(bytes)),
+        Err(_) => Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+            format!(
+                "Read {} bytes from the object {}, expected {} bytes",
+                data.len(),
+                cpc_nvm3_object_key,
+                std::mem::size_of::<u32>()
+            ),
+        )),
+    }
+}
+
+/// Read several counters while holding the instance lock. Each key gets its own result,
+/// so a missing counter, or an object that is not a counter, doesn't prevent the others from
+/// being read. Any other error, e.g. a lost connection, aborts the whole read.
+pub fn read_counters(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_keys: &[cpc_nvm3_object_key_t],
+) -> Result<Vec<(cpc_nvm3_object_key_t, Result<u32, CpcNvm3Error>)>, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Reading {} counters from NVM3 instance",
+        instance.label,
+        cpc_nvm3_object_keys.len()
     );
 
-    instance.write(&enumerate_objects_command.serialize()?)?;
+    let mut results = Vec::with_capacity(cpc_nvm3_object_keys.len());
+    for key in cpc_nvm3_object_keys {
+        match instance.read_counter(*key) {
+            Err(err) if !err.is_object_error() => return Err(err),
+            result => results.push((*key, result)),
+        }
+    }
+    Ok(results)
+}
 
-    let mut continue_reading = true;
-    let mut data = vec![];
+pub fn increment_counter(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<u32, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    while continue_reading {
-        let response = instance.get_response(&enumerate_objects_command)?;
+    log::debug!("[{}] Incrementing NVM3 counter", instance.label);
 
-        // Response can either be an error (StatusIs) or a success with the data
-        let received_data = match response {
-            CmdEnumerateObjectsResponse::Data(segment, last_fragment) => {
-                continue_reading = !last_fragment;
-                if !last_fragment {
-                    log::debug!(
-                          "Received {} bytes. Another fragment is available, fetching object list again",
-                          segment.len()
-                      );
-                }
-                Ok(segment)
-            }
-            CmdEnumerateObjectsResponse::StatusCode(status_code) => match status_code {
-                StatusCode::SlStatus(sl_status) => match sl_status {
-                    SlStatus::Ok | SlStatus::Fail | SlStatus::Unknown => {
-                        Err(CpcNvm3Error::ErrorCodeWithContext(
-                            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                            format!("Received an unexpected sl_status code {}", status_code),
-                        ))
-                    }
-                    SlStatus::Busy => {
-                        return Err(CpcNvm3Error::ErrorCodeWithContext(
-                            CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
-                            "NVM3 is busy with another operation, try again".to_string(),
-                        ))
-                    }
-                },
+    instance.increment_counter(cpc_nvm3_object_key)
+}
 
-                StatusCode::ECode(e_code) => match e_code {
-                    _ => Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                        format!("list_objects failed with status code: {}", status_code),
-                    )),
-                },
+/// Same as `increment_counter`, but returns the value of the counter before the increment
+/// along with the new value. The secondary increments counters by exactly one, so the
+/// previous value is derived from the new one without an additional read. A counter that
+/// wrapped reports a new value of 0 and a previous value of `u32::MAX`.
+pub fn increment_counter_ex(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<(u32, u32), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-                StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                    format!("Unknown response type received"),
-                )),
-            },
-        }?;
-        data.extend(received_data);
+    log::debug!("[{}] Incrementing NVM3 counter", instance.label);
+
+    let new_value = instance.increment_counter(cpc_nvm3_object_key)?;
+    Ok((new_value.wrapping_sub(1), new_value))
+}
+
+pub fn get_maximum_write_size(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching NVM3 maximum write size", instance.label);
+
+    Ok(instance.get_maximum_write_size()?)
+}
+
+/// Get the maximum size of an NVM3 object, along with whether it is an approximation. A
+/// secondary that does not report its maximum object size is assumed to accept objects as
+/// large as a single write, `true` is returned then.
+pub fn get_maximum_object_size(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+) -> Result<(u16, bool), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching NVM3 maximum object size", instance.label);
+
+    Ok((
+        instance.get_maximum_object_size()?,
+        instance.maximum_object_size_approximate,
+    ))
+}
+
+/// Get the largest chunk of data a single write command carries, larger writes are split in
+/// as many commands as needed.
+pub fn get_maximum_write_fragment_size(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Fetching NVM3 maximum write fragment size",
+        instance.label
+    );
+
+    instance.get_maximum_write_fragment_size()
+}
+
+pub fn get_cpc_max_write_size(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching CPC maximum write size", instance.label);
+
+    Ok(instance.get_cpc_max_write_size()?)
+}
+
+pub fn get_features(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u32, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching supported features", instance.label);
+
+    Ok(instance.get_features()?)
+}
+
+pub fn get_limits(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<CpcNvm3Limits, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching limits", instance.label);
+
+    let max_write_size = instance.get_maximum_write_size()?;
+    Ok(CpcNvm3Limits {
+        max_write_size,
+        max_object_size: instance.get_maximum_object_size()?,
+        max_fragment_size: instance.get_maximum_write_fragment_size()?,
+        cpc_max_write: instance.get_cpc_max_write_size()?,
+    })
+}
+
+/// Get everything learned about the secondary while opening the instance. Unlike the
+/// individual getters, this fails with `CPC_NVM3_NOT_OPEN` once the instance is closed.
+pub fn get_session_info(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+) -> Result<CpcNvm3SessionInfo, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching session info", instance.label);
+
+    if instance.cpc_endpoint.is_none() {
+        return Err(CpcNvm3Error::NotOpen);
     }
-    if data.len() > cpc_nvm3_object_keys_ptr.len() * CPC_NVM3_OBJECT_KEY_SIZE {
-        return Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL,
-            "list_objects failed, provided buffer is too small".to_string(),
-        ));
+    let secondary_version = instance.secondary_version.ok_or(CpcNvm3Error::NotOpen)?;
+    let cpcd_instance_name = instance
+        .cpcd_instance_name
+        .clone()
+        .ok_or(CpcNvm3Error::NotOpen)?;
+
+    let mut session_info = CpcNvm3SessionInfo {
+        secondary_version,
+        limits: CpcNvm3Limits {
+            max_write_size: instance.get_maximum_write_size()?,
+            max_object_size: instance.get_maximum_object_size()?,
+            max_fragment_size: instance.get_maximum_write_fragment_size()?,
+            cpc_max_write: instance.get_cpc_max_write_size()?,
+        },
+        features: instance.get_features()?,
+        cpcd_instance_name: [0; crate::CPC_NVM3_SESSION_INSTANCE_NAME_SIZE],
     };
 
-    let num_objects = data.len() / CPC_NVM3_OBJECT_KEY_SIZE;
-    if num_objects * CPC_NVM3_OBJECT_KEY_SIZE != data.len() {
-        return Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-            "The data length is not a multiple of the object size".to_string(),
-        ));
+    // The name is NUL-terminated, a longer name is truncated to fit
+    let name_len = cpcd_instance_name
+        .len()
+        .min(crate::CPC_NVM3_SESSION_INSTANCE_NAME_SIZE - 1);
+    if name_len < cpcd_instance_name.len() {
+        log::warn!(
+            "[{}] The cpcd instance name is truncated to {} bytes in the session info",
+            instance.label,
+            name_len
+        );
+    }
+    for (dst, src) in session_info
+        .cpcd_instance_name
+        .iter_mut()
+        .zip(&cpcd_instance_name.as_bytes()[..name_len])
+    {
+        *dst = *src as std::ffi::c_char;
     }
 
-    match extract_object_keys(&data) {
-        Ok((remaining, keys)) => {
-            if keys.len() != num_objects || remaining.len() != 0 {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    "Number of deserialized keys doesn't match the expected number.".to_string(),
-                ));
-            }
-            cpc_nvm3_object_keys_ptr.copy_from_slice(&keys);
-        }
-        Err(e) => {
-            return Err(CpcNvm3Error::ErrorCodeWithContext(
+    Ok(session_info)
+}
+
+/// Get the last status received from the secondary as `(response_type, value)`, where the
+/// response type is 0 for an sl_status and 1 for an ecode. Both are the raw values from the
+/// wire, so statuses unknown to this library are reported as well.
+pub fn get_last_status(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(u8, u32), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching last status", instance.label);
+
+    instance.last_status.ok_or_else(|| {
+        CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+            "No status was received from the secondary yet".to_string(),
+        )
+    })
+}
+
+/// Get the bytes of protocol overhead in each write and read data frame, header included.
+/// A write fragment carries at most the CPC maximum write size minus the write overhead. The
+/// fragment size chosen at open leaves a larger margin, so a full fragment doesn't always fill
+/// the CPC frame.
+pub fn get_protocol_overhead() -> (u16, u16) {
+    (
+        protocol::CmdWriteData::base_size(),
+        protocol::CmdReadDataIs::get_overhead(),
+    )
+}
+
+/// Get the number of times the instance reconnected to libcpc after losing the connection,
+/// and whether the last operation was the one to trigger a reconnection. Such an operation
+/// fails with `CPC_NVM3_TRY_AGAIN`.
+pub fn get_link_stats(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(u32, bool), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching link stats", instance.label);
+
+    Ok((instance.reconnect_count, instance.last_op_reconnected))
+}
+
+/// Get the rolling average of the round trip of the commands, from the write of a request to
+/// the reception of its response. Each command weighs for 1/8 of the average, the handshake
+/// commands included. The average is kept until the instance is de-initialized.
+pub fn get_round_trip_average(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+) -> Result<Duration, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching round trip average", instance.label);
+
+    instance
+        .round_trip_average_us
+        .map(Duration::from_micros)
+        .ok_or_else(|| {
+            CpcNvm3Error::ErrorCodeWithContext(
                 CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("Failed to deserialize keys: {:?}", e),
-            ));
-        }
-    }
+                "No response was received from the secondary yet".to_string(),
+            )
+        })
+}
 
-    *object_count = (data.len() / CPC_NVM3_OBJECT_KEY_SIZE) as u16;
-    Ok(())
+pub fn get_object_info(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<(u16, CpcNvm3ObjectType), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching NVM3 object info", instance.label);
+
+    instance.get_object_info(cpc_nvm3_object_key)
 }
 
-pub fn read_data(
+/// Same as `get_object_info`, but an object type the library doesn't know is reported as a
+/// protocol error rather than as `CPC_NVM3_OBJECT_TYPE_UNKNOWN`, so the returned type is
+/// always a data object or a counter.
+pub fn get_object_info_strict(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
     cpc_nvm3_object_key: cpc_nvm3_object_key_t,
-    buffer: &mut [u8],
-    data_size: &mut u16,
-) -> Result<(), CpcNvm3Error> {
-    log::debug!("Reading data from NVM3 instance");
+) -> Result<(u16, CpcNvm3ObjectType), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Fetching NVM3 object info", instance.label);
+
+    instance.get_object_info_strict(cpc_nvm3_object_key)
+}
 
+/// Get the size of a data object, to size the buffer of `read_data` exactly. A counter is
+/// reported like a missing key, with `CPC_NVM3_INVALID_OBJECT_KEY`, since it can't be read
+/// as data.
+pub fn get_object_size(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<u16, CpcNvm3Error> {
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance = instance_arc_mutex.lock().map_err(|err| {
-        CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, format!("{}", err))
-    })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    let mut read_command = CmdReadData::new(
-        instance.unique_id,
-        &mut instance.transaction_id,
-        cpc_nvm3_object_key,
-        buffer.len() as u16,
-    );
+    log::debug!("[{}] Fetching NVM3 object size", instance.label);
 
-    instance.write(&read_command.serialize()?)?;
+    match instance.get_object_info(cpc_nvm3_object_key)? {
+        (object_size, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA) => Ok(object_size),
+        _ => Err(CpcNvm3Error::InvalidKey(cpc_nvm3_object_key)),
+    }
+}
 
-    let mut continue_reading = true;
-    let mut data = vec![];
+pub fn delete_object(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    while continue_reading {
-        let response = instance.get_response(&read_command)?;
+    log::debug!(
+        "[{}] Deleting NVM3 object #{:?}",
+        instance.label,
+        cpc_nvm3_object_key
+    );
 
-        // Response can either be an error (StatusIs) or a success with the data
-        let received_data = match response {
-            CmdReadDataResponse::Data(segment, last_fragment) => {
-                continue_reading = !last_fragment;
-                if !last_fragment {
-                    log::debug!(
-                        "Received {} bytes. Another fragment is available, reading again",
-                        segment.len()
-                    );
-                }
-                Ok(segment)
-            }
-            CmdReadDataResponse::StatusCode(status_code) => match status_code {
-                StatusCode::SlStatus(sl_status) => match sl_status {
-                    SlStatus::Ok | SlStatus::Fail | SlStatus::Unknown => {
-                        Err(CpcNvm3Error::ErrorCodeWithContext(
-                            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                            format!("Received an unexpected sl_status code {}", status_code),
-                        ))
-                    }
-                    SlStatus::Busy => {
-                        return Err(CpcNvm3Error::ErrorCodeWithContext(
-                            CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
-                            "NVM3 is busy with another operation, try again".to_string(),
-                        ))
-                    }
-                },
+    instance.delete_object(cpc_nvm3_object_key)
+}
 
-                StatusCode::ECode(e_code) => match e_code {
-                    ECode::KeyNotFound => Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
-                        format!("{}", status_code),
-                    )),
-                    ECode::ReadDataSize => Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL,
-                        format!("{}", status_code),
-                    )),
-                    ECode::SizeTooSmall => Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL,
-                        format!("{}", status_code),
-                    )),
-                    _ => Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                        format!("Read failed with status code: {}", status_code),
-                    )),
-                },
+/// Delete the objects of the instance, at most `max_deletions` of them (0 means no limit),
+/// and return how many were deleted. `more_remaining` tells whether objects are left, so
+/// the caller can delete in bounded batches and release the instance lock between them.
+pub fn delete_all_objects(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    max_deletions: u16,
+    more_remaining: &mut bool,
+) -> Result<u16, CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-                StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                    format!("Unknown response type received"),
-                )),
-            },
-        }?;
-        data.extend(received_data);
+    log::debug!(
+        "[{}] Deleting up to {} NVM3 objects (0 is unlimited)",
+        instance.label,
+        max_deletions
+    );
+
+    let deletion_limit = match max_deletions {
+        0 => instance.get_object_count()?,
+        max_deletions => max_deletions,
+    };
+
+    *more_remaining = false;
+    if deletion_limit == 0 {
+        return Ok(0);
     }
-    if data.len() > buffer.len() {
+
+    // Ask for one more key than we delete to know if objects remain afterwards
+    let keys = instance.enumerate_objects(deletion_limit.saturating_add(1))?;
+    let mut deleted_count: u16 = 0;
+    for key in keys.iter().take(deletion_limit as usize) {
+        instance.delete_object(*key)?;
+        deleted_count += 1;
+    }
+
+    *more_remaining = keys.len() > deleted_count as usize;
+    log::debug!(
+        "Deleted {} NVM3 objects, more remaining: {}",
+        deleted_count,
+        *more_remaining
+    );
+    Ok(deleted_count)
+}
+
+/// Delete every object of the type `object_type` while holding the instance lock, and return
+/// how many were deleted. The type of each key is queried before deleting it, objects of the
+/// other type are left untouched. A key that vanishes before it is checked or deleted, e.g.
+/// deleted by another host, is skipped.
+pub fn delete_objects_by_type(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    object_type: CpcNvm3ObjectType,
+) -> Result<u16, CpcNvm3Error> {
+    if object_type == CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN {
         return Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL,
-            "Read failed, provided buffer is too small".to_string(),
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "Only data objects or counters can be deleted by type".to_string(),
         ));
-    };
+    }
 
-    buffer[..data.len()].copy_from_slice(&data);
-    *data_size = data.len() as u16;
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    Ok(())
+    log::debug!(
+        "[{}] Deleting every {} of NVM3 instance",
+        instance.label,
+        object_type
+    );
+
+    let mut deleted_count: u16 = 0;
+    for key in instance.collect_object_keys()? {
+        let result = match instance.get_object_info(key) {
+            Ok((_, key_type)) if key_type == object_type => instance.delete_object(key),
+            Ok(_) => continue,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(_) => deleted_count += 1,
+            Err(CpcNvm3Error::InvalidKey(_)) => {
+                log::debug!("NVM3 object {:?} vanished before it was deleted", key)
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    log::debug!("Deleted {} NVM3 objects", deleted_count);
+    Ok(deleted_count)
 }
 
-pub fn write_counter(
+/// Move a data object to another key. The object is read, written to `to_key`, read back
+/// to verify it, and only then deleted from `from_key`, all while holding the instance lock.
+/// If any step before the deletion fails, the original object is left untouched.
+///
+/// If `to_key` already exists, `CPC_NVM3_INVALID_ARG` is returned unless `overwrite` is set.
+pub fn move_object(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
-    value: u32,
+    from_key: cpc_nvm3_object_key_t,
+    to_key: cpc_nvm3_object_key_t,
+    overwrite: bool,
 ) -> Result<(), CpcNvm3Error> {
-    log::debug!("Writing to NVM3 counter");
+    if from_key == to_key {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "The source and destination keys must be different".to_string(),
+        ));
+    }
 
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    let write_counter_command = CmdWriteCounter::new(
-        instance.unique_id,
-        &mut instance.transaction_id,
-        cpc_nvm3_object_key,
-        value,
+    log::debug!(
+        "[{}] Moving NVM3 object #{:?} to #{:?}",
+        instance.label,
+        from_key,
+        to_key
     );
-    let write_data = write_counter_command.serialize()?;
-    instance.write(&write_data)?;
-    let response = instance.get_response(&write_counter_command)?;
 
-    match response {
-        StatusCode::SlStatus(sl_status) => match sl_status {
-            SlStatus::Ok => log::debug!("Received write counter acknowledgement"),
-            SlStatus::Fail => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    "Writing counter to NVM3 instance failed".to_string(),
-                ))
-            }
-            SlStatus::Unknown | SlStatus::Busy => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    format!("Received an unexpected sl_status code {}", sl_status),
-                ))
-            }
-        },
-        StatusCode::ECode(ecode) => match ecode {
-            ECode::KeyInvalid => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
-                    format!("{}", ecode.to_string()),
-                ))
-            }
-            _ => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                    format!("{}", ecode.to_string()),
-                ))
-            }
-        },
-        StatusCode::Unknown => {
+    let (object_size, object_type) = instance.get_object_info(from_key)?;
+    if object_type != CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA {
+        return Err(wrong_object_type(from_key, object_type));
+    }
+
+    match instance.get_object_info(to_key) {
+        Ok(_) if !overwrite => {
             return Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                format!("Unknown response type received"),
+                CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+                format!("NVM3 object #{:?} already exists", to_key),
             ))
         }
+        Ok(_) => log::debug!("NVM3 object #{:?} will be overwritten", to_key),
+        Err(err) if err.code() == CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY => {}
+        Err(err) => return Err(err),
     }
-    Ok(())
-}
-
-fn process_read_counter_response(response: CmdCounterValueResponse) -> Result<u32, CpcNvm3Error> {
-    // Response can either be an error (StatusIs) or a success with the data
-    match response {
-        CmdCounterValueResponse::Data(data) => Ok(data),
-        CmdCounterValueResponse::StatusCode(status_code) => match status_code {
-            StatusCode::SlStatus(sl_status) => match sl_status {
-                SlStatus::Ok | SlStatus::Fail | SlStatus::Unknown | SlStatus::Busy => {
-                    Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                        format!("Received an unexpected sl_status code {}", status_code),
-                    ))
-                }
-            },
 
-            StatusCode::ECode(e_code) => match e_code {
-                ECode::KeyNotFound => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
-                    format!("{}", status_code),
-                )),
-                _ => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    format!("Read failed with status code: {}", status_code),
-                )),
-            },
+    let data = instance.read_data(from_key, object_size)?;
+    instance.write_data(to_key, &data)?;
 
-            StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                format!("Unknown response type received"),
-            )),
-        },
+    let written_data = instance.read_data(to_key, object_size)?;
+    if written_data != data {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+            format!(
+                "Verification of NVM3 object #{:?} failed, #{:?} was not deleted",
+                to_key, from_key
+            ),
+        ));
     }
+
+    instance.delete_object(from_key)
 }
 
-pub fn read_counter(
+/// Set how many responses that don't match the command in flight an operation drops before
+/// giving up with `CPC_NVM3_FAILURE`. Such responses are late answers to timed out commands
+/// or frames meant for another host sharing CPCd. The default is 32.
+pub fn set_max_mismatched_responses(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
-) -> Result<u32, CpcNvm3Error> {
-    log::debug!("Reading counter from NVM3 instance");
-
+    max_mismatched_responses: u16,
+) -> Result<(), CpcNvm3Error> {
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance = instance_arc_mutex.lock().map_err(|err| {
-        CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, format!("{}", err))
-    })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    let read_counter_command = CmdReadCounter::new(
-        instance.unique_id,
-        &mut instance.transaction_id,
-        cpc_nvm3_object_key,
+    log::debug!(
+        "[{}] Setting the maximum number of mismatched responses to {}",
+        instance.label,
+        max_mismatched_responses
     );
-    instance.write(&read_counter_command.serialize()?)?;
-    let response = instance.get_response(&read_counter_command)?;
 
-    Ok(process_read_counter_response(response)?)
+    instance.max_mismatched_responses = max_mismatched_responses;
+    Ok(())
 }
 
-pub fn increment_counter(
+/// Set after how many consecutive responses with an unexpected transaction ID the instance
+/// is considered out of sync with the secondary, 0 disables the detection. The default is 8.
+/// A warning is always logged once the threshold is reached. With `reconnect` the endpoint
+/// is also reopened and the operation fails with `CPC_NVM3_TRY_AGAIN`, or with
+/// `CPC_NVM3_CONNECTION_LOST` when the automatic reconnection is disabled. Otherwise the
+/// operation keeps dropping responses until `set_max_mismatched_responses` is exceeded.
+pub fn set_desync_detection(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
-) -> Result<u32, CpcNvm3Error> {
-    log::debug!("Incrementing NVM3 counter");
-
+    threshold: u16,
+    reconnect: bool,
+) -> Result<(), CpcNvm3Error> {
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    let increment_counter_command = CmdIncrementCounter::new(
-        instance.unique_id,
-        &mut instance.transaction_id,
-        cpc_nvm3_object_key,
+    log::debug!(
+        "[{}] Setting the desynchronization threshold to {}, reconnect: {}",
+        instance.label,
+        threshold,
+        reconnect
     );
-    let write_data = increment_counter_command.serialize()?;
-    instance.write(&write_data)?;
-    let response = instance.get_response(&increment_counter_command)?;
-    Ok(process_read_counter_response(response)?)
-}
 
-pub fn get_maximum_write_size(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<u16, CpcNvm3Error> {
-    log::debug!("Fetching NVM3 maximum write size");
+    instance.desync_threshold = threshold;
+    instance.reconnect_on_desync = reconnect;
+    Ok(())
+}
 
+/// Reject the responses with a fixed layout, such as a counter value or an object info, that
+/// carry bytes past their last field. Such bytes are ignored by default, so that a newer
+/// firmware appending fields to a response keeps working, but they can also be the sign of a
+/// corrupted frame. A rejected response fails the operation with `CPC_NVM3_FAILURE`.
+pub fn set_strict_parsing(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    strict_parsing: bool,
+) -> Result<(), CpcNvm3Error> {
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    Ok(instance.get_maximum_write_size()?)
+    log::debug!(
+        "[{}] Setting strict parsing to {}",
+        instance.label,
+        strict_parsing
+    );
+
+    instance.strict_parsing = strict_parsing;
+    Ok(())
 }
 
-pub fn get_object_info(
+/// Set the most bytes the fragments of a single read or enumeration response can add up to.
+/// An operation receiving more fails with `CPC_NVM3_FAILURE`. With 0, the default, reads are
+/// bounded by the maximum object size and enumerations by 65535 keys.
+pub fn set_max_reassembly(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
-) -> Result<(u16, CpcNvm3ObjectType), CpcNvm3Error> {
-    log::debug!("Fetching NVM3 object info");
+    max_reassembly: u32,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!(
+        "[{}] Setting the maximum reassembly size to {} bytes",
+        instance.label,
+        max_reassembly
+    );
 
+    instance.max_reassembly = max_reassembly;
+    Ok(())
+}
+
+/// Reconnect to the CPC endpoint when the connection is lost, the default, in which case the
+/// operation that lost the connection fails with `CPC_NVM3_TRY_AGAIN`. Without the automatic
+/// reconnection it fails with `CPC_NVM3_CONNECTION_LOST` instead, and restoring the
+/// connection, by closing and opening the instance, is left to the caller. This also covers
+/// an endpoint lost by an earlier operation and the reconnection of `set_desync_detection`.
+pub fn set_auto_reconnect(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    auto_reconnect: bool,
+) -> Result<(), CpcNvm3Error> {
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    let get_object_info_command = CmdGetObjectInfo::new(
-        instance.unique_id,
-        &mut instance.transaction_id,
-        cpc_nvm3_object_key,
+    log::debug!(
+        "[{}] Setting automatic reconnection to {}",
+        instance.label,
+        auto_reconnect
     );
-    let write_data = get_object_info_command.serialize()?;
-    instance.write(&write_data)?;
 
-    let response = instance.get_response(&get_object_info_command)?;
-    match response {
-        CmdGetObjectInfoResponse::StatusCode(status_code) => match status_code {
-            StatusCode::SlStatus(sl_status) => match sl_status {
-                SlStatus::Ok | SlStatus::Fail | SlStatus::Busy | SlStatus::Unknown => {
-                    Err(CpcNvm3Error::ErrorCodeWithContext(
-                        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                        format!("Received an unexpected sl_status code {}", status_code),
-                    ))
-                }
-            },
+    instance.auto_reconnect = auto_reconnect;
+    Ok(())
+}
 
-            StatusCode::ECode(e_code) => match e_code {
-                ECode::KeyNotFound => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
-                    format!("{}", status_code),
-                )),
-                _ => Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    format!("Read failed with status code: {}", status_code),
-                )),
-            },
+/// Make the reads from the CPC endpoint return immediately when no response is available,
+/// in which case the operation fails with `CPC_NVM3_TRY_AGAIN` instead of waiting for the
+/// read timeout.
+///
+/// The command of an operation that fails this way was already sent to the secondary. Issue
+/// the same operation again to collect its response: as long as it is the next request, the
+/// command is not sent again. Any other operation sends its own command, the response of
+/// the pending one is then dropped. An operation that already received part of a fragmented
+/// response can't be resumed, it has to be issued again.
+pub fn set_nonblocking(
+    cpc_nvm3_handle: cpc_nvm3_handle_t,
+    nonblocking: bool,
+) -> Result<(), CpcNvm3Error> {
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-            StatusCode::Unknown => Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                format!("Unknown response type received"),
-            )),
-        },
-        CmdGetObjectInfoResponse::ObjectInfo {
-            object_type,
-            object_size,
-        } => Ok((object_size, object_type)),
+    log::debug!(
+        "[{}] Setting non-blocking reads to {}",
+        instance.label,
+        nonblocking
+    );
+
+    instance.nonblocking = nonblocking;
+    Ok(())
+}
+
+fn check_timeout_range(seconds: i32, microseconds: i32) -> Result<(), CpcNvm3Error> {
+    if seconds < 0 || !(0..1_000_000).contains(&microseconds) {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            format!(
+                "Invalid timeout of {} seconds and {} microseconds. The seconds must not be negative and the microseconds must be within [0, 999999]",
+                seconds, microseconds
+            ),
+        ));
     }
+    Ok(())
 }
 
-pub fn delete_object(
+/// Set the read timeout applied while waiting for the response of the command `command_id`,
+/// one of the `CPC_NVM3_COMMAND_*` values. The read timeout of the endpoint is restored once
+/// the response is received. A zero timeout removes the one of the command, which then uses
+/// the read timeout of the endpoint like any command without a timeout of its own.
+///
+/// The enumeration of the objects waits 15 seconds by default.
+pub fn set_command_timeout(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
-    cpc_nvm3_object_key: cpc_nvm3_object_key_t,
+    command_id: u8,
+    seconds: i32,
+    microseconds: i32,
 ) -> Result<(), CpcNvm3Error> {
-    log::debug!("Deleting NVM3 object #{:?}", cpc_nvm3_object_key);
+    if !protocol::is_host_command(command_id) {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            format!("{:#04x} is not a command ID", command_id),
+        ));
+    }
+    check_timeout_range(seconds, microseconds)?;
 
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let mut instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
+    let mut instance = lock_instance(&instance_arc_mutex)?;
 
-    let delete_object_command = CmdDeleteObject::new(
-        instance.unique_id,
-        &mut instance.transaction_id,
-        cpc_nvm3_object_key,
+    log::debug!(
+        "[{}] Configuring the timeout of command {:#04x} to {} seconds and {} microseconds",
+        instance.label,
+        command_id,
+        seconds,
+        microseconds
     );
-    let write_data = delete_object_command.serialize()?;
-    instance.write(&write_data)?;
-
-    let parsed_response = instance.get_response(&delete_object_command)?;
-    match parsed_response {
-        StatusCode::SlStatus(sl_status) => match sl_status {
-            SlStatus::Ok => log::debug!("Received delete object acknowledgement"),
-            SlStatus::Fail => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    "Deletion of NVM3 object failed".to_string(),
-                ))
-            }
-            SlStatus::Unknown | SlStatus::Busy => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                    format!("Received an unexpected sl_status code {}", sl_status),
-                ))
-            }
-        },
-        StatusCode::ECode(ecode) => match ecode {
-            ECode::KeyInvalid | ECode::KeyNotFound => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
-                    format!("{}", ecode.to_string()),
-                ))
-            }
-            _ => {
-                return Err(CpcNvm3Error::ErrorCodeWithContext(
-                    CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                    format!("{}", ecode.to_string()),
-                ))
-            }
-        },
-        StatusCode::Unknown => {
-            return Err(CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR,
-                format!("Unknown response type received"),
-            ))
-        }
+
+    let timeout = Duration::from_secs(seconds as u64) + Duration::from_micros(microseconds as u64);
+    if timeout.is_zero() {
+        instance.command_timeouts.remove(&command_id);
+    } else {
+        instance.command_timeouts.insert(command_id, timeout);
+    }
+    Ok(())
+}
+
+// The read timeout of an endpoint can't be zero, libcpc then blocks indefinitely
+fn check_read_timeout(seconds: i32, microseconds: i32) -> Result<(), CpcNvm3Error> {
+    check_timeout_range(seconds, microseconds)?;
+    if seconds == 0 && microseconds == 0 {
+        return Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            "A zero timeout blocks indefinitely, use non-blocking reads instead".to_string(),
+        ));
     }
+    Ok(())
+}
 
+fn default_read_timeout() -> Duration {
+    *CPC_NVM3_DEFAULT_READ_TIMEOUT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Set the read timeout given to the CPC endpoint of the instances opened from now on,
+/// 5 seconds by default. The instances already opened keep their timeout until they
+/// reconnect, use `set_timeout` to change it. The ranges are the same as for `set_timeout`.
+pub fn set_default_read_timeout(seconds: i32, microseconds: i32) -> Result<(), CpcNvm3Error> {
+    check_read_timeout(seconds, microseconds)?;
+
+    log::debug!(
+        "Configuring the default read timeout to {} seconds and {} microseconds",
+        seconds,
+        microseconds
+    );
+    *CPC_NVM3_DEFAULT_READ_TIMEOUT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) =
+        Duration::from_secs(seconds as u64) + Duration::from_micros(microseconds as u64);
     Ok(())
 }
 
+/// Set the read timeout of the CPC endpoint. `seconds` must not be negative and
+/// `microseconds` must be below one second. A zero timeout is rejected as well since libcpc
+/// then blocks indefinitely, use `set_nonblocking` to fail fast instead.
 pub fn set_timeout(
     cpc_nvm3_handle: cpc_nvm3_handle_t,
     seconds: i32,
     microseconds: i32,
 ) -> Result<(), CpcNvm3Error> {
+    check_read_timeout(seconds, microseconds)?;
+
+    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
     log::debug!(
-        "Configuring blocking timeout to {} seconds and {} microseconds",
+        "[{}] Configuring blocking timeout to {} seconds and {} microseconds",
+        instance.label,
         seconds,
         microseconds
     );
 
-    let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
-
     let set_timeout = libcpc::cpc_timeval_t {
         seconds: seconds,
         microseconds: microseconds,
@@ -1510,24 +4504,15 @@ pub fn set_timeout(
             endpoint.set_read_timeout(set_timeout)?;
             Ok(())
         }
-        None => Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-            format!("CPC Write failed. The CPC is not initialized. Call cpc_nvm3_open first."),
-        )),
+        None => Err(CpcNvm3Error::NotOpen),
     }
 }
 
 pub fn get_timeout(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(i32, i32), CpcNvm3Error> {
-    log::debug!("Obtaining configured timeout");
-
     let instance_arc_mutex = get_instance(cpc_nvm3_handle)?;
-    let instance: std::sync::MutexGuard<CpcNvm3Instance> =
-        instance_arc_mutex.lock().map_err(|err| {
-            CpcNvm3Error::ErrorCodeWithContext(
-                CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
-                format!("{}", err),
-            )
-        })?;
+    let instance = lock_instance(&instance_arc_mutex)?;
+
+    log::debug!("[{}] Obtaining configured timeout", instance.label);
 
     match &instance.cpc_endpoint {
         Some(endpoint) => {
@@ -1539,9 +4524,6 @@ pub fn get_timeout(cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(i32, i32), Cpc
             );
             Ok((timeout.seconds, timeout.microseconds))
         }
-        None => Err(CpcNvm3Error::ErrorCodeWithContext(
-            CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
-            format!("CPC Write failed. The CPC is not initialized. Call cpc_nvm3_open first."),
-        )),
+        None => Err(CpcNvm3Error::NotOpen),
     }
 }
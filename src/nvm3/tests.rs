@@ -1,12 +1,15 @@
 use super::*;
 
+// Transaction ID of the first operation after the handshake done by `open`
+const FIRST_TRANSACTION_ID: u8 = 0x04;
+
 fn prepare_test(response: Vec<u8>) -> cpc_nvm3_handle_t {
-    let _ = init_logger("", CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let handle = init().unwrap();
     open(handle, "cpcd_0", true).unwrap();
     let instance_arc_mutex = get_instance(handle).unwrap();
-    let mut instance = instance_arc_mutex.lock().unwrap();
+    let mut instance = lock_instance(&instance_arc_mutex).unwrap();
 
     let cpc_endpoint = instance.cpc_endpoint.as_mut().unwrap();
     cpc_endpoint.push_rx(response);
@@ -14,6 +17,170 @@ fn prepare_test(response: Vec<u8>) -> cpc_nvm3_handle_t {
     handle
 }
 
+fn push_response(handle: cpc_nvm3_handle_t, response: Vec<u8>) {
+    let instance_arc_mutex = get_instance(handle).unwrap();
+    let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+
+    let cpc_endpoint = instance.cpc_endpoint.as_mut().unwrap();
+    cpc_endpoint.push_rx(response);
+}
+
+// Drain the read timeouts of the endpoint reads since the last call
+fn take_read_timeouts(handle: cpc_nvm3_handle_t) -> Vec<(i32, i32)> {
+    let instance_arc_mutex = get_instance(handle).unwrap();
+    let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+
+    let cpc_endpoint = instance.cpc_endpoint.as_mut().unwrap();
+    cpc_endpoint.take_read_timeouts()
+}
+
+// Drain the frames written to the endpoint since the last call
+fn take_tx_frames(handle: cpc_nvm3_handle_t) -> Vec<Vec<u8>> {
+    let instance_arc_mutex = get_instance(handle).unwrap();
+    let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+
+    let cpc_endpoint = instance.cpc_endpoint.as_mut().unwrap();
+    std::iter::from_fn(|| cpc_endpoint.pop_tx()).collect()
+}
+
+fn push_read_error(handle: cpc_nvm3_handle_t, kind: std::io::ErrorKind) {
+    let instance_arc_mutex = get_instance(handle).unwrap();
+    let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+
+    let cpc_endpoint = instance.cpc_endpoint.as_mut().unwrap();
+    cpc_endpoint.push_rx_error(kind);
+}
+
+fn push_read_timeout(handle: cpc_nvm3_handle_t, delay: Duration) {
+    let instance_arc_mutex = get_instance(handle).unwrap();
+    let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+
+    let cpc_endpoint = instance.cpc_endpoint.as_mut().unwrap();
+    cpc_endpoint.push_rx_timeout(delay);
+}
+
+fn sl_status_response(transaction_id: u8, sl_status: u32) -> Vec<u8> {
+    let mut response = vec![
+        0x02, // cmd
+        0x05, // len
+        0x00, // len
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        transaction_id,
+        0x00, // response_type sl_status
+    ];
+    response.extend_from_slice(&sl_status.to_le_bytes());
+    response
+}
+
+fn ecode_response(transaction_id: u8, ecode: ECode) -> Vec<u8> {
+    let mut response = vec![
+        0x02, // cmd
+        0x05, // len
+        0x00, // len
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        transaction_id,
+        0x01, // response_type ecode
+    ];
+    response.extend_from_slice(&(ecode as u32).to_le_bytes());
+    response
+}
+
+fn read_data_response(transaction_id: u8, last_frag: bool, data: &[u8]) -> Vec<u8> {
+    let len = (data.len() + 1) as u16;
+    let mut response = vec![
+        0x09, // cmd
+        len.to_le_bytes()[0],
+        len.to_le_bytes()[1],
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        transaction_id,
+        last_frag as u8,
+    ];
+    response.extend_from_slice(data);
+    response
+}
+
+fn object_info_response(transaction_id: u8, object_size: u16) -> Vec<u8> {
+    let mut response = vec![
+        0x0B, // cmd
+        0x03, // len
+        0x00, // len
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        transaction_id,
+        0x00, // object_type data
+    ];
+    response.extend_from_slice(&object_size.to_le_bytes());
+    response
+}
+
+fn enumerate_objects_response(transaction_id: u8, keys: &[cpc_nvm3_object_key_t]) -> Vec<u8> {
+    enumerate_objects_fragment(transaction_id, true, keys)
+}
+
+fn enumerate_objects_fragment(
+    transaction_id: u8,
+    last_frag: bool,
+    keys: &[cpc_nvm3_object_key_t],
+) -> Vec<u8> {
+    let len = (keys.len() * CPC_NVM3_OBJECT_KEY_SIZE + 1) as u16;
+    let mut response = vec![
+        0x12, // cmd
+        len.to_le_bytes()[0],
+        len.to_le_bytes()[1],
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        transaction_id,
+        last_frag as u8,
+    ];
+    for key in keys {
+        response.extend_from_slice(&key.to_le_bytes());
+    }
+    response
+}
+
+fn object_count_response(transaction_id: u8, object_count: u16) -> Vec<u8> {
+    let mut response = vec![
+        0x14, // cmd
+        0x02, // len
+        0x00, // len
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        transaction_id,
+    ];
+    response.extend_from_slice(&object_count.to_le_bytes());
+    response
+}
+
+fn counter_response(transaction_id: u8, value: u32) -> Vec<u8> {
+    let mut response = vec![
+        0x0D, // cmd
+        0x04, // len
+        0x00, // len
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        transaction_id,
+    ];
+    response.extend_from_slice(&value.to_le_bytes());
+    response
+}
+
 fn finalize_test(sl_cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Error> {
     close(sl_cpc_nvm3_handle)?;
     deinit(sl_cpc_nvm3_handle)?;
@@ -22,7 +189,7 @@ fn finalize_test(sl_cpc_nvm3_handle: cpc_nvm3_handle_t) -> Result<(), CpcNvm3Err
 
 #[test]
 fn test_nvm3_close() {
-    let _ = init_logger("", CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let handle = init().unwrap();
     open(handle, "cpcd_0", true).unwrap();
@@ -31,7 +198,7 @@ fn test_nvm3_close() {
 
 #[test]
 fn test_nvm3_double_init_unique_handles() {
-    let _ = init_logger("", CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let handle_1 = init().unwrap();
     let handle_2 = init().unwrap();
@@ -43,19 +210,19 @@ fn test_nvm3_double_init_unique_handles() {
 #[test]
 fn test_nvm3_write_success() {
     let response = vec![
-        0x02, // cmd
-        0x05, // len
-        0x00, // len
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x03, // transaction_id
-        0x00, // response_type sl_status
-        0x00, // status byte
-        0x00, // status byte
-        0x00, // status byte
-        0x00, // status byte
+        0x02,                 // cmd
+        0x05,                 // len
+        0x00,                 // len
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        FIRST_TRANSACTION_ID, // transaction_id
+        0x00,                 // response_type sl_status
+        0x00,                 // status byte
+        0x00,                 // status byte
+        0x00,                 // status byte
+        0x00,                 // status byte
     ];
     let handle = prepare_test(response);
 
@@ -64,22 +231,52 @@ fn test_nvm3_write_success() {
     finalize_test(handle).unwrap();
 }
 
+#[test]
+fn test_nvm3_write_data_frame() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    // Drop the frames of the handshake
+    take_tx_frames(handle);
+
+    write_data(handle, 0x12345678, &[0xAA, 0xBB]).unwrap();
+    let expected_frame = vec![
+        crate::CPC_NVM3_COMMAND_WRITE_DATA, // cmd
+        0x09,                               // len
+        0x00,                               // len
+        0x00,                               // unique_id
+        0x00,                               // unique_id
+        0x00,                               // unique_id
+        0x00,                               // unique_id
+        FIRST_TRANSACTION_ID,               // transaction_id
+        0x78,                               // object_key
+        0x56,                               // object_key
+        0x34,                               // object_key
+        0x12,                               // object_key
+        0x00,                               // offset
+        0x00,                               // offset
+        0x01,                               // last_frag
+        0xAA,                               // data
+        0xBB,                               // data
+    ];
+    assert_eq!(take_tx_frames(handle), vec![expected_frame]);
+    finalize_test(handle).unwrap();
+}
+
 #[test]
 fn test_nvm3_write_invalid_key_response() {
     let response = vec![
-        0x02, // cmd
-        0x05, // len
-        0x00, // len
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x03, // transaction_id
-        0x01, // response_type ecode
-        0x0A, // status byte
-        0xE0, // status byte
-        0x00, // status byte
-        0xF0, // status byte
+        0x02,                 // cmd
+        0x05,                 // len
+        0x00,                 // len
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        FIRST_TRANSACTION_ID, // transaction_id
+        0x01,                 // response_type ecode
+        0x0A,                 // status byte
+        0xE0,                 // status byte
+        0x00,                 // status byte
+        0xF0,                 // status byte
     ];
     let handle = prepare_test(response);
 
@@ -88,32 +285,56 @@ fn test_nvm3_write_invalid_key_response() {
         Ok(_) => {
             panic!("Expected failure with invalid key error");
         }
-        Err(err) => match err {
-            CpcNvm3Error::ErrorCodeWithContext(err, context) => {
-                log::error!("{}", context);
-                assert_eq!(err, CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY);
-            }
-        },
+        Err(err) => {
+            log::error!("{}", err);
+            assert!(matches!(err, CpcNvm3Error::InvalidKey(1234)));
+        }
     }
     finalize_test(handle).unwrap();
 }
 
+#[test]
+fn test_nvm3_read_data_from_counter() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::ObjectIsNotData));
+
+    let mut buffer = [0u8; 4];
+    let mut data_size = 0;
+    let err = read_data(handle, 1234, &mut buffer, &mut data_size).unwrap_err();
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE);
+    assert!(err
+        .to_string()
+        .contains(&ECode::ObjectIsNotData.to_string()));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_data_to_counter() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::ObjectIsNotData));
+
+    let err = write_data(handle, 1234, &[0x1, 0x2]).unwrap_err();
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE);
+    assert!(err
+        .to_string()
+        .contains(&ECode::ObjectIsNotData.to_string()));
+    finalize_test(handle).unwrap();
+}
+
 #[test]
 fn test_nvm3_write_unknown_response() {
     let response = vec![
-        0x02, // cmd
-        0x05, // len
-        0x00, // len
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x03, // transaction_id
-        0x01, // response_type ecode
-        0x01, // status byte
-        0x00, // status byte
-        0x00, // status byte
-        0x00, // status byte
+        0x02,                 // cmd
+        0x05,                 // len
+        0x00,                 // len
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        FIRST_TRANSACTION_ID, // transaction_id
+        0x01,                 // response_type ecode
+        0x01,                 // status byte
+        0x00,                 // status byte
+        0x00,                 // status byte
+        0x00,                 // status byte
     ];
     let handle = prepare_test(response);
 
@@ -122,12 +343,10 @@ fn test_nvm3_write_unknown_response() {
         Ok(_) => {
             panic!("Expected failure with invalid key error");
         }
-        Err(err) => match err {
-            CpcNvm3Error::ErrorCodeWithContext(err, context) => {
-                log::error!("{}", context);
-                assert_eq!(err, CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR);
-            }
-        },
+        Err(err) => {
+            log::error!("{}", err);
+            assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_UNKNOWN_ERROR);
+        }
     }
     finalize_test(handle).unwrap();
 }
@@ -135,25 +354,25 @@ fn test_nvm3_write_unknown_response() {
 #[test]
 fn test_nvm3_read_success_small() {
     let response = vec![
-        0x09, // cmd
-        0x0B, // len
-        0x00, // len
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x03, // transaction_id
-        0x01, // last_frag
-        0x01, // data 1
-        0x02, // data 2
-        0x03, // data 3
-        0x04, // data 4
-        0x05, // data 5
-        0x06, // data 6
-        0x07, // data 7
-        0x08, // data 8
-        0x09, // data 9
-        0x0a, // data 10
+        0x09,                 // cmd
+        0x0B,                 // len
+        0x00,                 // len
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        FIRST_TRANSACTION_ID, // transaction_id
+        0x01,                 // last_frag
+        0x01,                 // data 1
+        0x02,                 // data 2
+        0x03,                 // data 3
+        0x04,                 // data 4
+        0x05,                 // data 5
+        0x06,                 // data 6
+        0x07,                 // data 7
+        0x08,                 // data 8
+        0x09,                 // data 9
+        0x0a,                 // data 10
     ];
     let handle = prepare_test(response);
     let mut buffer = [0u8; 10];
@@ -166,19 +385,19 @@ fn test_nvm3_read_success_small() {
 #[test]
 fn test_nvm3_read_fail_with_status() {
     let response = vec![
-        0x02, // cmd
-        0x05, // len
-        0x00, // len
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x00, // unique_id
-        0x03, // transaction_id
-        0x00, // response_type sl_status
-        0x01, // status byte
-        0x00, // status byte
-        0x00, // status byte
-        0x00, // status byte
+        0x02,                 // cmd
+        0x05,                 // len
+        0x00,                 // len
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        0x00,                 // unique_id
+        FIRST_TRANSACTION_ID, // transaction_id
+        0x00,                 // response_type sl_status
+        0x01,                 // status byte
+        0x00,                 // status byte
+        0x00,                 // status byte
+        0x00,                 // status byte
     ];
     let handle = prepare_test(response);
     let mut buffer = [0u8; 10];
@@ -188,9 +407,2900 @@ fn test_nvm3_read_fail_with_status() {
         Ok(_) => {
             panic!("Should have failed")
         }
-        Err(err) => match err {
-            CpcNvm3Error::ErrorCodeWithContext(_, context) => log::error!("{}", context),
-        },
+        Err(err) => log::error!("{}", err),
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_fragment_count() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 2, 0));
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        instance.maximum_write_fragment_size = Some(4);
+    }
+
+    let data: &[u8] = &[0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA];
+    let fragment_count = write_data_ex(handle, 1234, data).unwrap();
+    assert_eq!(fragment_count, 3);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_single_fragment_count() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+
+    let data: &[u8] = &[0x1, 0x2];
+    let fragment_count = write_data_ex(handle, 1234, data).unwrap();
+    assert_eq!(fragment_count, 1);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_empty_read_is_dropped() {
+    let handle = prepare_test(vec![]);
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID, 0));
+
+    let data: &[u8] = &[0x1, 0x2];
+    write_data(handle, 1234, data).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_repeated_empty_reads_try_again() {
+    let handle = prepare_test(vec![]);
+    push_response(handle, vec![]);
+    push_response(handle, vec![]);
+
+    let data: &[u8] = &[0x1, 0x2];
+    match write_data(handle, 1234, data) {
+        Ok(_) => panic!("Expected failure with try again error"),
+        Err(err) => {
+            log::error!("{}", err);
+            assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN);
+        }
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_concurrent_operations_complete() {
+    const THREAD_COUNT: u8 = 4;
+    const OPERATIONS_PER_THREAD: u8 = 10;
+
+    // Operations are serialized by the instance lock, so the responses are consumed
+    // in transaction id order regardless of which thread issued the request.
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    for transaction_id in
+        (FIRST_TRANSACTION_ID + 1)..(FIRST_TRANSACTION_ID + THREAD_COUNT * OPERATIONS_PER_THREAD)
+    {
+        push_response(handle, sl_status_response(transaction_id, 0));
+    }
+
+    let threads: Vec<_> = (0..THREAD_COUNT)
+        .map(|thread_index| {
+            std::thread::spawn(move || {
+                for value in 0..OPERATIONS_PER_THREAD {
+                    write_counter(handle, thread_index as u32, value as u32).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_cpc_max_write_size() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    match get_cpc_max_write_size(handle) {
+        Ok(_) => panic!("Expected failure since the instance is not opened"),
+        Err(err) => assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN),
+    }
+
+    open(handle, "cpcd_0", true).unwrap();
+    assert_eq!(
+        get_cpc_max_write_size(handle).unwrap(),
+        libcpc_mock::CPC_NVM3_MAX_WRITE_CAPABILITY as u16
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_update_existing_data() {
+    let handle = prepare_test(read_data_response(FIRST_TRANSACTION_ID, true, &[0x1, 0x2]));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+
+    update_data(handle, 1234, |current_data| {
+        assert_eq!(current_data, &[0x1, 0x2]);
+        let mut new_data = current_data.to_vec();
+        new_data.push(0x3);
+        new_data
+    })
+    .unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_update_creates_missing_data() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::KeyNotFound));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+
+    update_data(handle, 1234, |current_data| {
+        assert!(current_data.is_empty());
+        vec![0x7]
+    })
+    .unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_move_object() {
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 2));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::KeyNotFound),
+    );
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 2, true, &[0x1, 0x2]),
+    );
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 3, 0));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 4, true, &[0x1, 0x2]),
+    );
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 5, 0));
+
+    move_object(handle, 1, 2, false).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_move_object_existing_destination() {
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 2));
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 1, 4));
+
+    match move_object(handle, 1, 2, false) {
+        Err(CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG, _)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_move_object_counter() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+    let handle = prepare_test(counter_info_response);
+
+    assert_error_code(
+        move_object(handle, 1, 2, false),
+        CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_move_object_keeps_source_on_verify_failure() {
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 2));
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 1, 4));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 2, true, &[0x1, 0x2]),
+    );
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 3, 0));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 4, true, &[0x1, 0x3]),
+    );
+
+    match move_object(handle, 1, 2, true) {
+        Err(CpcNvm3Error::ErrorCodeWithContext(CpcNvm3ErrorCodes::CPC_NVM3_FAILURE, _)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    finalize_test(handle).unwrap();
+}
+
+fn assert_error_code<T: std::fmt::Debug>(
+    result: Result<T, CpcNvm3Error>,
+    expected_error_code: CpcNvm3ErrorCodes,
+) {
+    match result {
+        Err(err) => assert_eq!(err.code(), expected_error_code),
+        other => panic!("Expected {:?}, got {:?}", expected_error_code, other),
     }
+}
+
+#[test]
+fn test_nvm3_unknown_handle_is_not_initialized() {
+    init_test_logger();
+
+    let handle = u32::MAX;
+    let mut buffer = [0u8; 4];
+    let mut data_size = 0;
+    let not_initialized = CpcNvm3ErrorCodes::CPC_NVM3_NOT_INITIALIZED;
+
+    assert_error_code(open(handle, "cpcd_0", true), not_initialized);
+    assert_error_code(close(handle), not_initialized);
+    assert_error_code(write_data(handle, 1, &[0x1]), not_initialized);
+    assert_error_code(
+        read_data(handle, 1, &mut buffer, &mut data_size),
+        not_initialized,
+    );
+    assert_error_code(read_counter(handle, 1), not_initialized);
+    assert_error_code(get_object_count(handle), not_initialized);
+    assert_error_code(get_object_info(handle, 1), not_initialized);
+    assert_error_code(delete_object(handle, 1), not_initialized);
+    assert_error_code(get_maximum_write_size(handle), not_initialized);
+    assert_error_code(set_timeout(handle, 1, 0), not_initialized);
+    assert_error_code(get_timeout(handle), not_initialized);
+    assert_error_code(deinit(handle), not_initialized);
+}
+
+#[test]
+fn test_nvm3_unopened_handle_is_not_open() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    let mut buffer = [0u8; 4];
+    let mut data_size = 0;
+    let not_open = CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN;
+
+    assert_error_code(close(handle), not_open);
+    assert_error_code(write_data(handle, 1, &[0x1]), not_open);
+    assert_error_code(read_data(handle, 1, &mut buffer, &mut data_size), not_open);
+    assert_error_code(read_counter(handle, 1), not_open);
+    assert_error_code(get_object_count(handle), not_open);
+    assert_error_code(get_object_info(handle, 1), not_open);
+    assert_error_code(delete_object(handle, 1), not_open);
+    assert_error_code(get_maximum_write_size(handle), not_open);
+    assert_error_code(set_timeout(handle, 1, 0), not_open);
+    assert_error_code(get_timeout(handle), not_open);
+
+    deinit(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_features() {
+    let handle = prepare_test(vec![]);
+    assert_eq!(
+        get_features(handle).unwrap(),
+        crate::CPC_NVM3_FEATURE_MAX_OBJECT_SIZE
+    );
+    finalize_test(handle).unwrap();
+
+    // A secondary that does not answer the property only has the base features
+    libcpc_mock::reject_max_object_size_on_next_open();
+    let handle = prepare_test(vec![]);
+    assert_eq!(get_features(handle).unwrap(), crate::CPC_NVM3_FEATURES_BASE);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_list_objects_paged() {
+    let handle = prepare_test(enumerate_objects_response(
+        FIRST_TRANSACTION_ID,
+        &[10, 11, 12, 13, 14],
+    ));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[10, 11, 12, 13, 14]),
+    );
+
+    let mut keys = [0; 2];
+    let mut object_count = 0;
+    let mut more = false;
+
+    list_objects_paged(handle, 2, 1, &mut keys, &mut object_count, &mut more).unwrap();
+    assert_eq!(object_count, 2);
+    assert_eq!(keys, [12, 13]);
+    assert!(more);
+
+    list_objects_paged(handle, 2, 2, &mut keys, &mut object_count, &mut more).unwrap();
+    assert_eq!(object_count, 1);
+    assert_eq!(keys[0], 14);
+    assert!(!more);
+
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_object_cursor() {
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 3));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[10, 11, 12]),
+    );
+    // Drop the frames of the handshake
+    take_tx_frames(handle);
+
+    let mut cursor = open_object_cursor(handle, 2).unwrap();
+    let mut keys = [0; 2];
+
+    assert_eq!(cursor.next_page(&mut keys).unwrap(), 2);
+    assert_eq!(keys, [10, 11]);
+    assert!(cursor.has_more());
+    // The object count and a single enumeration
+    assert_eq!(take_tx_frames(handle).len(), 2);
+
+    // The next page carries on from the last key without enumerating again
+    assert_eq!(cursor.next_page(&mut keys).unwrap(), 1);
+    assert_eq!(keys[0], 12);
+    assert!(!cursor.has_more());
+    assert!(take_tx_frames(handle).is_empty());
+
+    // The cursor is exhausted
+    assert_eq!(cursor.next_page(&mut keys).unwrap(), 0);
+    assert_error_code(
+        cursor.next_page(&mut keys[..1]),
+        CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL,
+    );
+
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_delete_all_objects_bounded() {
+    let handle = prepare_test(enumerate_objects_response(
+        FIRST_TRANSACTION_ID,
+        &[10, 11, 12],
+    ));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 2, 0));
+
+    let mut more_remaining = false;
+    assert_eq!(
+        delete_all_objects(handle, 2, &mut more_remaining).unwrap(),
+        2
+    );
+    assert!(more_remaining);
+
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 3, &[12]),
+    );
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 4, 0));
+
+    assert_eq!(
+        delete_all_objects(handle, 2, &mut more_remaining).unwrap(),
+        1
+    );
+    assert!(!more_remaining);
+
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_delete_objects_by_type() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 3, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+    let mut vanished_counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 5, 4);
+    vanished_counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 4));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[1, 2, 3, 4]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 2));
+    push_response(handle, counter_info_response);
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 4, 0));
+    push_response(handle, vanished_counter_info_response);
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 6, ECode::KeyNotFound),
+    );
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 7, ECode::KeyNotFound),
+    );
+    take_tx_frames(handle);
+
+    let mut deleted_count = 0;
+    assert_eq!(
+        crate::cpc_nvm3_delete_objects_by_type(
+            handle,
+            CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER,
+            &mut deleted_count,
+        ),
+        0
+    );
+    assert_eq!(deleted_count, 1);
+
+    // Only the counters were deleted, the data object 1 was only queried
+    let deleted_keys: Vec<u8> = take_tx_frames(handle)
+        .iter()
+        .filter(|frame| frame[0] == crate::CPC_NVM3_COMMAND_DELETE_OBJECT)
+        .map(|frame| frame[8])
+        .collect();
+    assert_eq!(deleted_keys, vec![2, 3]);
+
+    assert_error_code(
+        delete_objects_by_type(handle, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_logger_reinit() {
+    // No other test can install its logger while this one replaces it
+    let _logger_lock = TEST_LOGGER_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    let first_path =
+        std::env::temp_dir().join(format!("cpc_nvm3_log_first_{}", std::process::id()));
+    let second_path =
+        std::env::temp_dir().join(format!("cpc_nvm3_log_second_{}", std::process::id()));
+
+    shutdown_logger().unwrap();
+    init_logger(
+        Some("first"),
+        CpcNvm3LogLevel::CPC_NVM3_LOG_INFO,
+        first_path.to_str(),
+        false,
+    )
+    .unwrap();
+    log::info!("Logged to the first file");
+    shutdown_logger().unwrap();
+    log::info!("Logged without a logger");
+
+    init_logger(
+        Some("second"),
+        CpcNvm3LogLevel::CPC_NVM3_LOG_INFO,
+        second_path.to_str(),
+        false,
+    )
+    .unwrap();
+    log::info!("Logged to the second file");
+    shutdown_logger().unwrap();
+
+    let first_log = std::fs::read_to_string(&first_path).unwrap();
+    let second_log = std::fs::read_to_string(&second_path).unwrap();
+    assert!(first_log.contains("Logged to the first file"));
+    assert!(!first_log.contains("Logged to the second file"));
+    assert!(second_log.contains("Logged to the second file"));
+    assert!(!second_log.contains("Logged to the first file"));
+    assert!(!first_log.contains("Logged without a logger"));
+    assert!(!second_log.contains("Logged without a logger"));
+    std::fs::remove_file(&first_path).unwrap();
+    std::fs::remove_file(&second_path).unwrap();
+}
+
+#[test]
+fn test_logger_write_failure() {
+    // A file opened read-only fails every write
+    let file = OpenOptions::new().read(true).open("/dev/null").unwrap();
+    let logger = std::sync::Arc::new(FileLogger::new(
+        LevelFilter::Debug,
+        "prefix".to_string(),
+        file,
+    ));
+    let record = |logger: &FileLogger| {
+        logger.log(
+            &Record::builder()
+                .args(format_args!("Dropped record"))
+                .level(log::Level::Info)
+                .build(),
+        );
+        logger.flush();
+    };
+    record(&logger);
+
+    let poisoning_logger = logger.clone();
+    let panicked = std::thread::spawn(move || {
+        let _file = poisoning_logger.file.lock().unwrap();
+        panic!("Poisoning the log file lock");
+    })
+    .join();
+    assert!(panicked.is_err());
+    record(&logger);
+}
+
+#[cfg(feature = "json-logs")]
+#[test]
+fn test_json_log_record() {
+    let file = OpenOptions::new().write(true).open("/dev/null").unwrap();
+    let logger = FileLogger::new(LevelFilter::Debug, "prefix".to_string(), file);
+
+    let line = logger.format_record(
+        &Record::builder()
+            .args(format_args!("Read {} bytes", 4))
+            .level(log::Level::Info)
+            .target("cpc_nvm3")
+            .build(),
+    );
+    assert!(line.ends_with('\n'));
+    assert_eq!(line.lines().count(), 1);
+
+    let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert!(json["ts"].is_string());
+    assert_eq!(json["level"], "INFO");
+    assert_eq!(json["prefix"], "prefix");
+    assert_eq!(json["target"], "cpc_nvm3");
+    assert_eq!(json["msg"], "Read 4 bytes");
+}
+
+#[test]
+fn test_nvm3_write_counter_busy() {
+    let handle = prepare_test(sl_status_response(
+        FIRST_TRANSACTION_ID,
+        SlStatus::Busy as u32,
+    ));
+
+    assert_error_code(
+        write_counter(handle, 1234, 1),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_delete_object_busy() {
+    let handle = prepare_test(sl_status_response(
+        FIRST_TRANSACTION_ID,
+        SlStatus::Busy as u32,
+    ));
+
+    assert_error_code(
+        delete_object(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_open_deadline_exceeded() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    assert_error_code(
+        open_ex(handle, "cpcd_0", true, Duration::ZERO),
+        CpcNvm3ErrorCodes::CPC_NVM3_TIMEOUT,
+    );
+    // The failed open leaves the instance closed
+    assert_error_code(close(handle), CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN);
+    deinit(handle).unwrap();
+}
+
+#[test]
+fn test_object_type_str() {
+    let object_type_str = |object_type| unsafe {
+        std::ffi::CStr::from_ptr(crate::cpc_nvm3_object_type_str(object_type))
+            .to_str()
+            .unwrap()
+    };
+
+    assert_eq!(
+        object_type_str(CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER),
+        "COUNTER"
+    );
+    assert_eq!(
+        object_type_str(CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA),
+        "DATA"
+    );
+    assert_eq!(
+        object_type_str(CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN),
+        "UNKNOWN"
+    );
+    assert_eq!(
+        CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA.to_string(),
+        "DATA"
+    );
+}
+
+#[test]
+fn test_nvm3_write_counter_readback() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 1, 42));
+
+    assert_eq!(write_counter_readback(handle, 1234, 42).unwrap(), 42);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_resync_after_invalid_length() {
+    let mut malformed_response = sl_status_response(FIRST_TRANSACTION_ID, 0);
+    malformed_response.push(0x00);
+    let handle = prepare_test(malformed_response);
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID, 0));
+
+    write_counter(handle, 1234, 1).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_resync_attempts_are_bounded() {
+    let mut malformed_response = sl_status_response(FIRST_TRANSACTION_ID, 0);
+    malformed_response.push(0x00);
+    let handle = prepare_test(malformed_response.clone());
+    for _ in 0..CPC_NVM3_MAX_RESYNC_ATTEMPTS {
+        push_response(handle, malformed_response.clone());
+    }
+
+    assert_error_code(
+        write_counter(handle, 1234, 1),
+        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_instance_label() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    assert_eq!(instance_label(handle), handle.to_string());
+
+    set_instance_label(handle, "gateway-1").unwrap();
+    assert_eq!(instance_label(handle), "gateway-1");
+
+    assert_error_code(
+        set_instance_label(handle, &"x".repeat(CPC_NVM3_MAX_LABEL_LEN + 1)),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    assert_eq!(instance_label(handle), "gateway-1");
+
+    // A locked instance is not waited for
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let _instance = lock_instance(&instance_arc_mutex).unwrap();
+        assert_eq!(instance_label(handle), handle.to_string());
+    }
+
+    set_instance_label(handle, "").unwrap();
+    assert_eq!(instance_label(handle), handle.to_string());
+
+    deinit(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_counters() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID, 7));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::KeyNotFound),
+    );
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 2, ECode::ObjectIsNotACounter),
+    );
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 3, 9));
+
+    let counters = read_counters(handle, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(counters.len(), 4);
+    assert!(matches!(counters[0], (1, Ok(7))));
+    assert!(matches!(counters[1], (2, Err(CpcNvm3Error::InvalidKey(2)))));
+    assert_eq!(
+        counters[2].1.as_ref().unwrap_err().code(),
+        CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE
+    );
+    assert!(matches!(counters[3], (4, Ok(9))));
+
+    // A link error is not reported per key, the read is aborted
+    push_read_error(handle, std::io::ErrorKind::ConnectionReset);
+    assert_error_code(
+        read_counters(handle, &[1, 2]),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_double_close() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+
+    close(handle).unwrap();
+    assert_error_code(close(handle), CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN);
+    deinit(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_collect_object_keys() {
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 3));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[10, 11, 12]),
+    );
+
+    assert_eq!(collect_object_keys(handle).unwrap(), vec![10, 11, 12]);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_collect_object_keys_grows() {
+    // An object was created between the count and the enumeration
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 1));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[10, 11]),
+    );
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 2, &[10, 11]),
+    );
+
+    assert_eq!(collect_object_keys(handle).unwrap(), vec![10, 11]);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_deinit_force_closes_instance() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+
+    assert_error_code(deinit(handle), CpcNvm3ErrorCodes::CPC_NVM3_NOT_CLOSED);
+    deinit_force(handle).unwrap();
+    assert_error_code(
+        deinit_force(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_INITIALIZED,
+    );
+}
+
+#[test]
+fn test_nvm3_write_data_batch_partial() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+    push_response(
+        handle,
+        sl_status_response(FIRST_TRANSACTION_ID + 2, SlStatus::Busy as u32),
+    );
+
+    let keys: [cpc_nvm3_object_key_t; 5] = [1, 2, 3, 4, 5];
+    let data: [u8; 4] = [0x1, 0x2, 0x3, 0x4];
+    let data_ptrs = [data.as_ptr(); 5];
+    let data_lengths = [data.len() as u16; 5];
+    let mut results = [1; 5];
+
+    let status = crate::cpc_nvm3_write_data_batch(
+        handle,
+        keys.as_ptr(),
+        data_ptrs.as_ptr(),
+        data_lengths.as_ptr(),
+        keys.len() as u16,
+        results.as_mut_ptr(),
+        std::ptr::null_mut(),
+    );
+
+    let try_again = CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN as i32;
+    assert_eq!(status, try_again);
+    assert_eq!(results, [0, 0, try_again, try_again, try_again]);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_data_batch() {
+    let handle = prepare_test(read_data_response(FIRST_TRANSACTION_ID, true, &[0xA, 0xB]));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 1, true, &[0xC]),
+    );
+
+    let mut first = [0; 4];
+    let mut second = [0; 4];
+    let mut entries: [(cpc_nvm3_object_key_t, &mut [u8]); 2] =
+        [(1, &mut first[..]), (2, &mut second[..])];
+
+    let reads = read_data_batch(handle, &mut entries).unwrap();
+    assert!(matches!(reads[..], [Ok(2), Ok(1)]));
+    assert_eq!(first[..2], [0xA, 0xB]);
+    assert_eq!(second[0], 0xC);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_list_objects_progress() {
+    let handle = prepare_test(enumerate_objects_fragment(
+        FIRST_TRANSACTION_ID,
+        false,
+        &[10, 11],
+    ));
+    push_response(
+        handle,
+        enumerate_objects_fragment(FIRST_TRANSACTION_ID, true, &[12]),
+    );
+
+    let mut keys = [0; 4];
+    let mut object_count = 0;
+    let mut progress = vec![];
+    list_objects_with_progress(handle, &mut keys, &mut object_count, |key_count| {
+        progress.push(key_count);
+        true
+    })
+    .unwrap();
+
+    assert_eq!(progress, vec![2, 3]);
+    assert_eq!(object_count, 3);
+    assert_eq!(keys[..3], [10, 11, 12]);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_list_objects_progress_cancelled() {
+    let handle = prepare_test(enumerate_objects_fragment(
+        FIRST_TRANSACTION_ID,
+        false,
+        &[10, 11],
+    ));
+    push_response(
+        handle,
+        enumerate_objects_fragment(FIRST_TRANSACTION_ID, true, &[12]),
+    );
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 1, 7));
+
+    let mut keys = [0; 4];
+    let mut object_count = 0;
+    assert_error_code(
+        list_objects_with_progress(handle, &mut keys, &mut object_count, |_| false),
+        CpcNvm3ErrorCodes::CPC_NVM3_CANCELLED,
+    );
+
+    // The remaining fragment of the cancelled enumeration is dropped
+    assert_eq!(read_counter(handle, 1234).unwrap(), 7);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_exceeds_object_size() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        instance.maximum_object_size = Some(8);
+    }
+
+    write_data(handle, 1234, &[0x1; 8]).unwrap();
+    match write_data(handle, 1234, &[0x1; 9]) {
+        Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            context,
+        )) => {
+            assert!(context.contains("maximum object size"))
+        }
+        result => panic!("Expected an invalid argument error, got {:?}", result),
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_exceeds_write_size() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+    let maximum_write_size = get_maximum_write_size(handle).unwrap();
+    assert!(maximum_write_size < libcpc_mock::CPC_NVM3_MOCK_MAX_OBJECT_SIZE);
+
+    let data = vec![0x1; maximum_write_size as usize + 1];
+    match write_data(handle, 1234, &data) {
+        Err(CpcNvm3Error::ErrorCodeWithContext(
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+            context,
+        )) => {
+            assert!(context.contains("maximum write size"))
+        }
+        result => panic!("Expected an invalid argument error, got {:?}", result),
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_limits() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    assert_error_code(get_limits(handle), CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN);
+
+    open(handle, "cpcd_0", true).unwrap();
+    let limits = get_limits(handle).unwrap();
+    assert_eq!(limits.max_write_size, 0x00FF);
+    assert_eq!(
+        limits.max_object_size,
+        libcpc_mock::CPC_NVM3_MOCK_MAX_OBJECT_SIZE
+    );
+    assert_eq!(
+        limits.cpc_max_write,
+        libcpc_mock::CPC_NVM3_MAX_WRITE_CAPABILITY as u16
+    );
+    assert_eq!(
+        limits.max_fragment_size,
+        limits.cpc_max_write - protocol::CmdWriteData::get_overhead()
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_maximum_write_fragment_size() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    assert_error_code(
+        get_maximum_write_fragment_size(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
+    );
+
+    open(handle, "cpcd_0", true).unwrap();
+    assert_eq!(
+        get_maximum_write_fragment_size(handle).unwrap(),
+        libcpc_mock::CPC_NVM3_MAX_WRITE_CAPABILITY as u16 - protocol::CmdWriteData::get_overhead()
+    );
+
+    let mut max_fragment_size = 0;
+    assert_eq!(
+        crate::cpc_nvm3_get_maximum_write_fragment_size(handle, &mut max_fragment_size),
+        0
+    );
+    assert_eq!(
+        max_fragment_size,
+        get_maximum_write_fragment_size(handle).unwrap()
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_maximum_object_size_fallback() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+    assert_eq!(
+        get_maximum_object_size(handle).unwrap(),
+        (libcpc_mock::CPC_NVM3_MOCK_MAX_OBJECT_SIZE, false)
+    );
+    finalize_test(handle).unwrap();
+
+    libcpc_mock::reject_max_object_size_on_next_open();
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+    let maximum_write_size = get_maximum_write_size(handle).unwrap();
+    assert_eq!(
+        get_maximum_object_size(handle).unwrap(),
+        (maximum_write_size, true)
+    );
+    assert_eq!(
+        get_limits(handle).unwrap().max_object_size,
+        maximum_write_size
+    );
+
+    let mut max_object_size = 0;
+    let mut approximate = false;
+    assert_eq!(
+        crate::cpc_nvm3_get_maximum_object_size(handle, &mut max_object_size, &mut approximate),
+        0
+    );
+    assert_eq!(max_object_size, maximum_write_size);
+    assert!(approximate);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_increment_counter_ex() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID, 5));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 1, 0));
+
+    assert_eq!(increment_counter_ex(handle, 1234).unwrap(), (4, 5));
+    // The counter wrapped around
+    assert_eq!(increment_counter_ex(handle, 1234).unwrap(), (u32::MAX, 0));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_ping() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+
+    ping(handle).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_keepalive() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+
+    assert_error_code(
+        enable_keepalive(handle, Duration::ZERO),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    enable_keepalive(handle, Duration::from_millis(10)).unwrap();
+
+    // Wait for the keepalive thread to send its ping
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let instance = lock_instance(&instance_arc_mutex).unwrap();
+        if instance.transaction_id == FIRST_TRANSACTION_ID {
+            break;
+        }
+        assert!(Instant::now() < deadline, "No keepalive was sent");
+        drop(instance);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    disable_keepalive(handle).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_last_status() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::KeyNotFound));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0x42));
+
+    assert_error_code(get_last_status(handle), CpcNvm3ErrorCodes::CPC_NVM3_FAILURE);
+
+    assert_error_code(
+        delete_object(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
+    );
+    assert_eq!(
+        get_last_status(handle).unwrap(),
+        (crate::CPC_NVM3_STATUS_KIND_ECODE, ECode::KeyNotFound as u32)
+    );
+
+    // The raw value is kept even if it does not map to a known sl_status
+    assert!(delete_object(handle, 1234).is_err());
+    assert_eq!(
+        get_last_status(handle).unwrap(),
+        (crate::CPC_NVM3_STATUS_KIND_SL_STATUS, 0x42)
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_data_from_fd() {
+    use std::io::{Seek, Write};
+    use std::os::unix::io::AsRawFd;
+
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 2, 0));
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        instance.maximum_write_fragment_size = Some(4);
+    }
+
+    let path = std::env::temp_dir().join(format!("cpc_nvm3_write_fd_{}", std::process::id()));
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(&[0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA])
+        .unwrap();
+    file.rewind().unwrap();
+
+    let status = crate::cpc_nvm3_write_data_from_fd(handle, 1234, file.as_raw_fd(), 10);
+    assert_eq!(status, 0);
+
+    // The file only holds 10 bytes, the write is aborted when reading its last fragment
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 3, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 4, 0));
+    file.rewind().unwrap();
+    assert_error_code(
+        write_data_from_reader(handle, 1234, &mut file, 11),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_snapshot() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 4, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 3));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[1, 2, 3]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 2));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &[0xA, 0xB]),
+    );
+    push_response(handle, counter_info_response);
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 5, 42));
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 6, 1));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 7, true, &[0xC]),
+    );
+
+    let mut objects = vec![];
+    let skipped = snapshot(handle, |key, object_type, value| {
+        let content = match value {
+            ObjectValue::Data(data) => data.to_vec(),
+            ObjectValue::Counter(value) => value.to_le_bytes().to_vec(),
+        };
+        objects.push((key, object_type, content));
+    })
+    .unwrap();
+
+    assert_eq!(skipped, 0);
+    assert_eq!(
+        objects,
+        vec![
+            (
+                1,
+                CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA,
+                vec![0xA, 0xB]
+            ),
+            (
+                2,
+                CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER,
+                42u32.to_le_bytes().to_vec()
+            ),
+            (3, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA, vec![0xC]),
+        ]
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_nvm3_async_read_data() {
+    let handle = prepare_test(read_data_response(FIRST_TRANSACTION_ID, true, &[0xA, 0xB]));
+    let nvm3 = r#async::Nvm3::new(handle);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let data = runtime.block_on(nvm3.read_data(1234, 16)).unwrap();
+    assert_eq!(data, vec![0xA, 0xB]);
+
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_command_timeout() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID, 1));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 1, 2));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 2, &[1]),
+    );
+    set_timeout(handle, 5, 0).unwrap();
+    set_command_timeout(handle, crate::CPC_NVM3_COMMAND_READ_COUNTER, 2, 500).unwrap();
+    take_read_timeouts(handle);
+
+    assert_eq!(read_counter(handle, 1234).unwrap(), 1);
+    assert_eq!(take_read_timeouts(handle), vec![(2, 500)]);
+    assert_eq!(get_timeout(handle).unwrap(), (5, 0));
+
+    // A zero timeout falls back to the timeout of the endpoint
+    set_command_timeout(handle, crate::CPC_NVM3_COMMAND_READ_COUNTER, 0, 0).unwrap();
+    assert_eq!(read_counter(handle, 1234).unwrap(), 2);
+    assert_eq!(take_read_timeouts(handle), vec![(5, 0)]);
+
+    // The enumeration has a longer timeout by default
+    let mut keys = [0; 4];
+    let mut object_count = 0;
+    list_objects(handle, &mut keys, &mut object_count).unwrap();
+    assert_eq!(
+        take_read_timeouts(handle),
+        vec![(CPC_NVM3_ENUMERATE_TIMEOUT_S as i32, 0)]
+    );
+    assert_eq!(get_timeout(handle).unwrap(), (5, 0));
+
+    // Every fragment of a response is read with the timeout of its command
+    set_command_timeout(handle, crate::CPC_NVM3_COMMAND_READ_DATA, 3, 0).unwrap();
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, false, &[0x5A; 16]),
+    );
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &[0xA5; 16]),
+    );
+    let mut buffer = [0u8; 32];
+    let mut data_size = 0;
+    read_data(handle, 1234, &mut buffer, &mut data_size).unwrap();
+    assert_eq!(take_read_timeouts(handle), vec![(3, 0), (3, 0)]);
+    assert_eq!(get_timeout(handle).unwrap(), (5, 0));
+
+    assert_error_code(
+        set_command_timeout(handle, 0x01, 1, 0),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    assert_error_code(
+        set_command_timeout(handle, crate::CPC_NVM3_COMMAND_ENUMERATE_OBJECTS, -1, 0),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_default_read_timeout() {
+    init_test_logger();
+
+    assert_error_code(
+        set_default_read_timeout(0, 0),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    assert_error_code(
+        set_default_read_timeout(1, 1_000_000),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+
+    let opened_handle = init().unwrap();
+    open(opened_handle, "cpcd_0", true).unwrap();
+
+    // The default is shared with the tests running in parallel, it is restored even when
+    // the open fails
+    struct DefaultReadTimeoutGuard;
+    impl Drop for DefaultReadTimeoutGuard {
+        fn drop(&mut self) {
+            set_default_read_timeout(CPC_NVM3_READ_TIMEOUT_S, 0).unwrap();
+        }
+    }
+
+    let default_read_timeout_guard = DefaultReadTimeoutGuard;
+    set_default_read_timeout(2, 500).unwrap();
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+
+    // Only the instances opened after the change use the new default
+    assert_eq!(get_timeout(handle).unwrap(), (2, 500));
+    assert_eq!(get_timeout(opened_handle).unwrap(), (5, 0));
+
+    // A reconnection opens a new endpoint, which gets the default as well
+    set_timeout(handle, 7, 0).unwrap();
+    push_read_error(handle, std::io::ErrorKind::ConnectionReset);
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    assert_eq!(get_timeout(handle).unwrap(), (2, 500));
+    drop(default_read_timeout_guard);
+
+    finalize_test(handle).unwrap();
+    finalize_test(opened_handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_object_info_unknown_type() {
+    let mut unknown_info_response = object_info_response(FIRST_TRANSACTION_ID, 12);
+    unknown_info_response[8] = 0x7F; // object_type
+    let mut strict_info_response = unknown_info_response.clone();
+    strict_info_response[7] = FIRST_TRANSACTION_ID + 1; // transaction_id
+
+    // The C API reports a type it doesn't know rather than failing
+    let handle = prepare_test(unknown_info_response);
+    let mut object_size = 0;
+    let mut object_type = CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA;
+    assert_eq!(
+        crate::cpc_nvm3_get_object_info(handle, 1234, &mut object_size, &mut object_type),
+        0
+    );
+    assert_eq!(object_size, 12);
+    assert_eq!(object_type, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN);
+
+    push_response(handle, strict_info_response);
+    assert_error_code(
+        get_object_info_strict(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_object_size() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 1, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 12));
+    push_response(handle, counter_info_response);
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 2, ECode::KeyNotFound),
+    );
+
+    let mut object_size = 0;
+    assert_eq!(
+        crate::cpc_nvm3_get_object_size(handle, 1234, &mut object_size),
+        0
+    );
+    assert_eq!(object_size, 12);
+    assert_error_code(
+        get_object_size(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
+    );
+    assert_error_code(
+        get_object_size(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_concat() {
+    let handle = prepare_test(read_data_response(FIRST_TRANSACTION_ID, true, &[0x1, 0x2]));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 1, true, &[0x3]),
+    );
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 2, true, &[0x4, 0x5, 0x6]),
+    );
+
+    let keys = [10, 11, 12];
+    let mut buffer = [0u8; 8];
+    let mut data_size = 0;
+    assert_eq!(
+        crate::cpc_nvm3_read_concat(
+            handle,
+            keys.as_ptr(),
+            keys.len() as u16,
+            buffer.as_mut_ptr() as *mut std::ffi::c_void,
+            buffer.len(),
+            &mut data_size,
+        ),
+        0
+    );
+    assert_eq!(data_size, 6);
+    assert_eq!(buffer[..6], [0x1, 0x2, 0x3, 0x4, 0x5, 0x6]);
+
+    // The missing object is reported with its key
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &[0x1]),
+    );
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 4, ECode::KeyNotFound),
+    );
+    assert!(matches!(
+        read_concat(handle, &keys, &mut buffer, &mut data_size),
+        Err(CpcNvm3Error::InvalidKey(11))
+    ));
+
+    // The size needed covers the objects read before the one that doesn't fit
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 5, true, &[0x1; 6]),
+    );
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 6, ECode::ReadDataSize),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 7, 5));
+    assert!(matches!(
+        read_concat(handle, &keys, &mut buffer, &mut data_size),
+        Err(CpcNvm3Error::BufferTooSmall { needed: 11, got: 8 })
+    ));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_u32_data() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 3, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 4));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 1, true, &[0x78, 0x56, 0x34, 0x12]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 5));
+    push_response(handle, counter_info_response);
+
+    let mut value = 0;
+    assert_eq!(crate::cpc_nvm3_read_u32_data(handle, 1234, &mut value), 0);
+    assert_eq!(value, 0x12345678);
+    assert_error_code(
+        read_u32_data(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    assert_error_code(
+        read_u32_data(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_data_truncating() {
+    let object: Vec<u8> = (0..20).collect();
+
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 20));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 1, true, &object),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 20));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &object),
+    );
+
+    let mut buffer = [0u8; 10];
+    let mut full_size = 0;
+    assert_eq!(
+        crate::cpc_nvm3_read_data_truncating(
+            handle,
+            1234,
+            buffer.as_mut_ptr() as *mut std::ffi::c_void,
+            buffer.len() as u16,
+            &mut full_size,
+        ),
+        0
+    );
+    assert_eq!(full_size, 20);
+    assert_eq!(buffer, object[..10]);
+
+    // A buffer large enough is not truncated
+    let mut buffer = [0u8; 32];
+    assert_eq!(read_data_truncating(handle, 1234, &mut buffer).unwrap(), 20);
+    assert_eq!(buffer[..20], object[..]);
+    assert!(buffer[20..].iter().all(|byte| *byte == 0));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_append_data() {
+    const DATA_OFFSET: usize = 15;
+
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 3));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 1, true, &[1, 2, 3]),
+    );
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 2, 0));
+    // Drop the frames of the handshake
+    take_tx_frames(handle);
+
+    assert_eq!(append_data(handle, 1234, &[4, 5]).unwrap(), 5);
+    let frames = take_tx_frames(handle);
+    assert_eq!(frames.len(), 3);
+    assert_eq!(&frames[2][DATA_OFFSET..], &[1, 2, 3, 4, 5]);
+
+    // A missing object is created
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 3, ECode::KeyNotFound),
+    );
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 4, 0));
+    let mut object_size = 0;
+    assert_eq!(
+        crate::cpc_nvm3_append_data(handle, 1234, [6, 7].as_ptr(), 2, &mut object_size),
+        0
+    );
+    assert_eq!(object_size, 2);
+    let frames = take_tx_frames(handle);
+    assert_eq!(frames.len(), 2);
+    assert_eq!(&frames[1][DATA_OFFSET..], &[6, 7]);
+
+    // Nothing is read nor written when the object would grow too large
+    let maximum_object_size = get_maximum_object_size(handle).unwrap().0;
+    push_response(
+        handle,
+        object_info_response(FIRST_TRANSACTION_ID + 5, maximum_object_size),
+    );
+    assert_error_code(
+        append_data(handle, 1234, &[8]),
+        CpcNvm3ErrorCodes::CPC_NVM3_OBJECT_TOO_LARGE,
+    );
+    assert_eq!(take_tx_frames(handle).len(), 1);
+
+    // Nor is it when the object is a counter
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 6, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+    push_response(handle, counter_info_response);
+    assert_error_code(
+        append_data(handle, 1234, &[8]),
+        CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE,
+    );
+    assert_eq!(take_tx_frames(handle).len(), 1);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_sync() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    assert_error_code(sync(handle), CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN);
+
+    open(handle, "cpcd_0", true).unwrap();
+    // Drop the frames of the handshake
+    take_tx_frames(handle);
+    assert_eq!(
+        crate::cpc_nvm3_sync(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_SUPPORTED as i32
+    );
+    // Nothing is sent to the secondary
+    assert!(take_tx_frames(handle).is_empty());
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_ffi_write_length_exceeds_object_size() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+
+    // The length is rejected before the buffer is accessed
+    let data: [u8; 4] = [0x1, 0x2, 0x3, 0x4];
+    let status = crate::cpc_nvm3_write_data(
+        handle,
+        1234,
+        data.as_ptr(),
+        libcpc_mock::CPC_NVM3_MOCK_MAX_OBJECT_SIZE + 1,
+    );
+    assert_eq!(status, CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_ffi_write_data_result() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::ObjectIsNotData));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+
+    let data: [u8; 2] = [0x1, 0x2];
+    let mut result = std::mem::MaybeUninit::<crate::CpcNvm3Result>::uninit();
+    let status = crate::cpc_nvm3_write_data_r(handle, 1234, data.as_ptr(), 2, result.as_mut_ptr());
+    let result = unsafe { result.assume_init() };
+    assert_eq!(status, CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE as i32);
+    assert_eq!(result.code, status);
+    let message = unsafe { std::ffi::CStr::from_ptr(result.message.as_ptr()) };
+    assert!(message
+        .to_str()
+        .unwrap()
+        .contains(&ECode::ObjectIsNotData.to_string()));
+
+    // The context is cleared once an operation succeeds
+    let mut result = std::mem::MaybeUninit::<crate::CpcNvm3Result>::uninit();
+    let status = crate::cpc_nvm3_write_data_r(handle, 1234, data.as_ptr(), 2, result.as_mut_ptr());
+    let result = unsafe { result.assume_init() };
+    assert_eq!(status, 0);
+    assert_eq!(result.code, 0);
+    assert_eq!(result.message[0], 0);
+
+    // The context of an argument error is reported too
+    let mut result = std::mem::MaybeUninit::<crate::CpcNvm3Result>::uninit();
+    let status = crate::cpc_nvm3_write_data_r(
+        handle,
+        1234,
+        data.as_ptr(),
+        libcpc_mock::CPC_NVM3_MOCK_MAX_OBJECT_SIZE + 1,
+        result.as_mut_ptr(),
+    );
+    let result = unsafe { result.assume_init() };
+    assert_eq!(status, CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG as i32);
+    let message = unsafe { std::ffi::CStr::from_ptr(result.message.as_ptr()) };
+    assert!(message
+        .to_str()
+        .unwrap()
+        .contains("exceeds the maximum object size"));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_export_all() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 4, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 3));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[1, 2, 3]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 2));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &[0xA, 0xB]),
+    );
+    push_response(handle, counter_info_response);
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 5, 42));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 6, ECode::KeyNotFound),
+    );
+
+    let mut data_objects = vec![];
+    let mut counters = vec![];
+    let skipped = export_all(
+        handle,
+        |key, data| data_objects.push((key, data.to_vec())),
+        |key, value| counters.push((key, value)),
+    )
+    .unwrap();
+
+    assert_eq!(data_objects, vec![(1, vec![0xA, 0xB])]);
+    assert_eq!(counters, vec![(2, 42)]);
+    assert_eq!(skipped, 1);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_nonblocking_try_again() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+
+    set_nonblocking(handle, true).unwrap();
+    take_tx_frames(handle);
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+
+    // The retry collects the response of the command already sent
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID, 1));
+    assert_eq!(read_counter(handle, 1234).unwrap(), 1);
+    let frames = take_tx_frames(handle);
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0][7], FIRST_TRANSACTION_ID);
+
+    // Another operation sends its own command, the retry used up a transaction ID
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 3, 2));
+    assert_eq!(read_counter(handle, 5678).unwrap(), 2);
+    assert_eq!(take_tx_frames(handle).len(), 2);
+
+    // A later blocking retry collects the response too
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 4, 3));
+    set_nonblocking(handle, false).unwrap();
+    assert_eq!(read_counter(handle, 1234).unwrap(), 3);
+    assert_eq!(take_tx_frames(handle).len(), 1);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_backup_to_buffer() {
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 1));
+    for pass in 0..2 {
+        let transaction_id = FIRST_TRANSACTION_ID + pass * 4;
+        if pass > 0 {
+            push_response(handle, object_count_response(transaction_id, 1));
+        }
+        push_response(handle, enumerate_objects_response(transaction_id + 1, &[1]));
+        push_response(handle, object_info_response(transaction_id + 2, 2));
+        push_response(
+            handle,
+            read_data_response(transaction_id + 3, true, &[0xA, 0xB]),
+        );
+    }
+    let expected = backup::encode(&[backup::BackupRecord::Data(1, vec![0xA, 0xB])]).unwrap();
+
+    let mut used_len = 0;
+    let mut buffer = vec![0u8; 4];
+    assert_eq!(
+        crate::cpc_nvm3_backup_to_buffer(handle, buffer.as_mut_ptr(), buffer.len(), &mut used_len),
+        CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL as i32
+    );
+    assert_eq!(used_len, expected.len());
+    assert_eq!(buffer, vec![0u8; 4]);
+
+    let mut buffer = vec![0u8; used_len];
+    assert_eq!(
+        backup_to_buffer(handle, &mut buffer, &mut used_len).unwrap(),
+        0
+    );
+    assert_eq!(buffer, expected);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_backup_link_error() {
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 2));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[1, 2]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 2));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &[0xA, 0xB]),
+    );
+    push_read_error(handle, std::io::ErrorKind::ConnectionReset);
+
+    // The lost connection is not an object to skip, no partial backup is reported
+    let mut used_len = 0;
+    let mut buffer = vec![0u8; 64];
+    assert_error_code(
+        backup_to_buffer(handle, &mut buffer, &mut used_len),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    assert_eq!(used_len, 0);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_backup_round_trip() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 4, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 2));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[1, 2]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 2));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &[0xA, 0xB]),
+    );
+    push_response(handle, counter_info_response);
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 5, 42));
+
+    let path = std::env::temp_dir().join(format!("cpc_nvm3_backup_{}", std::process::id()));
+    assert_eq!(backup_to_file(handle, &path).unwrap(), 0);
+
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 6, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 7, 0));
+    assert_eq!(restore_from_file(handle, &path).unwrap(), 2);
+
+    // Any flipped byte is caught before writing to the secondary
+    let backup = std::fs::read(&path).unwrap();
+    for index in [0, 4, backup.len() / 2, backup.len() - 1] {
+        let mut corrupted = backup.clone();
+        corrupted[index] ^= 0xFF;
+        std::fs::write(&path, corrupted).unwrap();
+        assert_error_code(
+            restore_from_file(handle, &path),
+            CpcNvm3ErrorCodes::CPC_NVM3_BACKUP_CORRUPT,
+        );
+    }
+
+    std::fs::remove_file(&path).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_link_stats() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+    assert_eq!(get_link_stats(handle).unwrap(), (0, false));
+
+    push_read_error(handle, std::io::ErrorKind::ConnectionReset);
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    assert_eq!(get_link_stats(handle).unwrap(), (1, true));
+
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 1, 42));
+    assert_eq!(read_counter(handle, 1234).unwrap(), 42);
+    assert_eq!(get_link_stats(handle).unwrap(), (1, false));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_max_reassembly() {
+    let fragment = [0x5A; 200];
+
+    // By default a read is bounded by the maximum object size
+    let handle = prepare_test(read_data_response(FIRST_TRANSACTION_ID, false, &fragment));
+    for _ in 0..2 {
+        push_response(
+            handle,
+            read_data_response(FIRST_TRANSACTION_ID, false, &fragment),
+        );
+    }
+    let mut buffer = [0u8; libcpc_mock::CPC_NVM3_MOCK_MAX_OBJECT_SIZE as usize];
+    let mut data_size = 0;
+    let err = read_data(handle, 1234, &mut buffer, &mut data_size).unwrap_err();
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_FAILURE);
+    assert!(err.to_string().contains("more than 512 bytes"));
+
+    set_max_reassembly(handle, 300).unwrap();
+    for _ in 0..2 {
+        push_response(
+            handle,
+            read_data_response(FIRST_TRANSACTION_ID + 1, false, &fragment),
+        );
+    }
+    assert_error_code(
+        read_data(handle, 1234, &mut buffer, &mut data_size),
+        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+    );
+
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 2, false, &fragment),
+    );
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 2, true, &fragment[..100]),
+    );
+    read_data(handle, 1234, &mut buffer, &mut data_size).unwrap();
+    assert_eq!(data_size, 300);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_auto_reconnect_disabled() {
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+    set_auto_reconnect(handle, false).unwrap();
+
+    push_read_error(handle, std::io::ErrorKind::ConnectionReset);
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_CONNECTION_LOST,
+    );
+    assert_eq!(get_link_stats(handle).unwrap(), (0, false));
+
+    set_auto_reconnect(handle, true).unwrap();
+    push_read_error(handle, std::io::ErrorKind::ConnectionReset);
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    assert_eq!(get_link_stats(handle).unwrap(), (1, true));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_auto_reconnect_disabled_lost_endpoint() {
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+    set_auto_reconnect(handle, false).unwrap();
+
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        let cpc_endpoint = instance.cpc_endpoint.take();
+        assert_error_code(
+            instance.write(&vec![0x00]),
+            CpcNvm3ErrorCodes::CPC_NVM3_CONNECTION_LOST,
+        );
+        assert_error_code(instance.read(), CpcNvm3ErrorCodes::CPC_NVM3_CONNECTION_LOST);
+        assert!(instance.cpc_endpoint.is_none());
+        instance.cpc_endpoint = cpc_endpoint;
+    }
+    assert_eq!(get_link_stats(handle).unwrap(), (0, false));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_auto_reconnect_disabled_desync() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID + 7, 1));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 8, 1));
+    set_auto_reconnect(handle, false).unwrap();
+    set_desync_detection(handle, 2, true).unwrap();
+
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_CONNECTION_LOST,
+    );
+    assert_eq!(get_link_stats(handle).unwrap(), (0, false));
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_object() {
+    let data: Vec<u8> = (0..300).map(|byte| byte as u8).collect();
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 2, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_info_response(FIRST_TRANSACTION_ID, 300));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 1, false, &data[..200]),
+    );
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 1, true, &data[200..]),
+    );
+    push_response(handle, counter_info_response);
+
+    let object = read_object(handle, 1234).unwrap();
+    assert_eq!(object.len(), 300);
+    assert_eq!(object, data);
+
+    assert_error_code(
+        read_object(handle, 1235),
+        CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_set_unique_id() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID, 42));
+
+    assert_error_code(
+        set_unique_id(handle, 0),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+
+    // The opened session keeps the unique ID it was opened with
+    set_unique_id(handle, 0xC0FFEE).unwrap();
+    assert_eq!(read_counter(handle, 1234).unwrap(), 42);
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        assert_eq!(instance.unique_id, 0);
+        instance.apply_requested_unique_id();
+        assert_eq!(instance.unique_id, 0xC0FFEE);
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_command_name() {
+    assert_eq!(command_name::<CmdReadData>(), "CmdReadData");
+    assert_eq!(command_name::<GetVersion>(), "GetVersion");
+}
+
+#[test]
+fn test_nvm3_round_trip_average() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    assert_error_code(
+        get_round_trip_average(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_FAILURE,
+    );
+
+    // The handshake commands are accounted for
+    open(handle, "cpcd_0", true).unwrap();
+    assert!(get_round_trip_average(handle).is_ok());
+    let mut average_us = u32::MAX;
+    assert_eq!(
+        crate::cpc_nvm3_get_round_trip_average(handle, &mut average_us),
+        0
+    );
+    assert_ne!(average_us, u32::MAX);
+
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        instance.round_trip_average_us = None;
+        instance.record_round_trip(800);
+        instance.record_round_trip(0);
+    }
+    assert_eq!(
+        get_round_trip_average(handle).unwrap(),
+        Duration::from_micros(700)
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_round_trip_average_fragmented_response() {
+    // An average far above any mock round trip tells how many samples were recorded:
+    // one sample takes it to 7/8 of its value, a second one to 49/64
+    const AVERAGE_US: u64 = 8_000_000_000;
+
+    let handle = prepare_test(read_data_response(FIRST_TRANSACTION_ID, false, &[0x5A; 16]));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID, true, &[0xA5; 16]),
+    );
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        instance.round_trip_average_us = Some(AVERAGE_US);
+    }
+
+    let mut buffer = [0u8; 32];
+    let mut data_size = 0;
+    read_data(handle, 1234, &mut buffer, &mut data_size).unwrap();
+    assert_eq!(data_size, 32);
+
+    let average_us = get_round_trip_average(handle).unwrap().as_micros() as u64;
+    assert!(average_us >= AVERAGE_US / 8 * 7);
+    assert!(average_us < AVERAGE_US);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_data_last_fragment() {
+    const FRAGMENT_SIZE: usize = 16;
+    const LAST_FRAG_OFFSET: usize = 14;
+    const DATA_OFFSET: usize = 15;
+
+    init_test_logger();
+
+    let handle = init().unwrap();
+    open(handle, "cpcd_0", true).unwrap();
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        instance.maximum_write_fragment_size = Some(FRAGMENT_SIZE as u16);
+    }
+    // Drop the frames of the handshake
+    take_tx_frames(handle);
+
+    let mut transaction_id = FIRST_TRANSACTION_ID;
+    for (length, expected_fragments) in [
+        (FRAGMENT_SIZE, 1),
+        (2 * FRAGMENT_SIZE, 2),
+        (2 * FRAGMENT_SIZE + 1, 3),
+    ] {
+        for _ in 0..expected_fragments {
+            push_response(handle, sl_status_response(transaction_id, 0));
+            transaction_id += 1;
+        }
+        let data: Vec<u8> = (0..length).map(|byte| byte as u8).collect();
+        write_data(handle, 1234, &data).unwrap();
+
+        let frames = take_tx_frames(handle);
+        assert_eq!(frames.len(), expected_fragments);
+        let last_frags: Vec<u8> = frames.iter().map(|frame| frame[LAST_FRAG_OFFSET]).collect();
+        let mut expected_last_frags = vec![0; expected_fragments - 1];
+        expected_last_frags.push(1);
+        assert_eq!(last_frags, expected_last_frags);
+
+        // No empty fragment is sent and the fragments add up to the data
+        assert!(frames.iter().all(|frame| frame.len() > DATA_OFFSET));
+        let written: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame[DATA_OFFSET..].to_vec())
+            .collect();
+        assert_eq!(written, data);
+    }
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_session_info() {
+    let handle = init().unwrap();
+    assert_error_code(
+        get_session_info(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
+    );
+
+    open(handle, "cpcd_0", true).unwrap();
+    let mut session_info = std::mem::MaybeUninit::<CpcNvm3SessionInfo>::zeroed();
+    assert_eq!(
+        crate::cpc_nvm3_get_session_info(handle, session_info.as_mut_ptr()),
+        0
+    );
+    let session_info = unsafe { session_info.assume_init() };
+    assert_eq!(
+        session_info.secondary_version,
+        [
+            CPC_NVM3_MAJOR_VERSION,
+            CPC_NVM3_MINOR_VERSION,
+            CPC_NVM3_PATCH_VERSION
+        ]
+    );
+    assert_eq!(session_info.limits, get_limits(handle).unwrap());
+    assert_eq!(
+        session_info.features,
+        crate::CPC_NVM3_FEATURE_MAX_OBJECT_SIZE
+    );
+    let name = unsafe { std::ffi::CStr::from_ptr(session_info.cpcd_instance_name.as_ptr()) };
+    assert_eq!(name.to_str().unwrap(), "cpcd_0");
+
+    close(handle).unwrap();
+    assert_error_code(
+        get_session_info(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
+    );
+    deinit(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_instance_name() {
+    let handle = init().unwrap();
+    assert_error_code(
+        get_instance_name(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN,
+    );
+
+    open(handle, "cpcd_0", true).unwrap();
+    assert_eq!(get_instance_name(handle).unwrap(), "cpcd_0");
+
+    let mut buffer = [0 as std::ffi::c_char; 7];
+    assert_eq!(
+        crate::cpc_nvm3_get_instance_name(handle, buffer.as_mut_ptr(), buffer.len()),
+        0
+    );
+    let name = unsafe { std::ffi::CStr::from_ptr(buffer.as_ptr()) };
+    assert_eq!(name.to_str().unwrap(), "cpcd_0");
+    assert_eq!(
+        crate::cpc_nvm3_get_instance_name(handle, buffer.as_mut_ptr(), buffer.len() - 1),
+        CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL as i32
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_typed_errors() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::KeyNotFound));
+
+    let err = read_counter(handle, 1234).unwrap_err();
+    assert!(matches!(err, CpcNvm3Error::InvalidKey(1234)));
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY);
+
+    // The size the buffer needs is queried when the secondary rejects the read
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::ReadDataSize),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 12));
+    let mut buffer = [0u8; 4];
+    let mut data_size = 0;
+    assert!(matches!(
+        read_data(handle, 1234, &mut buffer, &mut data_size),
+        Err(CpcNvm3Error::BufferTooSmall { needed: 12, got: 4 })
+    ));
+
+    let err = CpcNvm3Error::BufferTooSmall { needed: 4, got: 2 };
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_BUFFER_TOO_SMALL);
+    assert_eq!(
+        CpcNvm3Error::Timeout.code(),
+        CpcNvm3ErrorCodes::CPC_NVM3_TIMEOUT
+    );
+    assert_eq!(
+        CpcNvm3Error::NotOpen.code(),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_OPEN
+    );
+
+    close(handle).unwrap();
+    assert!(matches!(close(handle), Err(CpcNvm3Error::NotOpen)));
+    assert!(matches!(get_timeout(handle), Err(CpcNvm3Error::NotOpen)));
+    deinit(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_for_each_object_key() {
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 3));
+    // The second key is split across the first two fragments
+    let mut first_fragment = enumerate_objects_fragment(FIRST_TRANSACTION_ID + 1, false, &[1, 2]);
+    let split_key = first_fragment.split_off(first_fragment.len() - 2);
+    first_fragment[1] -= 2; // len
+    let mut second_fragment = enumerate_objects_fragment(FIRST_TRANSACTION_ID + 1, false, &[]);
+    second_fragment.extend_from_slice(&split_key);
+    second_fragment[1] += 2; // len
+    push_response(handle, first_fragment);
+    push_response(handle, second_fragment);
+    push_response(
+        handle,
+        enumerate_objects_fragment(FIRST_TRANSACTION_ID + 1, true, &[3]),
+    );
+
+    let object_count = get_object_count(handle).unwrap();
+    let mut keys = vec![];
+    for_each_object_key(handle, |key| {
+        keys.push(key);
+        true
+    })
+    .unwrap();
+    assert_eq!(keys, vec![1, 2, 3]);
+    assert_eq!(keys.len(), object_count as usize);
+
+    // Stopping early drops the remaining fragments
+    push_response(
+        handle,
+        enumerate_objects_fragment(FIRST_TRANSACTION_ID + 2, false, &[1]),
+    );
+    push_response(
+        handle,
+        enumerate_objects_fragment(FIRST_TRANSACTION_ID + 2, true, &[2]),
+    );
+    let mut visited = 0;
+    for_each_object_key(handle, |_| {
+        visited += 1;
+        false
+    })
+    .unwrap();
+    assert_eq!(visited, 1);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_set_timeout_invalid() {
+    let handle = prepare_test(vec![]);
+
+    for (seconds, microseconds) in [(-1, 0), (0, -1), (0, 1_000_000), (1, 1_000_000), (0, 0)] {
+        assert_error_code(
+            set_timeout(handle, seconds, microseconds),
+            CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+        );
+    }
+    set_timeout(handle, 0, 999_999).unwrap();
+    set_timeout(handle, 1, 0).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_list_objects_storage_errors() {
+    let handle = prepare_test(enumerate_objects_fragment(
+        FIRST_TRANSACTION_ID,
+        false,
+        &[1],
+    ));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID, ECode::StorageFull),
+    );
+    push_response(
+        handle,
+        enumerate_objects_fragment(FIRST_TRANSACTION_ID + 1, false, &[1]),
+    );
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::NvmAccess),
+    );
+
+    let mut keys = [0; 4];
+    let mut object_count = 0;
+    assert_error_code(
+        list_objects(handle, &mut keys, &mut object_count),
+        CpcNvm3ErrorCodes::CPC_NVM3_STORAGE_FULL,
+    );
+    assert_error_code(
+        list_objects(handle, &mut keys, &mut object_count),
+        CpcNvm3ErrorCodes::CPC_NVM3_IO_ERROR,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_transaction_id_seed() {
+    const TRANSACTION_ID_OFFSET: usize = 7;
+
+    let handle = prepare_test(counter_response(0x40, 1));
+    push_response(handle, counter_response(0x41, 2));
+    take_tx_frames(handle);
+
+    set_transaction_id_seed(handle, 0x40).unwrap();
+    assert_eq!(read_counter(handle, 1234).unwrap(), 1);
+    assert_eq!(read_counter(handle, 1234).unwrap(), 2);
+
+    let transaction_ids: Vec<u8> = take_tx_frames(handle)
+        .iter()
+        .map(|frame| frame[TRANSACTION_ID_OFFSET])
+        .collect();
+    assert_eq!(transaction_ids, vec![0x40, 0x41]);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_object_count_empty_storage() {
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 0));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::KeyNotFound),
+    );
+
+    assert_eq!(get_object_count(handle).unwrap(), 0);
+    // A KeyNotFound from the secondary is not taken for an empty storage
+    let mut object_count = u16::MAX;
+    assert_eq!(
+        crate::cpc_nvm3_get_object_count(handle, &mut object_count),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY as i32
+    );
+    assert_eq!(object_count, u16::MAX);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_get_object_count_by_type() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 3, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 3));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[1, 2, 3]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 2));
+    push_response(handle, counter_info_response);
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 4, 8));
+
+    assert_eq!(
+        get_object_count_by_type(handle, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA).unwrap(),
+        2
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_use_after_deinit() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    assert!(crate::cpc_nvm3_handle_is_valid(handle));
+    deinit(handle).unwrap();
+    assert!(!crate::cpc_nvm3_handle_is_valid(handle));
+
+    let err = write_data(handle, 1234, &[0x1, 0x2]).unwrap_err();
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_NOT_INITIALIZED);
+    assert!(err.to_string().contains("cpc_nvm3_deinit"));
+}
+
+#[test]
+fn test_nvm3_secondary_not_ready() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::NotOpened));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::NotOpened),
+    );
+
+    assert_error_code(
+        write_data(handle, 1234, &[0x1, 0x2]),
+        CpcNvm3ErrorCodes::CPC_NVM3_SECONDARY_NOT_READY,
+    );
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_SECONDARY_NOT_READY,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_secondary_rejected_parameter() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::Parameter));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::Parameter),
+    );
+
+    assert_error_code(
+        write_data(handle, 1234, &[0x1, 0x2]),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_object_size_not_supported() {
+    let handle = prepare_test(ecode_response(
+        FIRST_TRANSACTION_ID,
+        ECode::ObjectSizeNotSupported,
+    ));
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 1, ECode::ObjectSizeNotSupported),
+    );
+
+    assert_error_code(
+        write_data(handle, 1234, &[0x1, 0x2]),
+        CpcNvm3ErrorCodes::CPC_NVM3_OBJECT_TOO_LARGE,
+    );
+    assert_error_code(
+        write_counter(handle, 1234, 7),
+        CpcNvm3ErrorCodes::CPC_NVM3_OBJECT_TOO_LARGE,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_open_with_retry() {
+    init_test_logger();
+
+    let handle = init().unwrap();
+    libcpc_mock::fail_next_inits(2);
+    assert_error_code(
+        open_with_retry(handle, "cpcd_0", true, 2, Duration::from_millis(1)),
+        CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR,
+    );
+
+    libcpc_mock::fail_next_inits(1);
+    open_with_retry(handle, "cpcd_0", true, 2, Duration::from_millis(1)).unwrap();
+    assert_error_code(
+        open_with_retry(handle, "cpcd_0", true, 0, Duration::from_millis(1)),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_data_oneshot() {
+    init_test_logger();
+
+    libcpc_mock::push_rx_on_next_open(read_data_response(FIRST_TRANSACTION_ID, true, &[0x1, 0x2]));
+    let mut buffer = [0u8; 4];
+    let mut object_size = 0;
+    let instance_name = std::ffi::CString::new("cpcd_0").unwrap();
+    assert_eq!(
+        crate::cpc_nvm3_read_data_oneshot(
+            instance_name.as_ptr(),
+            1234,
+            buffer.as_mut_ptr() as *mut std::ffi::c_void,
+            buffer.len() as u16,
+            &mut object_size,
+        ),
+        0
+    );
+    assert_eq!(&buffer[..object_size as usize], &[0x1, 0x2]);
+
+    libcpc_mock::push_rx_on_next_open(ecode_response(FIRST_TRANSACTION_ID, ECode::KeyNotFound));
+    assert_error_code(
+        read_data_oneshot("cpcd_0", 1234, &mut buffer, &mut object_size),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
+    );
+
+    libcpc_mock::fail_next_inits(1);
+    assert_error_code(
+        read_data_oneshot("cpcd_0", 1234, &mut buffer, &mut object_size),
+        CpcNvm3ErrorCodes::CPC_NVM3_CPC_ENDPOINT_ERROR,
+    );
+}
+
+#[test]
+fn test_nvm3_create_counter() {
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::KeyNotFound));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 4));
+    take_tx_frames(handle);
+
+    create_counter(handle, 1234, 7).unwrap();
+    assert_error_code(
+        create_counter(handle, 1234, 7),
+        CpcNvm3ErrorCodes::CPC_NVM3_ALREADY_EXISTS,
+    );
+    // Only the first call wrote the counter
+    assert_eq!(take_tx_frames(handle).len(), 3);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_update_counter() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 1, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(ecode_response(FIRST_TRANSACTION_ID, ECode::KeyNotFound));
+    push_response(handle, counter_info_response);
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 2, 0));
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 3, 2));
+
+    assert_error_code(
+        update_counter(handle, 1234, 7),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_OBJECT_KEY,
+    );
+    update_counter(handle, 1234, 7).unwrap();
+    assert_error_code(
+        update_counter(handle, 1234, 7),
+        CpcNvm3ErrorCodes::CPC_NVM3_WRONG_OBJECT_TYPE,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_data_bytes_written() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 2, 0));
+    push_response(
+        handle,
+        sl_status_response(FIRST_TRANSACTION_ID + 3, SlStatus::Busy as u32),
+    );
+    push_response(
+        handle,
+        sl_status_response(FIRST_TRANSACTION_ID + 4, SlStatus::Busy as u32),
+    );
+
+    assert_eq!(write_data(handle, 1234, &[0x1, 0x2, 0x3]).unwrap(), 3);
+
+    let data: [u8; 4] = [0x1, 0x2, 0x3, 0x4];
+    let mut bytes_written = 0;
+    let status = crate::cpc_nvm3_write_data_ex(
+        handle,
+        1234,
+        data.as_ptr(),
+        data.len() as u16,
+        std::ptr::null_mut(),
+        &mut bytes_written,
+    );
+    assert_eq!(status, 0);
+    assert_eq!(bytes_written, 4);
+
+    // The batch stops at its second entry, only the first one was written
+    let mut batch_bytes_written = [u16::MAX; 3];
+    let results = write_data_batch(
+        handle,
+        &[(1, &data[..2]), (2, &data), (3, &data)],
+        &mut batch_bytes_written,
+    )
+    .unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(*results[0].as_ref().unwrap(), 2);
+    assert_eq!(
+        results[1].as_ref().unwrap_err().code(),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN
+    );
+    assert_eq!(batch_bytes_written, [2, 0, 0]);
+
+    let status = crate::cpc_nvm3_write_data_ex(
+        handle,
+        1234,
+        data.as_ptr(),
+        data.len() as u16,
+        std::ptr::null_mut(),
+        &mut bytes_written,
+    );
+    assert_eq!(status, CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN as i32);
+    assert_eq!(bytes_written, 0);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_data_bytes_acknowledged() {
+    const FRAGMENT_SIZE: usize = 16;
+
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    push_response(
+        handle,
+        sl_status_response(FIRST_TRANSACTION_ID + 1, SlStatus::Busy as u32),
+    );
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 2, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 3, 0));
+    push_response(
+        handle,
+        sl_status_response(FIRST_TRANSACTION_ID + 4, SlStatus::Busy as u32),
+    );
+    {
+        let instance_arc_mutex = get_instance(handle).unwrap();
+        let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+        instance.maximum_write_fragment_size = Some(FRAGMENT_SIZE as u16);
+    }
+
+    // Only the first fragment was acknowledged before the write failed
+    let data: Vec<u8> = (0..2 * FRAGMENT_SIZE).map(|byte| byte as u8).collect();
+    let mut bytes_written = 0;
+    let status = crate::cpc_nvm3_write_data_ex(
+        handle,
+        1234,
+        data.as_ptr(),
+        data.len() as u16,
+        std::ptr::null_mut(),
+        &mut bytes_written,
+    );
+    assert_eq!(status, CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN as i32);
+    assert_eq!(bytes_written, FRAGMENT_SIZE as u16);
+
+    // The second entry of the batch fails on its second fragment
+    let keys: [cpc_nvm3_object_key_t; 3] = [1, 2, 3];
+    let data_ptrs = [data.as_ptr(); 3];
+    let data_lengths = [FRAGMENT_SIZE as u16, data.len() as u16, data.len() as u16];
+    let mut results = [1; 3];
+    let mut batch_bytes_written = [u16::MAX; 3];
+    let status = crate::cpc_nvm3_write_data_batch(
+        handle,
+        keys.as_ptr(),
+        data_ptrs.as_ptr(),
+        data_lengths.as_ptr(),
+        keys.len() as u16,
+        results.as_mut_ptr(),
+        batch_bytes_written.as_mut_ptr(),
+    );
+    let try_again = CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN as i32;
+    assert_eq!(status, try_again);
+    assert_eq!(results, [0, try_again, try_again]);
+    assert_eq!(
+        batch_bytes_written,
+        [FRAGMENT_SIZE as u16, FRAGMENT_SIZE as u16, 0]
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_read_counter_signed() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID, 0xFFFFFFFF));
+    push_response(
+        handle,
+        counter_response(FIRST_TRANSACTION_ID + 1, 0x80000000),
+    );
+
+    assert_eq!(read_counter_signed(handle, 1234).unwrap(), -1);
+    let mut value = 0;
+    assert_eq!(
+        crate::cpc_nvm3_read_counter_signed(handle, 1234, &mut value),
+        0
+    );
+    assert_eq!(value, i32::MIN);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_interrupt() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID, 7));
+    // Nothing is in flight, the interrupt must not cancel the next operation
+    interrupt(handle).unwrap();
+    assert_eq!(read_counter(handle, 1234).unwrap(), 7);
+
+    push_read_timeout(handle, Duration::from_millis(500));
+    let reader = std::thread::spawn(move || read_counter(handle, 1234));
+    // Let the reader take the lock and block in its read
+    while !reader.is_finished()
+        && try_lock_instance(&get_instance(handle).unwrap())
+            .unwrap()
+            .is_some()
+    {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    interrupt(handle).unwrap();
+    assert_error_code(
+        reader.join().unwrap(),
+        CpcNvm3ErrorCodes::CPC_NVM3_CANCELLED,
+    );
+    finalize_test(handle).unwrap();
+
+    assert_error_code(
+        interrupt(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_NOT_INITIALIZED,
+    );
+}
+
+#[test]
+fn test_nvm3_interrupt_received_frame() {
+    let response = counter_response(FIRST_TRANSACTION_ID, 7);
+    let handle = prepare_test(response.clone());
+    push_read_timeout(handle, Duration::from_millis(1));
+
+    let instance_arc_mutex = get_instance(handle).unwrap();
+    let mut instance = lock_instance(&instance_arc_mutex).unwrap();
+    // A frame that was received is returned, the interrupt waits for a read that times out
+    instance.interrupted.store(true, Ordering::SeqCst);
+    assert_eq!(instance.read().unwrap(), response);
+    assert_error_code(instance.read(), CpcNvm3ErrorCodes::CPC_NVM3_CANCELLED);
+    assert!(!instance.interrupted.load(Ordering::SeqCst));
+    drop(instance);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_export_all_to_dir() {
+    let mut counter_info_response = object_info_response(FIRST_TRANSACTION_ID + 4, 4);
+    counter_info_response[8] = 0x01; // object_type counter
+
+    let handle = prepare_test(object_count_response(FIRST_TRANSACTION_ID, 3));
+    push_response(
+        handle,
+        enumerate_objects_response(FIRST_TRANSACTION_ID + 1, &[1, 2, 3]),
+    );
+    push_response(handle, object_info_response(FIRST_TRANSACTION_ID + 2, 2));
+    push_response(
+        handle,
+        read_data_response(FIRST_TRANSACTION_ID + 3, true, &[0xA, 0xB]),
+    );
+    push_response(handle, counter_info_response);
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 5, 42));
+    // The last object vanished since the enumeration, it is skipped
+    push_response(
+        handle,
+        ecode_response(FIRST_TRANSACTION_ID + 6, ECode::KeyNotFound),
+    );
+
+    let dir = std::env::temp_dir().join(format!("cpc_nvm3_export_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    assert_eq!(export_all_to_dir(handle, &dir).unwrap(), 2);
+    assert_eq!(std::fs::read(dir.join("1.bin")).unwrap(), vec![0xA, 0xB]);
+    assert_eq!(
+        std::fs::read(dir.join("2.cnt")).unwrap(),
+        42u32.to_le_bytes().to_vec()
+    );
+    assert!(!dir.join("3.bin").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_error_code(
+        export_all_to_dir(handle, &dir),
+        CpcNvm3ErrorCodes::CPC_NVM3_INVALID_ARG,
+    );
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_protocol_overhead() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    take_tx_frames(handle);
+
+    let mut write_overhead = 0;
+    let mut read_overhead = 0;
+    assert_eq!(
+        crate::cpc_nvm3_get_protocol_overhead(&mut write_overhead, &mut read_overhead),
+        0
+    );
+    assert_eq!(write_overhead, 15);
+    assert_eq!(read_overhead, 9);
+
+    // A full fragment fits in the CPC frame
+    let limits = get_limits(handle).unwrap();
+    let data = vec![0x5A; limits.max_fragment_size as usize];
+    assert_eq!(write_data_ex(handle, 1234, &data).unwrap(), 1);
+    let frames = take_tx_frames(handle);
+    assert_eq!(
+        frames[0].len(),
+        (limits.max_fragment_size + write_overhead) as usize
+    );
+    assert!(frames[0].len() <= limits.cpc_max_write as usize);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_too_many_mismatched_responses() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID - 1, 0));
+    for _ in 0..CPC_NVM3_MAX_MISMATCHED_RESPONSES {
+        push_response(handle, sl_status_response(FIRST_TRANSACTION_ID - 1, 0));
+    }
+
+    let err = delete_object(handle, 1234).unwrap_err();
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_FAILURE);
+    assert!(err.to_string().contains("too many mismatched responses"));
+    // Along with the reason the last response was dropped
+    assert!(matches!(
+        err,
+        CpcNvm3Error::TooManyMismatchedResponses {
+            last_error: ProtocolError::InvalidTransactionId(FIRST_TRANSACTION_ID, received_id),
+            ..
+        } if received_id == FIRST_TRANSACTION_ID - 1
+    ));
+    assert!(err.to_string().contains("invalid transaction id"));
+
+    set_max_mismatched_responses(handle, CPC_NVM3_MAX_MISMATCHED_RESPONSES + 1).unwrap();
+    for _ in 0..=CPC_NVM3_MAX_MISMATCHED_RESPONSES {
+        push_response(handle, sl_status_response(FIRST_TRANSACTION_ID - 1, 0));
+    }
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID + 1, 0));
+    delete_object(handle, 1234).unwrap();
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_write_status_carries_no_key() {
+    // The status of another write has no key to tell it apart, only its transaction ID
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID - 1, 0));
+    push_response(handle, sl_status_response(FIRST_TRANSACTION_ID, 0));
+    take_tx_frames(handle);
+
+    write_data(handle, 1234, &[0x1, 0x2]).unwrap();
+    let frames = take_tx_frames(handle);
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0][7], FIRST_TRANSACTION_ID);
+    assert_eq!(frames[0][8..12], 1234u32.to_le_bytes());
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_strict_parsing() {
+    let mut trailing_counter_response = counter_response(FIRST_TRANSACTION_ID, 42);
+    trailing_counter_response.push(0xFF);
+    trailing_counter_response[1] += 1; // len
+
+    // Extra bytes are ignored by default
+    let handle = prepare_test(trailing_counter_response.clone());
+    assert_eq!(read_counter(handle, 1234).unwrap(), 42);
+
+    set_strict_parsing(handle, true).unwrap();
+    trailing_counter_response[7] = FIRST_TRANSACTION_ID + 1; // transaction_id
+    push_response(handle, trailing_counter_response);
+    let err = read_counter(handle, 1234).unwrap_err();
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_FAILURE);
+    assert!(err.to_string().contains("trailing bytes"));
+
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 2, 7));
+    assert_eq!(read_counter(handle, 1234).unwrap(), 7);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_poisoned_instance_lock() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID, 1));
+
+    let instance_arc_mutex = get_instance(handle).unwrap();
+    let panicked = std::thread::spawn(move || {
+        let _instance = instance_arc_mutex.lock().unwrap();
+        panic!("Poisoning the instance lock");
+    })
+    .join();
+    assert!(panicked.is_err());
+
+    let err = read_counter(handle, 1234).unwrap_err();
+    assert_eq!(err.code(), CpcNvm3ErrorCodes::CPC_NVM3_INTERNAL_POISONED);
+    assert!(err
+        .to_string()
+        .contains("poisoned by a panic in another thread"));
+    let mut value = 0;
+    assert_eq!(
+        crate::cpc_nvm3_read_counter(handle, 1234, &mut value),
+        CpcNvm3ErrorCodes::CPC_NVM3_INTERNAL_POISONED as i32
+    );
+
+    // Only the forced de-init releases a poisoned instance
+    assert_error_code(
+        deinit(handle),
+        CpcNvm3ErrorCodes::CPC_NVM3_INTERNAL_POISONED,
+    );
+    deinit_force(handle).unwrap();
+    assert!(get_instance(handle).is_err());
+}
+
+#[test]
+fn test_nvm3_get_last_transaction_id() {
+    let handle = prepare_test(sl_status_response(FIRST_TRANSACTION_ID, 0));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 1, 1));
+    // The handshake used the IDs before the first transaction
+    assert_eq!(
+        get_last_transaction_id(handle).unwrap(),
+        FIRST_TRANSACTION_ID - 1
+    );
+
+    delete_object(handle, 1234).unwrap();
+    assert_eq!(
+        get_last_transaction_id(handle).unwrap(),
+        FIRST_TRANSACTION_ID
+    );
+    read_counter(handle, 1234).unwrap();
+    let mut transaction_id = 0;
+    assert_eq!(
+        crate::cpc_nvm3_get_last_transaction_id(handle, &mut transaction_id),
+        0
+    );
+    assert_eq!(transaction_id, FIRST_TRANSACTION_ID + 1);
+
+    set_transaction_id_seed(handle, 0xFF).unwrap();
+    push_response(handle, sl_status_response(0xFF, 0));
+    push_response(handle, sl_status_response(0x00, 0));
+    delete_object(handle, 1234).unwrap();
+    assert_eq!(get_last_transaction_id(handle).unwrap(), 0xFF);
+    delete_object(handle, 1234).unwrap();
+    assert_eq!(get_last_transaction_id(handle).unwrap(), 0x00);
+    finalize_test(handle).unwrap();
+}
+
+#[test]
+fn test_nvm3_transaction_id_desync() {
+    let handle = prepare_test(counter_response(FIRST_TRANSACTION_ID + 7, 1));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 8, 1));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID, 42));
+
+    // By default a desynchronization is only logged
+    set_desync_detection(handle, 2, false).unwrap();
+    assert_eq!(read_counter(handle, 1234).unwrap(), 42);
+    assert_eq!(get_link_stats(handle).unwrap(), (0, false));
+
+    set_desync_detection(handle, 3, true).unwrap();
+    for offset in [7, 8, 9] {
+        push_response(handle, counter_response(FIRST_TRANSACTION_ID + offset, 1));
+    }
+    assert_error_code(
+        read_counter(handle, 1234),
+        CpcNvm3ErrorCodes::CPC_NVM3_TRY_AGAIN,
+    );
+    assert_eq!(get_link_stats(handle).unwrap(), (1, true));
+
+    // A frame dropped for another reason breaks the run of mismatches
+    let mut version_response = counter_response(FIRST_TRANSACTION_ID + 2, 1);
+    version_response[0] = 0x01;
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 7, 1));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 8, 1));
+    push_response(handle, version_response);
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 9, 1));
+    push_response(handle, counter_response(FIRST_TRANSACTION_ID + 2, 7));
+    assert_eq!(read_counter(handle, 1234).unwrap(), 7);
     finalize_test(handle).unwrap();
 }
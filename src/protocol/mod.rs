@@ -18,8 +18,7 @@
 mod tests;
 
 use crate::CpcNvm3ObjectType;
-use nom::error::{Error, ErrorKind};
-use nom::Err;
+use nom::error::Error;
 use num_enum::TryFromPrimitive;
 use std::fmt;
 use std::num::NonZeroUsize;
@@ -80,12 +79,23 @@ pub enum SlStatus {
     Unknown = u32::MAX,
 }
 
-impl From<u8> for CpcNvm3ObjectType {
-    fn from(value: u8) -> Self {
+// `From<u8>` can't live next to `TryFrom<u8>`, the lenient mapping the C boundary relies on
+// is kept as a constructor instead. An unknown object type is a value of its own in C.
+impl CpcNvm3ObjectType {
+    pub fn from_lenient(value: u8) -> Self {
+        CpcNvm3ObjectType::try_from(value)
+            .unwrap_or(CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN)
+    }
+}
+
+impl TryFrom<u8> for CpcNvm3ObjectType {
+    type Error = ProtocolError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0 => CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA,
-            1 => CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER,
-            _ => CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN,
+            0 => Ok(CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_DATA),
+            1 => Ok(CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER),
+            _ => Err(ProtocolError::InvalidObjectType(value)),
         }
     }
 }
@@ -164,31 +174,32 @@ pub enum ProtocolError {
     InvalidUniqueId(u32, u32),
     #[error("Received a response with invalid len: expected={0}, received={1}")]
     InvalidResponseLen(usize, u16),
+    #[error("Received an unknown object type: {0}")]
+    InvalidObjectType(u8),
+    #[error("Received a {0:?} response with {1} unexpected trailing bytes")]
+    TrailingBytes(SecondaryCmd, usize),
 }
 
-#[derive(
-    serde_repr::Serialize_repr,
-    serde_repr::Deserialize_repr,
-    num_enum::TryFromPrimitive,
-    PartialEq,
-    Copy,
-    Clone,
-    Debug,
-)]
+#[derive(num_enum::TryFromPrimitive, PartialEq, Copy, Clone, Debug)]
 #[repr(u8)]
 enum HostCmd {
-    CmdGetVersion = 0x00,
-    CmdNoop = 0x03,
-    CmdPropValueGet = 0x04,
-    CmdWriteData = 0x06,
-    CmdReadData = 0x08,
-    CmdGetObjectInfo = 0x0A,
-    CmdReadCounter = 0x0C,
-    CmdWriteCounter = 0x0E,
-    CmdIncrementCounter = 0x0F,
-    CmdDeleteObject = 0x10,
-    CmdEnumerateObjects = 0x11,
-    CmdGetObjectCount = 0x13,
+    CmdGetVersion = crate::CPC_NVM3_COMMAND_GET_VERSION,
+    CmdNoop = crate::CPC_NVM3_COMMAND_NOOP,
+    CmdPropValueGet = crate::CPC_NVM3_COMMAND_PROP_VALUE_GET,
+    CmdWriteData = crate::CPC_NVM3_COMMAND_WRITE_DATA,
+    CmdReadData = crate::CPC_NVM3_COMMAND_READ_DATA,
+    CmdGetObjectInfo = crate::CPC_NVM3_COMMAND_GET_OBJECT_INFO,
+    CmdReadCounter = crate::CPC_NVM3_COMMAND_READ_COUNTER,
+    CmdWriteCounter = crate::CPC_NVM3_COMMAND_WRITE_COUNTER,
+    CmdIncrementCounter = crate::CPC_NVM3_COMMAND_INCREMENT_COUNTER,
+    CmdDeleteObject = crate::CPC_NVM3_COMMAND_DELETE_OBJECT,
+    CmdEnumerateObjects = crate::CPC_NVM3_COMMAND_ENUMERATE_OBJECTS,
+    CmdGetObjectCount = crate::CPC_NVM3_COMMAND_GET_OBJECT_COUNT,
+}
+
+/// Whether `command_id` identifies a command the host sends to the secondary
+pub fn is_host_command(command_id: u8) -> bool {
+    HostCmd::try_from(command_id).is_ok()
 }
 
 #[derive(
@@ -221,15 +232,6 @@ pub enum PropertyType {
     Unknown = 0xFF,
 }
 
-impl serde::Serialize for PropertyType {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_u8(*self as u8)
-    }
-}
-
 impl fmt::Display for PropertyValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -245,7 +247,7 @@ pub enum PropertyValue {
     Unknown,
 }
 
-#[derive(serde::Serialize, Copy)]
+#[derive(Copy)]
 #[repr(C, packed)]
 pub struct Header<T: Copy + Clone + std::fmt::Debug> {
     pub cmd: T,
@@ -324,6 +326,19 @@ impl<T: Copy + Clone + std::fmt::Debug> Header<T> {
     }
 }
 
+impl Header<HostCmd> {
+    // The fields are written one by one, in the order of the wire format, every integer
+    // being little endian whatever the host
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(std::mem::size_of::<Self>());
+        bytes.push(self.cmd as u8);
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes.extend_from_slice(&self.unique_id.to_le_bytes());
+        bytes.push(self.transaction_id.value);
+        bytes
+    }
+}
+
 impl<T: Copy + std::fmt::Debug> Clone for Header<T> {
     fn clone(&self) -> Self {
         Self {
@@ -335,6 +350,88 @@ impl<T: Copy + std::fmt::Debug> Clone for Header<T> {
     }
 }
 
+// The lengths sent and expected on the wire are derived from the size of the packed structs
+// below, a change of their layout must not go unnoticed. `ObjectInfoIs` is not listed, its
+// object type takes 4 bytes in memory for a single byte on the wire, and the structs holding
+// a `Vec` have no fixed size.
+const _: () = assert!(std::mem::size_of::<TransactionId>() == 1);
+const _: () = assert!(std::mem::size_of::<Header<HostCmd>>() == 8);
+const _: () = assert!(std::mem::size_of::<Header<SecondaryCmd>>() == 8);
+const _: () = assert!(std::mem::size_of::<GetVersion>() == 8);
+const _: () = assert!(std::mem::size_of::<CmdNoop>() == 8);
+const _: () = assert!(std::mem::size_of::<PropValueGet>() == 9);
+const _: () = assert!(std::mem::size_of::<CmdReadData>() == 14);
+const _: () = assert!(std::mem::size_of::<CmdGetObjectInfo>() == 12);
+const _: () = assert!(std::mem::size_of::<CmdReadCounter>() == 12);
+const _: () = assert!(std::mem::size_of::<CmdWriteCounter>() == 16);
+const _: () = assert!(std::mem::size_of::<CmdIncrementCounter>() == 12);
+const _: () = assert!(std::mem::size_of::<CmdDeleteObject>() == 12);
+const _: () = assert!(std::mem::size_of::<CmdEnumerateObjects>() == 10);
+const _: () = assert!(std::mem::size_of::<CmdGetObjectCount>() == 8);
+const _: () = assert!(std::mem::size_of::<VersionIs>() == 11);
+const _: () = assert!(std::mem::size_of::<CmdReadDataIsHeader>() == 1);
+const _: () = assert!(std::mem::size_of::<CounterIs>() == 12);
+const _: () = assert!(std::mem::size_of::<ObjectCountIs>() == 10);
+
+// The size of the fields that follow the header in the responses with a fixed layout
+fn fixed_payload_len(cmd: SecondaryCmd) -> Option<usize> {
+    match cmd {
+        // Major, minor and patch version
+        SecondaryCmd::CmdVersionIs => Some(3),
+        // Object type and object size
+        SecondaryCmd::CmdObjectInfoIs => Some(3),
+        // Counter value
+        SecondaryCmd::CmdCounterIs => Some(4),
+        _ => None,
+    }
+}
+
+// The transaction ID is the last byte of the header of every frame, request or response
+const TRANSACTION_ID_OFFSET: usize = std::mem::size_of::<Header<HostCmd>>() - 1;
+
+/// The transaction ID carried by a frame, None if the frame is shorter than a header
+pub fn frame_transaction_id(frame: &[u8]) -> Option<u8> {
+    frame.get(TRANSACTION_ID_OFFSET).copied()
+}
+
+/// A copy of `frame` carrying `transaction_id` instead of its own
+pub fn with_transaction_id(frame: &[u8], transaction_id: u8) -> Vec<u8> {
+    let mut frame = frame.to_vec();
+    if let Some(frame_transaction_id) = frame.get_mut(TRANSACTION_ID_OFFSET) {
+        *frame_transaction_id = transaction_id;
+    }
+    frame
+}
+
+/// Whether two requests only differ by their transaction ID, like a request and its retry
+pub fn is_same_request(request: &[u8], other: &[u8]) -> bool {
+    request.len() == other.len()
+        && request.len() > TRANSACTION_ID_OFFSET
+        && request
+            .iter()
+            .zip(other)
+            .enumerate()
+            .all(|(offset, (byte, other_byte))| {
+                offset == TRANSACTION_ID_OFFSET || byte == other_byte
+            })
+}
+
+/// Check that a response with a fixed layout ends with its last field. The deserializers
+/// ignore any byte past the fields they know, so that a newer firmware can append fields
+/// to a response without breaking this library, which also lets trailing garbage through.
+pub fn check_trailing_bytes(input: &[u8]) -> Result<(), ProtocolError> {
+    let (remaining, header) = deserialize_header(input)
+        .map_err(|err| ProtocolError::DeserializationError(err.to_string()))?;
+    let cmd = header.cmd;
+    match fixed_payload_len(cmd) {
+        Some(payload_len) if remaining.len() > payload_len => Err(ProtocolError::TrailingBytes(
+            cmd,
+            remaining.len() - payload_len,
+        )),
+        _ => Ok(()),
+    }
+}
+
 fn extract_and_validate_header(
     input: &[u8],
     expected_cmd: SecondaryCmd,
@@ -342,11 +439,20 @@ fn extract_and_validate_header(
     expected_transaction_id: u8,
 ) -> Result<(Header<SecondaryCmd>, &[u8]), ProtocolError> {
     let input_len = input.len();
+    let header_len = std::mem::size_of::<Header<SecondaryCmd>>();
+
+    // A truncated frame would underflow the expected length computed below
+    if input_len < header_len {
+        return Err(ProtocolError::DeserializationError(format!(
+            "Received a frame of {} bytes, which is shorter than the {} bytes header",
+            input_len, header_len
+        )));
+    }
 
     let (remaining, header) = deserialize_header(input)
         .map_err(|err| ProtocolError::DeserializationError(err.to_string()))?;
 
-    let expected_len = input_len - std::mem::size_of::<Header<SecondaryCmd>>();
+    let expected_len = input_len - header_len;
 
     header.validate(
         expected_cmd,
@@ -369,12 +475,23 @@ fn parse_status_response(
     Ok(status_code)
 }
 
+// The command ID of a command is the one of the `Header<HostCmd>` it starts with
+macro_rules! header_command_id {
+    () => {
+        fn command_id(&self) -> u8 {
+            self.header.cmd as u8
+        }
+    };
+}
+
 pub trait Command {
     type Response;
     fn parse_response(&self, input: &[u8]) -> Result<Self::Response, ProtocolError>;
+    /// The identifier of the command, one of the `CPC_NVM3_COMMAND_*` values
+    fn command_id(&self) -> u8;
 }
 
-#[derive(serde::Serialize, Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct TransactionId {
     value: u8,
@@ -388,13 +505,13 @@ impl TransactionId {
     }
 }
 
-pub trait Serializer: serde::Serialize {
+pub trait Serializer {
+    /// The command as sent on the wire, header included
+    fn to_bytes(&self) -> Vec<u8>;
+
     fn serialize(&self) -> Result<Vec<u8>, ProtocolError> {
         log::debug!("Serializing");
-        match bincode::serialize(&self) {
-            Ok(bytestream) => Ok(bytestream),
-            Err(err) => Err(ProtocolError::SerializationError(err.to_string())),
-        }
+        Ok(self.to_bytes())
     }
 }
 
@@ -403,7 +520,6 @@ pub enum PropValueGetResponse {
     StatusCode(StatusCode),
 }
 
-#[derive(serde::Serialize)]
 #[repr(C, packed)]
 pub struct PropValueGet {
     header: Header<HostCmd>,
@@ -411,6 +527,7 @@ pub struct PropValueGet {
 }
 impl Command for PropValueGet {
     type Response = PropValueGetResponse;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<PropValueGetResponse, ProtocolError> {
         // Check the very first byte to know which type of response we got
         let (_, cmd) = deserialize_cmd(input).map_err(|e| {
@@ -441,7 +558,13 @@ impl Command for PropValueGet {
     }
 }
 
-impl Serializer for PropValueGet {}
+impl Serializer for PropValueGet {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.push(self.property_type as u8);
+        bytes
+    }
+}
 impl PropValueGet {
     pub fn new(unique_id: u32, transaction_id: &mut u8, property_type: PropertyType) -> Self {
         let len = (std::mem::size_of::<Self>() - std::mem::size_of::<Header<HostCmd>>()) as u16;
@@ -457,14 +580,18 @@ impl PropValueGet {
     }
 }
 
-#[derive(serde::Serialize)]
 #[repr(C, packed)]
 pub struct GetVersion {
     header: Header<HostCmd>,
 }
-impl Serializer for GetVersion {}
+impl Serializer for GetVersion {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.header.to_bytes()
+    }
+}
 impl Command for GetVersion {
     type Response = VersionIs;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<VersionIs, ProtocolError> {
         // Check the very first byte to know which type of response we got
         let (_, cmd) = deserialize_cmd(input).map_err(|e| {
@@ -516,23 +643,14 @@ impl PropValueIs {
             expected_transaction_id,
         )?;
 
-        let result = || -> nom::IResult<&[u8], Self> {
-            let (remaining, property_type) = deserialize_property_type(remaining)?;
-            let (remaining, property_value) = deserialize_property_value(property_type, remaining)?;
-            Ok((
-                remaining,
-                Self {
-                    header,
-                    property_type,
-                    property_value,
-                },
-            ))
-        };
-
-        match result() {
-            Ok(tuple) => Ok(tuple.1),
-            Err(err) => Err(ProtocolError::DeserializationError(err.to_string())),
-        }
+        let (remaining, property_type) = deserialize_property_type(remaining)
+            .map_err(|err| ProtocolError::DeserializationError(err.to_string()))?;
+        let property_value = deserialize_property_value(property_type, remaining)?;
+        Ok(Self {
+            header,
+            property_type,
+            property_value,
+        })
     }
 }
 
@@ -652,7 +770,6 @@ pub enum CmdReadDataResponse {
 }
 
 #[repr(C, packed)]
-#[derive(serde::Serialize)]
 pub struct CmdReadData {
     header: Header<HostCmd>,
     object_key: u32,
@@ -660,6 +777,7 @@ pub struct CmdReadData {
 }
 impl Command for CmdReadData {
     type Response = CmdReadDataResponse;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<CmdReadDataResponse, ProtocolError> {
         // Check the very first byte to know which type of response we got
         let (_, cmd) = deserialize_cmd(input).map_err(|e| {
@@ -711,11 +829,13 @@ impl CmdReadData {
             max_read_size,
         }
     }
-    pub fn serialize(&mut self) -> Result<Vec<u8>, ProtocolError> {
-        match bincode::serialize(&self) {
-            Ok(bytestream) => Ok(bytestream),
-            Err(err) => Err(ProtocolError::SerializationError(err.to_string())),
-        }
+}
+impl Serializer for CmdReadData {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.object_key.to_le_bytes());
+        bytes.extend_from_slice(&self.max_read_size.to_le_bytes());
+        bytes
     }
 }
 
@@ -725,13 +845,13 @@ pub enum CmdEnumerateObjectsResponse {
 }
 
 #[repr(C, packed)]
-#[derive(serde::Serialize)]
 pub struct CmdEnumerateObjects {
     header: Header<HostCmd>,
     max_objects: u16,
 }
 impl Command for CmdEnumerateObjects {
     type Response = CmdEnumerateObjectsResponse;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<CmdEnumerateObjectsResponse, ProtocolError> {
         // Check the very first byte to know which type of response we got
         let (_, cmd) = deserialize_cmd(input).map_err(|e| {
@@ -777,11 +897,12 @@ impl CmdEnumerateObjects {
             max_objects,
         }
     }
-    pub fn serialize(&mut self) -> Result<Vec<u8>, ProtocolError> {
-        match bincode::serialize(&self) {
-            Ok(bytestream) => Ok(bytestream),
-            Err(err) => Err(ProtocolError::SerializationError(err.to_string())),
-        }
+}
+impl Serializer for CmdEnumerateObjects {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.max_objects.to_le_bytes());
+        bytes
     }
 }
 
@@ -856,6 +977,35 @@ impl ObjectInfoIs {
         input: &[u8],
         expected_transaction_id: u8,
         expected_unique_id: u32,
+    ) -> Result<Self, ProtocolError> {
+        Self::deserialize_with(
+            input,
+            expected_transaction_id,
+            expected_unique_id,
+            |object_type| Ok(CpcNvm3ObjectType::from_lenient(object_type)),
+        )
+    }
+
+    /// Like `deserialize`, but an object type the library doesn't know is an error instead
+    /// of `CPC_NVM3_OBJECT_TYPE_UNKNOWN`
+    pub fn deserialize_strict(
+        input: &[u8],
+        expected_transaction_id: u8,
+        expected_unique_id: u32,
+    ) -> Result<Self, ProtocolError> {
+        Self::deserialize_with(
+            input,
+            expected_transaction_id,
+            expected_unique_id,
+            CpcNvm3ObjectType::try_from,
+        )
+    }
+
+    fn deserialize_with(
+        input: &[u8],
+        expected_transaction_id: u8,
+        expected_unique_id: u32,
+        to_object_type: fn(u8) -> Result<CpcNvm3ObjectType, ProtocolError>,
     ) -> Result<Self, ProtocolError> {
         let expected_cmd = SecondaryCmd::CmdObjectInfoIs;
         let (header, remaining) = extract_and_validate_header(
@@ -865,27 +1015,27 @@ impl ObjectInfoIs {
             expected_transaction_id,
         )?;
 
-        let result = || -> nom::IResult<&[u8], Self> {
-            let (remaining, object_type) = deserialize_object_type(remaining)?;
+        let result = || -> nom::IResult<&[u8], (u8, u16)> {
+            let (remaining, object_type) = nom::number::complete::u8(remaining)?;
             let (remaining, object_size) = nom::number::complete::le_u16(remaining)?;
-            Ok((
-                remaining,
-                Self {
-                    header,
-                    object_type,
-                    object_size,
-                },
-            ))
+            Ok((remaining, (object_type, object_size)))
         };
 
         match result() {
-            Ok(tuple) => Ok(tuple.1),
+            Ok((_, (object_type, object_size))) => Ok(Self {
+                header,
+                object_type: to_object_type(object_type)?,
+                object_size,
+            }),
             Err(err) => Err(ProtocolError::DeserializationError(err.to_string())),
         }
     }
 
     pub fn len(&self) -> u16 {
-        std::mem::size_of::<ObjectInfoIs>() as u16
+        // The object type is a C enum in memory but a single byte on the wire
+        (std::mem::size_of::<Header<SecondaryCmd>>()
+            + std::mem::size_of::<u8>()
+            + std::mem::size_of::<u16>()) as u16
     }
 }
 
@@ -921,14 +1071,20 @@ fn parse_response_counter_read_response(
 }
 
 #[repr(C, packed)]
-#[derive(serde::Serialize)]
 pub struct CmdReadCounter {
     header: Header<HostCmd>,
     object_key: u32,
 }
-impl Serializer for CmdReadCounter {}
+impl Serializer for CmdReadCounter {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.object_key.to_le_bytes());
+        bytes
+    }
+}
 impl Command for CmdReadCounter {
     type Response = CmdCounterValueResponse;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<CmdCounterValueResponse, ProtocolError> {
         Ok(parse_response_counter_read_response(
             self.header.unique_id,
@@ -953,16 +1109,23 @@ impl CmdReadCounter {
 }
 
 #[repr(C, packed)]
-#[derive(serde::Serialize)]
 pub struct CmdWriteCounter {
     header: Header<HostCmd>,
     object_key: u32,
     data: u32,
 }
 
-impl Serializer for CmdWriteCounter {}
+impl Serializer for CmdWriteCounter {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.object_key.to_le_bytes());
+        bytes.extend_from_slice(&self.data.to_le_bytes());
+        bytes
+    }
+}
 impl Command for CmdWriteCounter {
     type Response = StatusCode;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<StatusCode, ProtocolError> {
         parse_status_response(
             self.header.transaction_id.value,
@@ -988,14 +1151,20 @@ impl CmdWriteCounter {
 }
 
 #[repr(C, packed)]
-#[derive(serde::Serialize)]
 pub struct CmdIncrementCounter {
     header: Header<HostCmd>,
     object_key: u32,
 }
-impl Serializer for CmdIncrementCounter {}
+impl Serializer for CmdIncrementCounter {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.object_key.to_le_bytes());
+        bytes
+    }
+}
 impl Command for CmdIncrementCounter {
     type Response = CmdCounterValueResponse;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<CmdCounterValueResponse, ProtocolError> {
         Ok(parse_response_counter_read_response(
             self.header.unique_id,
@@ -1070,15 +1239,43 @@ pub enum CmdGetObjectInfoResponse {
 }
 
 #[repr(C, packed)]
-#[derive(serde::Serialize)]
 pub struct CmdGetObjectInfo {
     header: Header<HostCmd>,
     object_key: u32,
 }
-impl Serializer for CmdGetObjectInfo {}
+impl Serializer for CmdGetObjectInfo {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.object_key.to_le_bytes());
+        bytes
+    }
+}
 impl Command for CmdGetObjectInfo {
     type Response = CmdGetObjectInfoResponse;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<CmdGetObjectInfoResponse, ProtocolError> {
+        self.parse_object_info_response(input, ObjectInfoIs::deserialize)
+    }
+}
+impl CmdGetObjectInfo {
+    pub fn new(unique_id: u32, transaction_id: &mut u8, object_key: u32) -> Self {
+        let len = (std::mem::size_of::<Self>() - std::mem::size_of::<Header<HostCmd>>()) as u16;
+        Self {
+            header: Header::new(
+                HostCmd::CmdGetObjectInfo,
+                len,
+                unique_id,
+                TransactionId::new(transaction_id),
+            ),
+            object_key,
+        }
+    }
+
+    fn parse_object_info_response(
+        &self,
+        input: &[u8],
+        deserialize_object_info: fn(&[u8], u8, u32) -> Result<ObjectInfoIs, ProtocolError>,
+    ) -> Result<CmdGetObjectInfoResponse, ProtocolError> {
         // Check the very first byte to know which type of response we got
         let (_, cmd) = deserialize_cmd(input).map_err(|e| {
             ProtocolError::DeserializationError(format!("Failed to deserialize command: {:?}", e))
@@ -1092,8 +1289,8 @@ impl Command for CmdGetObjectInfo {
                 )?))
             }
             SecondaryCmd::CmdObjectInfoIs => {
-                log::debug!("Received counter value");
-                let response = ObjectInfoIs::deserialize(
+                log::debug!("Received object info");
+                let response = deserialize_object_info(
                     input,
                     self.header.transaction_id.value,
                     self.header.unique_id,
@@ -1110,18 +1307,28 @@ impl Command for CmdGetObjectInfo {
         }
     }
 }
-impl CmdGetObjectInfo {
+
+/// The same command as `CmdGetObjectInfo`, for the safe API which has no value for an
+/// unknown object type: the response is parsed with `ObjectInfoIs::deserialize_strict`
+pub struct CmdGetObjectInfoStrict(CmdGetObjectInfo);
+impl Serializer for CmdGetObjectInfoStrict {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+}
+impl Command for CmdGetObjectInfoStrict {
+    type Response = CmdGetObjectInfoResponse;
+    fn command_id(&self) -> u8 {
+        self.0.command_id()
+    }
+    fn parse_response(&self, input: &[u8]) -> Result<CmdGetObjectInfoResponse, ProtocolError> {
+        self.0
+            .parse_object_info_response(input, ObjectInfoIs::deserialize_strict)
+    }
+}
+impl CmdGetObjectInfoStrict {
     pub fn new(unique_id: u32, transaction_id: &mut u8, object_key: u32) -> Self {
-        let len = (std::mem::size_of::<Self>() - std::mem::size_of::<Header<HostCmd>>()) as u16;
-        Self {
-            header: Header::new(
-                HostCmd::CmdGetObjectInfo,
-                len,
-                unique_id,
-                TransactionId::new(transaction_id),
-            ),
-            object_key,
-        }
+        Self(CmdGetObjectInfo::new(unique_id, transaction_id, object_key))
     }
 }
 
@@ -1130,13 +1337,17 @@ pub enum CmdGetObjectCountResponse {
     ObjectCount { object_count: u16 },
 }
 #[repr(C, packed)]
-#[derive(serde::Serialize)]
 pub struct CmdGetObjectCount {
     header: Header<HostCmd>,
 }
-impl Serializer for CmdGetObjectCount {}
+impl Serializer for CmdGetObjectCount {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.header.to_bytes()
+    }
+}
 impl Command for CmdGetObjectCount {
     type Response = CmdGetObjectCountResponse;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<CmdGetObjectCountResponse, ProtocolError> {
         // Check the very first byte to know which type of response we got
         let (_, cmd) = deserialize_cmd(input).map_err(|e| {
@@ -1224,17 +1435,16 @@ impl ObjectCountIs {
     }
 }
 
-#[derive(serde::Serialize)]
 pub struct CmdWriteData {
     header: Header<HostCmd>,
     object_key: u32,
     offset: u16,
     last_frag: u8,
-    #[serde(skip_serializing)]
     data: Vec<u8>,
 }
 impl Command for CmdWriteData {
     type Response = StatusCode;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<StatusCode, ProtocolError> {
         parse_status_response(
             self.header.transaction_id.value,
@@ -1253,8 +1463,7 @@ impl CmdWriteData {
             data: vec![],
         };
 
-        let serialized = bincode::serialize(&base_struct).unwrap();
-        serialized.len() as u16
+        base_struct.to_bytes().len() as u16
     }
 
     pub fn new(
@@ -1284,17 +1493,73 @@ impl CmdWriteData {
     pub fn get_overhead() -> u16 {
         (std::mem::size_of::<Self>() - std::mem::size_of::<Header<HostCmd>>()) as u16
     }
+}
 
-    pub fn serialize(&mut self) -> Result<Vec<u8>, ProtocolError> {
-        let mut bytestream = match bincode::serialize(&self) {
-            Ok(bytestream) => bytestream,
-            Err(err) => return Err(ProtocolError::SerializationError(err.to_string())),
-        };
-        bytestream.append(&mut self.data);
-        Ok(bytestream)
+impl Serializer for CmdWriteData {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.object_key.to_le_bytes());
+        bytes.extend_from_slice(&self.offset.to_le_bytes());
+        bytes.push(self.last_frag);
+        bytes.extend_from_slice(&self.data);
+        bytes
     }
 }
 
+/// Split a write of `length` bytes in fragments of at most `fragment_size` bytes, as
+/// `(offset, fragment length, last fragment)`. An empty write is a single empty fragment
+/// so that the object is still created. A fragment size of 0, or a length whose offsets
+/// don't fit the 16 bits offset of a fragment, is rejected.
+pub fn fragment_offsets(
+    length: usize,
+    fragment_size: usize,
+) -> Result<impl Iterator<Item = (u16, usize, bool)>, ProtocolError> {
+    if fragment_size == 0 {
+        return Err(ProtocolError::SerializationError(
+            "The fragment size must not be 0".to_string(),
+        ));
+    }
+    // Every offset is below the length
+    if length > u16::MAX as usize + 1 {
+        return Err(ProtocolError::SerializationError(format!(
+            "A write of {} bytes can't be addressed with 16 bits offsets",
+            length
+        )));
+    }
+
+    let mut offset = 0;
+    let mut done = false;
+    Ok(std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let fragment_len = fragment_size.min(length - offset);
+        let fragment = (offset as u16, fragment_len, offset + fragment_len == length);
+        done = fragment.2;
+        offset += fragment_len;
+        Some(fragment)
+    }))
+}
+
+/// Same as `fragment_offsets`, with the data of each fragment.
+pub fn fragment(
+    data: &[u8],
+    fragment_size: usize,
+) -> Result<impl Iterator<Item = (u16, &[u8], bool)>, ProtocolError> {
+    let fragments = fragment_offsets(data.len(), fragment_size)?;
+    Ok(fragments.map(move |(offset, fragment_len, last_fragment)| {
+        let offset_usize = offset as usize;
+        (
+            offset,
+            &data[offset_usize..offset_usize + fragment_len],
+            last_fragment,
+        )
+    }))
+}
+
+/// The status frame answers every command that has no dedicated response, writes included.
+/// It does not echo the object key, so a status is only matched to its command by the
+/// unique ID and transaction ID of the header.
 pub struct StatusIs {
     pub status_code: StatusCode,
 }
@@ -1324,18 +1589,37 @@ impl StatusIs {
             Err(err) => Err(ProtocolError::DeserializationError(err.to_string())),
         }
     }
+
+    /// Extract the response type and the status value exactly as sent by the secondary,
+    /// before they get mapped to a `StatusCode`. Returns `None` if `input` is not a status frame.
+    pub fn raw_status(input: &[u8]) -> Option<(u8, u32)> {
+        let (remaining, header) = deserialize_header(input).ok()?;
+        let cmd = header.cmd;
+        if cmd != SecondaryCmd::CmdStatusIs {
+            return None;
+        }
+        let (remaining, response_type) =
+            nom::number::complete::u8::<_, Error<&[u8]>>(remaining).ok()?;
+        let (_, value) = nom::number::complete::le_u32::<_, Error<&[u8]>>(remaining).ok()?;
+        Some((response_type, value))
+    }
 }
 
-#[derive(serde::Serialize)]
 #[repr(C, packed)]
-
 pub struct CmdDeleteObject {
     header: Header<HostCmd>,
     object_key: u32,
 }
-impl Serializer for CmdDeleteObject {}
+impl Serializer for CmdDeleteObject {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.object_key.to_le_bytes());
+        bytes
+    }
+}
 impl Command for CmdDeleteObject {
     type Response = StatusCode;
+    header_command_id!();
     fn parse_response(&self, input: &[u8]) -> Result<StatusCode, ProtocolError> {
         parse_status_response(
             self.header.transaction_id.value,
@@ -1359,11 +1643,46 @@ impl CmdDeleteObject {
     }
 }
 
+#[repr(C, packed)]
+pub struct CmdNoop {
+    header: Header<HostCmd>,
+}
+impl Serializer for CmdNoop {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.header.to_bytes()
+    }
+}
+impl Command for CmdNoop {
+    type Response = StatusCode;
+    header_command_id!();
+    fn parse_response(&self, input: &[u8]) -> Result<StatusCode, ProtocolError> {
+        parse_status_response(
+            self.header.transaction_id.value,
+            self.header.unique_id,
+            input,
+        )
+    }
+}
+impl CmdNoop {
+    pub fn new(unique_id: u32, transaction_id: &mut u8) -> Self {
+        let len = (std::mem::size_of::<Self>() - std::mem::size_of::<Header<HostCmd>>()) as u16;
+        Self {
+            header: Header::new(
+                HostCmd::CmdNoop,
+                len,
+                unique_id,
+                TransactionId::new(transaction_id),
+            ),
+        }
+    }
+}
+
 fn deserialize_status_code(
     response_type: StatusIsResponseType,
     input: &[u8],
 ) -> nom::IResult<&[u8], StatusCode> {
-    let (remaining, value) = nom::number::complete::u32(nom::number::Endianness::Native)(input)?;
+    // The secondary sends the status value in little-endian, like every other field
+    let (remaining, value) = nom::number::complete::le_u32(input)?;
 
     match response_type {
         StatusIsResponseType::ResponseTypeSlStatus => {
@@ -1396,32 +1715,50 @@ fn deserialize_property_type(input: &[u8]) -> nom::IResult<&[u8], PropertyType>
     Ok((remaining, property_type))
 }
 
+// The value is checked against the size of the property before it is read, so that a
+// truncated frame names what is missing instead of failing somewhere in nom
+fn check_property_len(
+    property_type: PropertyType,
+    input: &[u8],
+    expected_len: usize,
+) -> Result<(), ProtocolError> {
+    if input.len() < expected_len {
+        return Err(ProtocolError::DeserializationError(format!(
+            "The {:?} property value is {} bytes, expected {} bytes",
+            property_type,
+            input.len(),
+            expected_len
+        )));
+    }
+    Ok(())
+}
+
 fn deserialize_property_value(
     property_type: PropertyType,
     input: &[u8],
-) -> nom::IResult<&[u8], PropertyValue> {
+) -> Result<PropertyValue, ProtocolError> {
     match property_type {
         PropertyType::MaxObjectSize => {
-            let (remaining, value) = nom::number::complete::le_u16(input)?;
-            Ok((remaining, PropertyValue::MaxObjectSize(value)))
+            check_property_len(property_type, input, std::mem::size_of::<u16>())?;
+            Ok(PropertyValue::MaxObjectSize(u16::from_le_bytes([
+                input[0], input[1],
+            ])))
         }
         PropertyType::MaxWriteSize => {
-            let (remaining, value) = nom::number::complete::le_u16(input)?;
-            Ok((remaining, PropertyValue::MaxWriteSize(value)))
+            check_property_len(property_type, input, std::mem::size_of::<u16>())?;
+            Ok(PropertyValue::MaxWriteSize(u16::from_le_bytes([
+                input[0], input[1],
+            ])))
         }
-        _ => {
+        PropertyType::Unknown => {
             log::error!("Unknown property type");
-            Err(Err::Error(Error::new(input, ErrorKind::NoneOf)))
+            Err(ProtocolError::DeserializationError(
+                "Received an unknown property type".to_string(),
+            ))
         }
     }
 }
 
-fn deserialize_object_type(input: &[u8]) -> nom::IResult<&[u8], CpcNvm3ObjectType> {
-    let (remaining, object_type) = nom::number::complete::u8(input)?;
-    let object_type = CpcNvm3ObjectType::from(object_type);
-    Ok((remaining, object_type))
-}
-
 fn deserialize_cmd(input: &[u8]) -> nom::IResult<&[u8], SecondaryCmd> {
     let (remaining, cmd) = nom::number::complete::u8(input)?;
     let cmd = SecondaryCmd::try_from(cmd).unwrap_or(SecondaryCmd::UnsupportedCmdIs);
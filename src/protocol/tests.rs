@@ -1,15 +1,22 @@
 use super::*;
-use crate::nvm3::init_logger;
-use crate::CpcNvm3LogLevel;
+use crate::nvm3::init_test_logger;
+
+// The responses are built with the header the secondary sends: cmd, the payload len in
+// little endian, the unique_id and the transaction_id. The len only accounts for what
+// follows the header, 5 bytes for a sl_status response.
 
 #[test]
 fn test_invalid_transaction_id_error() {
-    let _ = init_logger(CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let write_completed_response_with_invalid_transaction_id = vec![
-        0x01, // cmd
-        0x00, // len 1
-        0x06, // len 2
+        0x02, // cmd
+        0x05, // len 1
+        0x00, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
         0x00, // transaction_id
         0x00, // response_type sl_status
         0x00, // status byte 1
@@ -24,7 +31,7 @@ fn test_invalid_transaction_id_error() {
     let last_frag = 1;
     let data = vec![0u8; 1024];
     let cmd_write_data =
-        CmdWriteData::new(&mut transaction_id, object_key, offset, last_frag, data);
+        CmdWriteData::new(0, &mut transaction_id, object_key, offset, last_frag, data);
 
     match cmd_write_data.parse_response(&write_completed_response_with_invalid_transaction_id) {
         Err(ProtocolError::InvalidTransactionId(expected, actual)) => {
@@ -41,12 +48,16 @@ fn test_invalid_transaction_id_error() {
 
 #[test]
 fn test_invalid_command_id_error() {
-    let _ = init_logger(CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let write_completed_response_with_invalid_command_id = vec![
         0x00, // cmd
-        0x00, // len 1
-        0x06, // len 2
+        0x05, // len 1
+        0x00, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
         0x01, // transaction_id
         0x00, // response_type sl_status
         0x00, // status byte 1
@@ -61,7 +72,7 @@ fn test_invalid_command_id_error() {
     let last_frag = 1;
     let data = vec![0u8; 1024];
     let cmd_write_data =
-        CmdWriteData::new(&mut transaction_id, object_key, offset, last_frag, data);
+        CmdWriteData::new(0, &mut transaction_id, object_key, offset, last_frag, data);
 
     match cmd_write_data.parse_response(&write_completed_response_with_invalid_command_id) {
         Err(ProtocolError::InvalidCommandId) => {
@@ -76,12 +87,16 @@ fn test_invalid_command_id_error() {
 }
 #[test]
 fn test_invalid_transaction_id_wrap_around() {
-    let _ = init_logger(CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let write_completed_response_with_overflowed_transaction_id = vec![
         0x02, // cmd
-        0x06, // len 1
+        0x05, // len 1
         0x00, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
         0x00, // transaction_id
         0x00, // response_type sl_status
         0x00, // status byte 1
@@ -96,7 +111,7 @@ fn test_invalid_transaction_id_wrap_around() {
     let last_frag = 1;
     let data = vec![0u8; 1024];
     let cmd_write_data =
-        CmdWriteData::new(&mut transaction_id, object_key, offset, last_frag, data);
+        CmdWriteData::new(0, &mut transaction_id, object_key, offset, last_frag, data);
     cmd_write_data
         .parse_response(&write_completed_response_with_overflowed_transaction_id)
         .unwrap();
@@ -104,12 +119,16 @@ fn test_invalid_transaction_id_wrap_around() {
 
 #[test]
 fn test_invalid_response_len() {
-    let _ = init_logger(CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let write_completed_response_with_invalid_len = vec![
         0x02, // cmd
         0x00, // len 1
         0x10, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
         0x01, // transaction_id
         0x00, // response_type sl_status
         0x00, // status byte 1
@@ -124,11 +143,11 @@ fn test_invalid_response_len() {
     let last_frag = 1;
     let data = vec![0u8; 1024];
     let cmd_write_data =
-        CmdWriteData::new(&mut transaction_id, object_key, offset, last_frag, data);
+        CmdWriteData::new(0, &mut transaction_id, object_key, offset, last_frag, data);
 
     match cmd_write_data.parse_response(&write_completed_response_with_invalid_len) {
         Err(ProtocolError::InvalidResponseLen(expected, actual)) => {
-            assert_eq!(expected, 6);
+            assert_eq!(expected, 5);
             assert_eq!(actual, 4096);
         }
         Err(err) => {
@@ -141,12 +160,16 @@ fn test_invalid_response_len() {
 
 #[test]
 fn test_valid_write_completed_response() {
-    let _ = init_logger(CpcNvm3LogLevel::CPC_NVM3_LOG_DEBUG, None).ok();
+    init_test_logger();
 
     let write_completed_response_with_invalid_len = vec![
         0x02, // cmd
-        0x06, // len 1
+        0x05, // len 1
         0x00, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
         0x01, // transaction_id
         0x00, // response_type sl_status
         0x00, // status byte 1
@@ -161,9 +184,393 @@ fn test_valid_write_completed_response() {
     let last_frag = 1;
     let data = vec![0u8; 1024];
     let cmd_write_data =
-        CmdWriteData::new(&mut transaction_id, object_key, offset, last_frag, data);
+        CmdWriteData::new(0, &mut transaction_id, object_key, offset, last_frag, data);
 
     cmd_write_data
         .parse_response(&write_completed_response_with_invalid_len)
         .unwrap();
 }
+
+#[test]
+fn test_truncated_response() {
+    init_test_logger();
+
+    let truncated_response = vec![
+        0x02, // cmd
+        0x05, // len 1
+        0x00, // len 2
+    ];
+
+    let mut transaction_id: u8 = 0;
+    let object_key: u32 = 1234;
+    let offset: u16 = 100;
+    let last_frag = 1;
+    let data = vec![0u8; 1024];
+    let cmd_write_data =
+        CmdWriteData::new(0, &mut transaction_id, object_key, offset, last_frag, data);
+
+    match cmd_write_data.parse_response(&truncated_response) {
+        Err(ProtocolError::DeserializationError(_)) => {}
+        Err(err) => {
+            log::error!("Error details: {:?}", err);
+            panic!("Expected DeserializationError error");
+        }
+        _ => panic!("Expected DeserializationError error"),
+    }
+}
+
+#[test]
+fn test_status_code_is_little_endian() {
+    init_test_logger();
+
+    let status_response = |response_type: u8, value: u32| {
+        let mut response = vec![
+            0x02, // cmd
+            0x05, // len 1
+            0x00, // len 2
+            0x00, // unique_id
+            0x00, // unique_id
+            0x00, // unique_id
+            0x00, // unique_id
+            0x01, // transaction_id
+            response_type,
+        ];
+        response.extend_from_slice(&value.to_le_bytes());
+        response
+    };
+
+    let mut transaction_id: u8 = 0;
+    let cmd_delete_object = CmdDeleteObject::new(0, &mut transaction_id, 1234);
+
+    match cmd_delete_object
+        .parse_response(&status_response(0x00, SlStatus::Busy as u32))
+        .unwrap()
+    {
+        StatusCode::SlStatus(sl_status) => assert_eq!(sl_status, SlStatus::Busy),
+        _ => panic!("Expected a sl_status response"),
+    }
+
+    match cmd_delete_object
+        .parse_response(&status_response(0x01, ECode::KeyNotFound as u32))
+        .unwrap()
+    {
+        StatusCode::ECode(e_code) => assert_eq!(e_code, ECode::KeyNotFound),
+        _ => panic!("Expected an ecode response"),
+    }
+}
+
+#[test]
+fn test_command_byte_layout() {
+    init_test_logger();
+
+    let unique_id: u32 = 0x1122_3344;
+    let header = |cmd: u8, len: u16| {
+        vec![
+            cmd,
+            len as u8,        // len 1
+            (len >> 8) as u8, // len 2
+            0x44,             // unique_id
+            0x33,             // unique_id
+            0x22,             // unique_id
+            0x11,             // unique_id
+            0x01,             // transaction_id
+        ]
+    };
+    let object_key: u32 = 0xA1B2_C3D4;
+    let object_key_bytes = [0xD4, 0xC3, 0xB2, 0xA1];
+
+    let mut transaction_id: u8 = 0;
+    assert_eq!(
+        GetVersion::new(unique_id, &mut transaction_id).to_bytes(),
+        header(0x00, 0)
+    );
+
+    let mut transaction_id: u8 = 0;
+    assert_eq!(
+        CmdNoop::new(unique_id, &mut transaction_id).to_bytes(),
+        header(0x03, 0)
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x04, 1);
+    expected.push(0x02); // property_type
+    assert_eq!(
+        PropValueGet::new(unique_id, &mut transaction_id, PropertyType::MaxWriteSize).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x06, 10);
+    expected.extend_from_slice(&object_key_bytes);
+    expected.extend_from_slice(&[0x34, 0x12]); // offset
+    expected.push(0x01); // last_frag
+    expected.extend_from_slice(&[0xAA, 0xBB, 0xCC]); // data
+    assert_eq!(
+        CmdWriteData::new(
+            unique_id,
+            &mut transaction_id,
+            object_key,
+            0x1234,
+            1,
+            vec![0xAA, 0xBB, 0xCC]
+        )
+        .to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x08, 6);
+    expected.extend_from_slice(&object_key_bytes);
+    expected.extend_from_slice(&[0xFF, 0x00]); // max_read_size
+    assert_eq!(
+        CmdReadData::new(unique_id, &mut transaction_id, object_key, 0x00FF).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x0A, 4);
+    expected.extend_from_slice(&object_key_bytes);
+    assert_eq!(
+        CmdGetObjectInfo::new(unique_id, &mut transaction_id, object_key).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x0C, 4);
+    expected.extend_from_slice(&object_key_bytes);
+    assert_eq!(
+        CmdReadCounter::new(unique_id, &mut transaction_id, object_key).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x0E, 8);
+    expected.extend_from_slice(&object_key_bytes);
+    expected.extend_from_slice(&[0x78, 0x56, 0x34, 0x12]); // data
+    assert_eq!(
+        CmdWriteCounter::new(unique_id, &mut transaction_id, object_key, 0x1234_5678).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x0F, 4);
+    expected.extend_from_slice(&object_key_bytes);
+    assert_eq!(
+        CmdIncrementCounter::new(unique_id, &mut transaction_id, object_key).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x10, 4);
+    expected.extend_from_slice(&object_key_bytes);
+    assert_eq!(
+        CmdDeleteObject::new(unique_id, &mut transaction_id, object_key).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    let mut expected = header(0x11, 2);
+    expected.extend_from_slice(&[0x02, 0x01]); // max_objects
+    assert_eq!(
+        CmdEnumerateObjects::new(unique_id, &mut transaction_id, 0x0102).to_bytes(),
+        expected
+    );
+
+    let mut transaction_id: u8 = 0;
+    assert_eq!(
+        CmdGetObjectCount::new(unique_id, &mut transaction_id).to_bytes(),
+        header(0x13, 0)
+    );
+}
+
+#[test]
+fn test_strict_object_type() {
+    init_test_logger();
+
+    let object_info_response = |object_type: u8| {
+        vec![
+            0x0B, // cmd
+            0x03, // len 1
+            0x00, // len 2
+            0x00, // unique_id
+            0x00, // unique_id
+            0x00, // unique_id
+            0x00, // unique_id
+            0x01, // transaction_id
+            object_type,
+            0x10, // object_size
+            0x00, // object_size
+        ]
+    };
+
+    assert!(matches!(
+        CpcNvm3ObjectType::try_from(0x7F),
+        Err(ProtocolError::InvalidObjectType(0x7F))
+    ));
+    assert_eq!(
+        CpcNvm3ObjectType::from_lenient(0x7F),
+        CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN
+    );
+
+    let lenient = ObjectInfoIs::deserialize(&object_info_response(0x7F), 1, 0).unwrap();
+    assert_eq!(
+        { lenient.object_type },
+        CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN
+    );
+    assert!(matches!(
+        ObjectInfoIs::deserialize_strict(&object_info_response(0x7F), 1, 0),
+        Err(ProtocolError::InvalidObjectType(0x7F))
+    ));
+
+    // The command of the C boundary stays lenient
+    let mut transaction_id: u8 = 0;
+    let cmd_get_object_info = CmdGetObjectInfo::new(0, &mut transaction_id, 1234);
+    assert!(matches!(
+        cmd_get_object_info.parse_response(&object_info_response(0x7F)),
+        Ok(CmdGetObjectInfoResponse::ObjectInfo {
+            object_type: CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_UNKNOWN,
+            ..
+        })
+    ));
+
+    let mut transaction_id: u8 = 0;
+    let cmd_get_object_info = CmdGetObjectInfoStrict::new(0, &mut transaction_id, 1234);
+    assert!(matches!(
+        cmd_get_object_info.parse_response(&object_info_response(0x7F)),
+        Err(ProtocolError::InvalidObjectType(0x7F))
+    ));
+    match cmd_get_object_info
+        .parse_response(&object_info_response(0x01))
+        .unwrap()
+    {
+        CmdGetObjectInfoResponse::ObjectInfo {
+            object_type,
+            object_size,
+        } => {
+            assert_eq!(object_type, CpcNvm3ObjectType::CPC_NVM3_OBJECT_TYPE_COUNTER);
+            assert_eq!(object_size, 0x10);
+        }
+        _ => panic!("Expected an object info response"),
+    }
+}
+
+#[test]
+fn test_truncated_property_value() {
+    init_test_logger();
+
+    let truncated_prop_value_is = vec![
+        0x05, // cmd
+        0x02, // len 1
+        0x00, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x01, // transaction_id
+        0x02, // property_type
+        0xFF, // value, its second byte is missing
+    ];
+
+    match PropValueIs::deserialize(&truncated_prop_value_is, 1, 0) {
+        Err(ProtocolError::DeserializationError(message)) => {
+            assert!(message.contains("MaxWriteSize"), "{}", message);
+            assert!(message.contains("expected 2 bytes"), "{}", message);
+        }
+        Err(err) => panic!("Expected DeserializationError error, got {:?}", err),
+        Ok(_) => panic!("Expected DeserializationError error"),
+    }
+}
+
+#[test]
+fn test_trailing_bytes() {
+    init_test_logger();
+
+    let mut counter_is = vec![
+        0x0D, // cmd
+        0x04, // len 1
+        0x00, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x01, // transaction_id
+        0x2A, // value
+        0x00, // value
+        0x00, // value
+        0x00, // value
+    ];
+    check_trailing_bytes(&counter_is).unwrap();
+
+    counter_is.push(0xFF);
+    counter_is[1] += 1; // len
+
+    // The deserializer itself stays lenient
+    assert_eq!(
+        { CounterIs::deserialize(&counter_is, 1, 0).unwrap().data },
+        42
+    );
+    assert!(matches!(
+        check_trailing_bytes(&counter_is),
+        Err(ProtocolError::TrailingBytes(SecondaryCmd::CmdCounterIs, 1))
+    ));
+
+    // The size of a data response depends on the object, it never has trailing bytes
+    let read_data_is = vec![
+        0x09, // cmd
+        0x03, // len 1
+        0x00, // len 2
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x00, // unique_id
+        0x01, // transaction_id
+        0x01, // last_frag
+        0xAA, // data
+        0xBB, // data
+    ];
+    check_trailing_bytes(&read_data_is).unwrap();
+}
+
+#[test]
+fn test_fragment() {
+    let collect = |data: &[u8], fragment_size| -> Vec<(u16, Vec<u8>, bool)> {
+        fragment(data, fragment_size)
+            .unwrap()
+            .map(|(offset, data, last)| (offset, data.to_vec(), last))
+            .collect()
+    };
+
+    // An empty write still sends a single, last, fragment
+    assert_eq!(collect(&[], 4), vec![(0, vec![], true)]);
+    assert_eq!(collect(&[1], 4), vec![(0, vec![1], true)]);
+    assert_eq!(
+        collect(&[1, 2, 3, 4, 5, 6, 7, 8], 4),
+        vec![(0, vec![1, 2, 3, 4], false), (4, vec![5, 6, 7, 8], true)]
+    );
+    assert_eq!(
+        collect(&[1, 2, 3, 4, 5, 6, 7, 8, 9], 4),
+        vec![
+            (0, vec![1, 2, 3, 4], false),
+            (4, vec![5, 6, 7, 8], false),
+            (8, vec![9], true)
+        ]
+    );
+    assert_eq!(
+        fragment_offsets(0x200, 0xF7).unwrap().collect::<Vec<_>>(),
+        vec![(0, 0xF7, false), (0xF7, 0xF7, false), (0x1EE, 0x12, true)]
+    );
+
+    // The last offset of the largest write still fits in 16 bits
+    assert_eq!(
+        fragment_offsets(0x10000, 0x8000).unwrap().last(),
+        Some((0x8000, 0x8000, true))
+    );
+    assert!(matches!(
+        fragment_offsets(0x10001, 0x8000),
+        Err(ProtocolError::SerializationError(_))
+    ));
+    assert!(matches!(
+        fragment_offsets(8, 0),
+        Err(ProtocolError::SerializationError(_))
+    ));
+}